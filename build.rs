@@ -27,12 +27,27 @@ fn build_bdwgc() {
     build
         .pic(true)
         .define("BUILD_SHARED_LIBS", "OFF")
-        .define("enable_parallel_mark", "Off")
         .cflag("-DGC_ALWAYS_MULTITHREADED");
 
+    #[cfg(not(feature = "parallel-mark"))]
+    build.define("enable_parallel_mark", "Off");
+
+    #[cfg(feature = "parallel-mark")]
+    build.define("enable_parallel_mark", "ON");
+
     #[cfg(feature = "gc-assertions")]
     build.define("enable_gc_assertions", "ON");
 
+    // Make BDWGC define `pthread_create` and friends itself, forwarding to the
+    // real libc implementations found via `dlsym(RTLD_NEXT, ...)`.
+    #[cfg(feature = "interpose-threads")]
+    build.cflag("-DGC_USE_DLOPEN_WRAP");
+
+    // Sweep every block at the end of each collection instead of lazily
+    // during subsequent allocations.
+    #[cfg(feature = "eager-sweep")]
+    build.cflag("-DEAGER_SWEEP");
+
     #[cfg(not(feature = "gc-debug"))]
     build.profile("Release");
 
@@ -43,6 +58,8 @@ fn build_bdwgc() {
 
     println!("cargo:rustc-link-search=native={}", &build_dir.display());
     println!("cargo:rustc-link-lib=static=gc");
+    #[cfg(feature = "interpose-threads")]
+    println!("cargo:rustc-link-lib=dylib=dl");
 }
 
 fn main() {
@@ -0,0 +1,105 @@
+//! Pinning the parallel marker threads to chosen CPUs (Linux only).
+//!
+//! BDWGC has no hook that runs on a marker thread when it starts, and does
+//! not expose the markers' thread handles. On Linux it does name each marker
+//! `GC-marker-<n>`, so the markers are found by name among the process's
+//! tasks in `/proc/self/task` and pinned from outside with
+//! `sched_setaffinity`.
+
+use core::mem;
+
+const MARKER_PREFIX: &[u8] = b"GC-marker-";
+const TASK_DIR: &[u8] = b"/proc/self/task/";
+
+/// Pin every parallel marker thread to one CPU from `cpus`: marker `n` goes
+/// to `cpus[n % cpus.len()]`, so the list is used round-robin. Returns the
+/// number of markers pinned.
+///
+/// The markers are started first if they are not running yet (see
+/// [`crate::start_mark_threads`]). Markers started later, e.g. by
+/// [`crate::after_fork_child`] in a forked child, are new threads and must
+/// be pinned again. CPU numbers beyond what `cpu_set_t` can hold are
+/// skipped, as are markers the kernel refuses to move (e.g. because a CPU is
+/// offline or outside the process's cpuset).
+pub fn set_marker_affinity(cpus: &[usize]) -> usize {
+    if cpus.is_empty() {
+        return 0;
+    }
+    crate::start_mark_threads();
+
+    let dir = unsafe { libc::opendir(c"/proc/self/task".as_ptr()) };
+    if dir.is_null() {
+        return 0;
+    }
+    let mut pinned = 0;
+    loop {
+        let entry = unsafe { libc::readdir(dir) };
+        if entry.is_null() {
+            break;
+        }
+        let name = unsafe { core::ffi::CStr::from_ptr((*entry).d_name.as_ptr()) }.to_bytes();
+        let Some(tid) = parse_decimal(name) else {
+            continue;
+        };
+        let Some(marker) = marker_id(name) else {
+            continue;
+        };
+        let cpu = cpus[marker % cpus.len()];
+        if cpu >= libc::CPU_SETSIZE as usize {
+            continue;
+        }
+        let ret = unsafe {
+            let mut set: libc::cpu_set_t = mem::zeroed();
+            libc::CPU_SET(cpu, &mut set);
+            libc::sched_setaffinity(tid as libc::pid_t, mem::size_of_val(&set), &set)
+        };
+        if ret == 0 {
+            pinned += 1;
+        }
+    }
+    unsafe { libc::closedir(dir) };
+    pinned
+}
+
+/// The marker number of task `tid` (its directory name under `TASK_DIR`),
+/// read from its `comm`, or `None` if it is not a marker.
+fn marker_id(tid: &[u8]) -> Option<usize> {
+    const COMM: &[u8] = b"/comm\0";
+
+    let mut path = [0u8; 64];
+    let len = TASK_DIR.len() + tid.len() + COMM.len();
+    if len > path.len() {
+        return None;
+    }
+    path[..TASK_DIR.len()].copy_from_slice(TASK_DIR);
+    path[TASK_DIR.len()..][..tid.len()].copy_from_slice(tid);
+    path[TASK_DIR.len() + tid.len()..len].copy_from_slice(COMM);
+
+    let mut comm = [0u8; 32];
+    let n = unsafe {
+        let fd = libc::open(path.as_ptr() as *const libc::c_char, libc::O_RDONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return None;
+        }
+        let n = libc::read(fd, comm.as_mut_ptr() as *mut libc::c_void, comm.len());
+        libc::close(fd);
+        n
+    };
+    if n <= 0 {
+        return None;
+    }
+    let comm = comm[..n as usize].strip_suffix(b"\n").unwrap_or(&comm[..n as usize]);
+    parse_decimal(comm.strip_prefix(MARKER_PREFIX)?)
+}
+
+fn parse_decimal(digits: &[u8]) -> Option<usize> {
+    if digits.is_empty() {
+        return None;
+    }
+    digits.iter().try_fold(0usize, |n, &d| {
+        if !d.is_ascii_digit() {
+            return None;
+        }
+        n.checked_mul(10)?.checked_add((d - b'0') as usize)
+    })
+}
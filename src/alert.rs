@@ -0,0 +1,231 @@
+//! Threshold alerts on the collector's statistics.
+
+use core::mem;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+/// Maximum number of rules an [`AlertMonitor`] can hold.
+pub const MAX_ALERT_RULES: usize = 8;
+
+// Alerts fired but not yet passed to the callback. Should they pile up,
+// the oldest are dropped.
+const MAX_QUEUED: usize = 2 * MAX_ALERT_RULES;
+
+/// Fraction of the threshold by which a metric must move back before a
+/// raised alert clears, so that a metric hovering around the threshold does
+/// not raise it over and over.
+pub const ALERT_HYSTERESIS: f64 = 0.1;
+
+/// A statistic an alert rule can watch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertMetric {
+    /// Free and unmapped bytes as a percentage of the heap; alerts when
+    /// below the threshold.
+    FreePercent,
+    /// Collections per minute since the previous sample; alerts when above
+    /// the threshold.
+    CollectionsPerMinute,
+    /// Bytes unmapped to the OS; alerts when above the threshold.
+    UnmappedBytes,
+    /// Heap size in bytes; alerts when above the threshold.
+    HeapSize,
+}
+
+impl AlertMetric {
+    fn alerts_below(self) -> bool {
+        self == AlertMetric::FreePercent
+    }
+}
+
+/// A rule crossing its threshold, or moving back past it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Alert {
+    pub metric: AlertMetric,
+    pub value: f64,
+    pub threshold: f64,
+    /// `true` when the alert is raised, `false` when it clears.
+    pub raised: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Rule {
+    metric: AlertMetric,
+    threshold: f64,
+    raised: bool,
+}
+
+struct State {
+    callback: Option<fn(Alert)>,
+    rules: [Option<Rule>; MAX_ALERT_RULES],
+    // Time and collection count of the previous sample.
+    last: Option<(Duration, usize)>,
+    queued: [Option<Alert>; MAX_QUEUED],
+    n_queued: usize,
+}
+
+static LOCKED: AtomicBool = AtomicBool::new(false);
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static mut STATE: State = State {
+    callback: None,
+    rules: [None; MAX_ALERT_RULES],
+    last: None,
+    queued: [None; MAX_QUEUED],
+    n_queued: 0,
+};
+
+/// A set of threshold rules on the collector's statistics, evaluated after
+/// every collection once [`install`](AlertMonitor::install)ed.
+///
+/// The callback is called once when a rule's metric crosses its threshold
+/// and once more when it has moved back past it by [`ALERT_HYSTERESIS`] of
+/// the threshold. The rules are evaluated on the first allocation after a
+/// collection, or on return from [`crate::collect`] or
+/// [`crate::run_pending_gc_hooks`] if that comes first. The callback is
+/// only called where the post-collection hook runs, alongside rather than
+/// instead of it, never from inside an allocation: alerts fired meanwhile
+/// are queued, up to twice [`MAX_ALERT_RULES`] of them.
+///
+/// ```ignore
+/// AlertMonitor::new(report)
+///     .rule(AlertMetric::FreePercent, 5.0)
+///     .rule(AlertMetric::CollectionsPerMinute, 600.0)
+///     .install();
+/// ```
+pub struct AlertMonitor {
+    callback: fn(Alert),
+    rules: [Option<Rule>; MAX_ALERT_RULES],
+}
+
+impl AlertMonitor {
+    pub fn new(callback: fn(Alert)) -> Self {
+        AlertMonitor { callback, rules: [None; MAX_ALERT_RULES] }
+    }
+
+    /// Add a rule on `metric`. Panics if [`MAX_ALERT_RULES`] are already
+    /// held.
+    pub fn rule(mut self, metric: AlertMetric, threshold: f64) -> Self {
+        let slot = self.rules.iter_mut().find(|r| r.is_none()).expect("too many alert rules");
+        *slot = Some(Rule { metric, threshold, raised: false });
+        self
+    }
+
+    /// Start evaluating the rules, replacing those of any monitor installed
+    /// before.
+    pub fn install(self) {
+        lock();
+        let state = &raw mut STATE;
+        unsafe {
+            (*state).callback = Some(self.callback);
+            (*state).rules = self.rules;
+            (*state).last = None;
+            (*state).n_queued = 0;
+        }
+        unlock();
+        ACTIVE.store(true, Ordering::Release);
+        crate::events::install();
+    }
+
+    /// Stop evaluating the installed rules. Alerts still queued are dropped.
+    pub fn uninstall() {
+        ACTIVE.store(false, Ordering::Release);
+    }
+}
+
+#[inline]
+pub(crate) fn alerts_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+pub(crate) fn check_alerts() {
+    if !alerts_active() {
+        return;
+    }
+    let Ok(stats) = crate::get_prof_stats() else {
+        return;
+    };
+    let now = crate::now();
+    // Another thread is evaluating the same collections.
+    if LOCKED.swap(true, Ordering::Acquire) {
+        return;
+    }
+    let state = &raw mut STATE;
+    let state = unsafe { &mut *state };
+    let per_minute = state.last.map(|(then, gc_no)| {
+        let minutes = (now - then).as_secs_f64() / 60.0;
+        let collections = stats.gc_no.wrapping_sub(gc_no) as f64;
+        if minutes > 0.0 {
+            collections / minutes
+        } else {
+            0.0
+        }
+    });
+    state.last = Some((now, stats.gc_no));
+
+    for rule in state.rules.iter_mut().flatten() {
+        let value = match rule.metric {
+            AlertMetric::FreePercent if stats.heapsize_full == 0 => 100.0,
+            AlertMetric::FreePercent => {
+                stats.free_bytes_full as f64 * 100.0 / stats.heapsize_full as f64
+            }
+            AlertMetric::CollectionsPerMinute => match per_minute {
+                Some(rate) => rate,
+                None => continue,
+            },
+            AlertMetric::UnmappedBytes => stats.unmapped_bytes as f64,
+            AlertMetric::HeapSize => stats.heapsize_full as f64,
+        };
+        let margin = rule.threshold.abs() * ALERT_HYSTERESIS;
+        let (crossed, recovered) = if rule.metric.alerts_below() {
+            (value < rule.threshold, value > rule.threshold + margin)
+        } else {
+            (value > rule.threshold, value < rule.threshold - margin)
+        };
+        if (!rule.raised && crossed) || (rule.raised && recovered) {
+            rule.raised = !rule.raised;
+            let alert = Alert {
+                metric: rule.metric,
+                value,
+                threshold: rule.threshold,
+                raised: rule.raised,
+            };
+            if state.n_queued == MAX_QUEUED {
+                state.queued.rotate_left(1);
+                state.n_queued -= 1;
+            }
+            state.queued[state.n_queued] = Some(alert);
+            state.n_queued += 1;
+        }
+    }
+    unlock();
+}
+
+/// Pass the alerts queued by [`check_alerts`] to the callback. Only called
+/// where the post-collection hook runs.
+pub(crate) fn run_queued_alerts() {
+    if !alerts_active() {
+        return;
+    }
+    lock();
+    let state = &raw mut STATE;
+    let state = unsafe { &mut *state };
+    let queued = state.queued;
+    let n_queued = mem::take(&mut state.n_queued);
+    let callback = state.callback;
+    unlock();
+
+    // Called outside the lock, so that the callback may install a new
+    // monitor.
+    if let Some(callback) = callback {
+        queued[..n_queued].iter().flatten().copied().for_each(callback);
+    }
+}
+
+fn lock() {
+    while LOCKED.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+        core::hint::spin_loop();
+    }
+}
+
+fn unlock() {
+    LOCKED.store(false, Ordering::Release);
+}
@@ -0,0 +1,95 @@
+//! Type-erased GC objects with a checked downcast.
+
+use core::alloc::Layout;
+use core::any::TypeId;
+use core::ptr::{self, NonNull};
+
+#[repr(C)]
+struct Header {
+    type_id: TypeId,
+    drop_value: unsafe fn(*mut u8),
+    value_offset: usize,
+}
+
+/// A handle to a GC object of any `'static` type, recording the type in a
+/// header in front of the value so that it can be recovered with a checked
+/// [`downcast`](GcAny::downcast).
+///
+/// If the type needs dropping, a finalizer drops the value once the object
+/// is unreachable. Like any pointer to GC memory, the handle must be kept
+/// where the collector scans it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GcAny {
+    header: NonNull<Header>,
+}
+
+impl GcAny {
+    /// Move `value` into a new GC object. Returns `None` if the allocation
+    /// fails.
+    pub fn new<T: 'static>(value: T) -> Option<Self> {
+        let (layout, value_offset) = Layout::new::<Header>().extend(Layout::new::<T>()).ok()?;
+        let header = NonNull::new(unsafe { crate::gc_malloc(layout) })?.cast::<Header>();
+        unsafe {
+            header.as_ptr().write(Header {
+                type_id: TypeId::of::<T>(),
+                drop_value: drop_value::<T>,
+                value_offset,
+            });
+            (header.as_ptr() as *mut u8).add(value_offset).cast::<T>().write(value);
+            if core::mem::needs_drop::<T>() {
+                crate::finalize::drop_when_unreachable::<Header>(
+                    header.as_ptr() as *mut u8,
+                    ptr::null_mut(),
+                );
+            }
+        }
+        Some(GcAny { header })
+    }
+
+    /// Recover a handle from the address returned by [`as_ptr`]. Returns
+    /// `None` if `ptr` is not the base of a GC object.
+    ///
+    /// # Safety
+    ///
+    /// If `ptr` is the base of a GC object, that object must have been
+    /// created by [`GcAny::new`].
+    ///
+    /// [`as_ptr`]: GcAny::as_ptr
+    pub unsafe fn from_ptr(ptr: *const u8) -> Option<Self> {
+        let base = crate::base_of(ptr)?;
+        if base.as_ptr() as *const u8 != ptr {
+            return None;
+        }
+        Some(GcAny { header: base.cast() })
+    }
+
+    /// The base address of the object.
+    pub fn as_ptr(self) -> *const u8 {
+        self.header.as_ptr() as *const u8
+    }
+
+    /// Whether the value is a `T`.
+    pub fn is<T: 'static>(self) -> bool {
+        unsafe { (*self.header.as_ptr()).type_id == TypeId::of::<T>() }
+    }
+
+    /// A pointer to the value if it is a `T`, `None` otherwise.
+    pub fn downcast<T: 'static>(self) -> Option<NonNull<T>> {
+        if !self.is::<T>() {
+            return None;
+        }
+        let offset = unsafe { (*self.header.as_ptr()).value_offset };
+        Some(unsafe { self.header.cast::<u8>().add(offset).cast() })
+    }
+}
+
+unsafe fn drop_value<T>(value: *mut u8) {
+    unsafe { ptr::drop_in_place(value as *mut T) };
+}
+
+impl crate::finalize::DropGlue for Header {
+    unsafe fn drop(obj: *mut u8, _: *mut u8) {
+        let header = unsafe { &*(obj as *const Header) };
+        unsafe { (header.drop_value)(obj.add(header.value_offset)) };
+    }
+}
@@ -0,0 +1,69 @@
+//! `allocator_api2::alloc::Allocator` for the crate's allocators, for crates
+//! (e.g. hashbrown) that take their allocator through `allocator-api2`
+//! rather than the unstable `core::alloc::Allocator`.
+//!
+//! Every method forwards to the `core::alloc::Allocator` implementation, so
+//! both traits share the same allocation, growth and deallocation paths.
+
+use allocator_api2::alloc::{AllocError, Allocator};
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use crate::{AtomicGcAllocator, GcAllocator};
+
+macro_rules! forward_allocator {
+    ($t:ty) => {
+        unsafe impl Allocator for $t {
+            #[inline]
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                core::alloc::Allocator::allocate(self, layout).map_err(|_| AllocError)
+            }
+
+            #[inline]
+            fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                core::alloc::Allocator::allocate_zeroed(self, layout).map_err(|_| AllocError)
+            }
+
+            #[inline]
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                unsafe { core::alloc::Allocator::deallocate(self, ptr, layout) }
+            }
+
+            #[inline]
+            unsafe fn grow(
+                &self,
+                ptr: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> Result<NonNull<[u8]>, AllocError> {
+                unsafe { core::alloc::Allocator::grow(self, ptr, old_layout, new_layout) }
+                    .map_err(|_| AllocError)
+            }
+
+            #[inline]
+            unsafe fn grow_zeroed(
+                &self,
+                ptr: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> Result<NonNull<[u8]>, AllocError> {
+                unsafe { core::alloc::Allocator::grow_zeroed(self, ptr, old_layout, new_layout) }
+                    .map_err(|_| AllocError)
+            }
+
+            #[inline]
+            unsafe fn shrink(
+                &self,
+                ptr: NonNull<u8>,
+                old_layout: Layout,
+                new_layout: Layout,
+            ) -> Result<NonNull<[u8]>, AllocError> {
+                unsafe { core::alloc::Allocator::shrink(self, ptr, old_layout, new_layout) }
+                    .map_err(|_| AllocError)
+            }
+        }
+    };
+}
+
+forward_allocator!(GcAllocator);
+forward_allocator!(AtomicGcAllocator);
@@ -0,0 +1,98 @@
+//! Bump-allocated groups of objects finalized together.
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::{self, NonNull};
+
+#[repr(C)]
+struct Header {
+    drop_elements: unsafe fn(*mut u8, usize),
+    len: usize,
+    capacity: usize,
+    elements: usize,
+}
+
+/// A single GC object holding up to `capacity` values of `T`, whose
+/// destructors all run from one finalizer when the arena is collected.
+///
+/// Registering one finalizer for a whole group of objects with a common
+/// lifetime is much cheaper than registering one per object. A pointer to
+/// any element keeps the whole arena alive, so the destructors only run
+/// once none of the elements is reachable. They run in allocation order,
+/// each exactly once, and in no particular order relative to other
+/// finalizers: a `T` whose destructor touches other GC objects must not rely
+/// on them not having been finalized already.
+///
+/// The handle itself is a plain pointer to the arena and must be kept where
+/// the collector scans it (the stack, or GC memory) for as long as it is
+/// used.
+pub struct GcArena<T> {
+    header: NonNull<Header>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> GcArena<T> {
+    /// Allocate an empty arena with room for `capacity` values. Returns
+    /// `None` if the allocation fails.
+    pub fn with_capacity(capacity: usize) -> Option<Self> {
+        let (layout, elements) =
+            Layout::new::<Header>().extend(Layout::array::<T>(capacity).ok()?).ok()?;
+        let header = NonNull::new(unsafe { crate::gc_malloc(layout) })?.cast::<Header>();
+        unsafe {
+            header.as_ptr().write(Header {
+                drop_elements: drop_elements::<T>,
+                len: 0,
+                capacity,
+                elements,
+            });
+            if mem::needs_drop::<T>() {
+                crate::finalize::drop_when_unreachable::<Header>(
+                    header.as_ptr() as *mut u8,
+                    ptr::null_mut(),
+                );
+            }
+        }
+        Some(GcArena { header, _marker: PhantomData })
+    }
+
+    /// Move `value` into the arena, handing it back if the arena is full.
+    pub fn alloc(&mut self, value: T) -> Result<NonNull<T>, T> {
+        let header = unsafe { &mut *self.header.as_ptr() };
+        if header.len == header.capacity {
+            return Err(value);
+        }
+        let slot = unsafe { self.elements().add(header.len) };
+        unsafe { slot.write(value) };
+        header.len += 1;
+        Ok(unsafe { NonNull::new_unchecked(slot) })
+    }
+
+    pub fn len(&self) -> usize {
+        unsafe { (*self.header.as_ptr()).len }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        unsafe { (*self.header.as_ptr()).capacity }
+    }
+
+    fn elements(&self) -> *mut T {
+        unsafe { (self.header.as_ptr() as *mut u8).add((*self.header.as_ptr()).elements) as *mut T }
+    }
+}
+
+unsafe fn drop_elements<T>(elements: *mut u8, len: usize) {
+    unsafe { ptr::drop_in_place(ptr::slice_from_raw_parts_mut(elements as *mut T, len)) };
+}
+
+impl crate::finalize::DropGlue for Header {
+    unsafe fn drop(obj: *mut u8, _: *mut u8) {
+        let header = unsafe { &mut *(obj as *mut Header) };
+        let len = mem::replace(&mut header.len, 0);
+        unsafe { (header.drop_elements)(obj.add(header.elements), len) };
+    }
+}
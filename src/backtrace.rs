@@ -0,0 +1,241 @@
+//! Allocation-site backtraces for live GC objects (`backtrace` feature).
+//!
+//! Each allocation records the return addresses of its call stack in a side
+//! table keyed by object base. The table is a fixed-size open-addressing hash
+//! table mapped directly from the OS, so recording never allocates from the
+//! GC heap. Entries for objects found unreachable are dropped at the end of
+//! each mark phase. When every slot on a key's probe sequence is taken, the
+//! entry in its home slot is evicted, so lookups are best effort.
+
+use core::{
+    ffi::c_void,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+};
+
+/// Maximum number of frames kept per allocation.
+pub const MAX_FRAMES: usize = 16;
+
+const SLOTS: usize = 1 << 16;
+const MAX_PROBES: usize = 32;
+const EMPTY: usize = 0;
+// Object bases are word aligned, so this can never be a real key.
+const TOMBSTONE: usize = 1;
+
+/// The call stack, as return addresses, at the point an object was allocated.
+/// Symbolize the addresses with the platform's tools (e.g. `addr2line`).
+#[derive(Clone, Copy)]
+pub struct Backtrace {
+    frames: [usize; MAX_FRAMES],
+    len: usize,
+}
+
+impl Backtrace {
+    /// Return addresses, innermost first.
+    pub fn frames(&self) -> &[usize] {
+        &self.frames[..self.len]
+    }
+}
+
+impl core::fmt::Debug for Backtrace {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.frames().iter().map(|ip| *ip as *const u8)).finish()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Slot {
+    key: usize,
+    trace: Backtrace,
+}
+
+static TABLE: AtomicPtr<Slot> = AtomicPtr::new(ptr::null_mut());
+static LOCKED: AtomicBool = AtomicBool::new(false);
+
+// The most recently recorded backtraces, oldest overwritten first, guarded by
+// `LOCKED` like the table.
+const RECENT: usize = 4;
+static mut RECENT_TRACES: [Backtrace; RECENT] =
+    [Backtrace { frames: [0; MAX_FRAMES], len: 0 }; RECENT];
+static mut RECENT_NEXT: usize = 0;
+
+#[thread_local]
+static mut RECORDING: bool = false;
+
+#[allow(non_camel_case_types)]
+type _Unwind_Trace_Fn = extern "C" fn(ctx: *mut c_void, arg: *mut c_void) -> i32;
+
+extern "C" {
+    fn _Unwind_Backtrace(trace: _Unwind_Trace_Fn, arg: *mut c_void) -> i32;
+    fn _Unwind_GetIP(ctx: *mut c_void) -> usize;
+}
+
+/// Returns the backtrace recorded when the object at `ptr` was allocated, if
+/// it is still known. `ptr` may point anywhere inside the object.
+pub fn allocation_backtrace<T>(ptr: *const T) -> Option<Backtrace> {
+    let base = crate::base_of(ptr)?.as_ptr() as usize;
+    let table = TABLE.load(Ordering::Acquire);
+    if table.is_null() {
+        return None;
+    }
+    lock();
+    let found = unsafe { find(table, base) }.map(|i| unsafe { (*table.add(i)).trace });
+    unlock();
+    found
+}
+
+/// Record the current call stack against the newly allocated object `base`.
+pub(crate) fn record(base: *mut u8) {
+    unsafe {
+        // Unwinding may itself allocate on first use.
+        if RECORDING {
+            return;
+        }
+        RECORDING = true;
+    }
+    let mut trace = Backtrace { frames: [0; MAX_FRAMES], len: 0 };
+    unsafe { _Unwind_Backtrace(push_frame, &mut trace as *mut _ as *mut c_void) };
+    if let Some(table) = table() {
+        lock();
+        unsafe {
+            insert(table, base as usize, trace);
+            RECENT_TRACES[RECENT_NEXT % RECENT] = trace;
+            RECENT_NEXT += 1;
+        }
+        unlock();
+    }
+    unsafe { RECORDING = false };
+}
+
+/// Call `f` on the most recently recorded backtraces, newest first. Gives
+/// up without calling `f` if another thread (possibly a stopped one) holds
+/// the table lock, so it is safe to call from an abort handler.
+pub(crate) fn for_each_recent(mut f: impl FnMut(&Backtrace)) {
+    if LOCKED.swap(true, Ordering::Acquire) {
+        return;
+    }
+    unsafe {
+        let next = RECENT_NEXT;
+        for i in 1..=next.min(RECENT) {
+            let trace = RECENT_TRACES[(next - i) % RECENT];
+            f(&trace);
+        }
+    }
+    unlock();
+}
+
+/// Forget objects that were not marked, or were freed explicitly. Called at
+/// the end of the mark phase, with the allocator lock held and possibly the
+/// world stopped.
+pub(crate) fn prune_unmarked() {
+    let table = TABLE.load(Ordering::Acquire);
+    // A stopped thread may hold the table lock; skip pruning this cycle.
+    if table.is_null() || LOCKED.swap(true, Ordering::Acquire) {
+        return;
+    }
+    for i in 0..SLOTS {
+        let slot = unsafe { &mut *table.add(i) };
+        if slot.key > TOMBSTONE && !is_marked_object(slot.key) {
+            slot.key = TOMBSTONE;
+        }
+    }
+    unlock();
+}
+
+// `GC_is_marked` only takes object bases: an explicitly freed large object
+// (e.g. the old copy left by a reallocation) may have been merged into a
+// larger free block, whose interior has no header to read.
+fn is_marked_object(key: usize) -> bool {
+    unsafe {
+        crate::GC_base(key as *mut u8) as usize == key && crate::GC_is_marked(key as *const u8) != 0
+    }
+}
+
+extern "C" fn push_frame(ctx: *mut c_void, arg: *mut c_void) -> i32 {
+    // _URC_NO_REASON continues the walk, _URC_END_OF_STACK stops it.
+    const CONTINUE: i32 = 0;
+    const STOP: i32 = 5;
+    let trace = unsafe { &mut *(arg as *mut Backtrace) };
+    if trace.len == MAX_FRAMES {
+        return STOP;
+    }
+    trace.frames[trace.len] = unsafe { _Unwind_GetIP(ctx) };
+    trace.len += 1;
+    CONTINUE
+}
+
+fn table() -> Option<*mut Slot> {
+    let table = TABLE.load(Ordering::Acquire);
+    if !table.is_null() {
+        return Some(table);
+    }
+    let map = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            SLOTS * size_of::<Slot>(),
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+            -1,
+            0,
+        )
+    };
+    if map == libc::MAP_FAILED {
+        return None;
+    }
+    match TABLE.compare_exchange(
+        ptr::null_mut(),
+        map as *mut Slot,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+    ) {
+        Ok(_) => {
+            crate::events::install();
+            Some(map as *mut Slot)
+        }
+        Err(winner) => {
+            unsafe { libc::munmap(map, SLOTS * size_of::<Slot>()) };
+            Some(winner)
+        }
+    }
+}
+
+fn lock() {
+    while LOCKED.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+        core::hint::spin_loop();
+    }
+}
+
+fn unlock() {
+    LOCKED.store(false, Ordering::Release);
+}
+
+fn home(key: usize) -> usize {
+    // Fibonacci hashing of the (word-aligned) address.
+    (key >> 3).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (usize::BITS - SLOTS.trailing_zeros())
+}
+
+unsafe fn find(table: *mut Slot, key: usize) -> Option<usize> {
+    let h = home(key);
+    for p in 0..MAX_PROBES {
+        let i = (h + p) & (SLOTS - 1);
+        match unsafe { (*table.add(i)).key } {
+            EMPTY => return None,
+            k if k == key => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+unsafe fn insert(table: *mut Slot, key: usize, trace: Backtrace) {
+    let h = home(key);
+    // A reused address replaces its stale entry; otherwise take the first free
+    // slot, falling back to evicting the home slot.
+    let i = unsafe { find(table, key) }.unwrap_or_else(|| {
+        (0..MAX_PROBES)
+            .map(|p| (h + p) & (SLOTS - 1))
+            .find(|&i| unsafe { (*table.add(i)).key } <= TOMBSTONE)
+            .unwrap_or(h)
+    });
+    unsafe { table.add(i).write(Slot { key, trace }) };
+}
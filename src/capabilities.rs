@@ -0,0 +1,66 @@
+//! Which optional BDWGC APIs the linked library provides.
+//!
+//! With `link-shared` the library found at run time may be older than the
+//! headers this crate was written against. Functions are usually bound
+//! lazily, so a missing one is only noticed when it is first called, and the
+//! process dies there. Checking [`capabilities`] first lets a caller fall
+//! back instead.
+
+/// Version of the linked BDWGC library as `(major, minor, micro)`.
+pub fn gc_version() -> (u8, u8, u8) {
+    let version = unsafe { crate::GC_get_version() };
+    ((version >> 16) as u8, (version >> 8) as u8, version as u8)
+}
+
+/// Optional features of the linked BDWGC library, derived from its version
+/// by the release that first shipped each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GcCapabilities {
+    /// The version the flags below were derived from.
+    pub version: (u8, u8, u8),
+    /// `GC_get_prof_stats` and [`crate::get_prof_stats`] (7.4.0).
+    pub prof_stats: bool,
+    /// `GC_set_time_limit_tv`, pause limits finer than a millisecond (8.0.0).
+    pub time_limit_tv: bool,
+    /// `GC_set_markers_count`, choosing the number of parallel markers
+    /// before start-up (8.2.0).
+    pub markers_count: bool,
+    /// `GC_get_obtained_from_os_bytes` and the matching profile statistics
+    /// field, used by [`crate::memory_breakdown`] (8.2.0).
+    pub obtained_from_os_bytes: bool,
+    /// `GC_start_incremental_collection`, used by
+    /// [`crate::collect_incrementally`] and
+    /// [`crate::collect_incremental_with_progress`] (8.2.0).
+    pub start_incremental_collection: bool,
+    /// `GC_set_interrupt_finalizers`, used by
+    /// [`crate::invoke_finalizers_within`] to run one finalizer at a time
+    /// (8.3.0).
+    pub interrupt_finalizers: bool,
+    /// `GC_foreach_heap_section_inner`, used by [`crate::resident_report`]
+    /// to walk the heap sections (8.3.0).
+    pub foreach_heap_section: bool,
+}
+
+impl GcCapabilities {
+    /// The capabilities of BDWGC `version`.
+    pub const fn for_version(version: (u8, u8, u8)) -> Self {
+        const fn at_least(version: (u8, u8, u8), major: u8, minor: u8) -> bool {
+            version.0 > major || (version.0 == major && version.1 >= minor)
+        }
+        GcCapabilities {
+            version,
+            prof_stats: at_least(version, 7, 4),
+            time_limit_tv: at_least(version, 8, 0),
+            markers_count: at_least(version, 8, 2),
+            obtained_from_os_bytes: at_least(version, 8, 2),
+            start_incremental_collection: at_least(version, 8, 2),
+            interrupt_finalizers: at_least(version, 8, 3),
+            foreach_heap_section: at_least(version, 8, 3),
+        }
+    }
+}
+
+/// The capabilities of the linked BDWGC library.
+pub fn capabilities() -> GcCapabilities {
+    GcCapabilities::for_version(gc_version())
+}
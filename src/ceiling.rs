@@ -0,0 +1,121 @@
+//! A hard limit on the memory held by live GC objects.
+
+use core::fmt::Write;
+use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// What [`enforce_memory_ceiling`] does when a collection leaves more than
+/// the ceiling in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CeilingAction {
+    /// Run a full collection, and abort only if the ceiling is still
+    /// exceeded afterwards.
+    CollectThenAbort,
+    /// Abort at once.
+    AbortImmediately,
+    /// Call the function and carry on. It is called again each time the
+    /// post-collection hooks run after a collection that left the ceiling
+    /// exceeded.
+    Callback(fn()),
+}
+
+// Zero when no ceiling is enforced.
+static CEILING: AtomicUsize = AtomicUsize::new(0);
+// 0 for `CollectThenAbort`, 1 for `AbortImmediately`, otherwise the address
+// of the callback.
+static ACTION: AtomicUsize = AtomicUsize::new(0);
+// The memory use the last check found above the ceiling, or zero, for
+// `act_on_breach` to deal with.
+static BREACH: AtomicUsize = AtomicUsize::new(0);
+
+/// Bytes occupied by GC objects: the heap size less its free space. This is
+/// what [`enforce_memory_ceiling`] compares against the ceiling.
+pub fn memory_use() -> usize {
+    let usage = crate::heap_usage();
+    usage.heap_size.saturating_sub(usage.free_bytes)
+}
+
+/// Check [`memory_use`] against `bytes` after every collection and take
+/// `on_exceed` when it is above. A ceiling of zero stops enforcement.
+///
+/// The check runs on the first allocation after a collection, or on return
+/// from [`crate::collect`] or [`crate::run_pending_gc_hooks`] if that comes
+/// first. [`CeilingAction::AbortImmediately`] aborts right there. The
+/// callback and the collection of [`CeilingAction::CollectThenAbort`] are
+/// only run where the post-collection hook is, never from inside an
+/// allocation, so a program that never collects explicitly must call
+/// [`crate::run_pending_gc_hooks`] for them to happen. Unlike
+/// [`crate::set_max_heap_size`], which makes allocations fail, this
+/// terminates the process, with a message on standard error, e.g. to
+/// contain a runaway tenant.
+pub fn enforce_memory_ceiling(bytes: usize, on_exceed: CeilingAction) {
+    let action = match on_exceed {
+        CeilingAction::CollectThenAbort => 0,
+        CeilingAction::AbortImmediately => 1,
+        CeilingAction::Callback(f) => f as usize,
+    };
+    ACTION.store(action, Ordering::Relaxed);
+    CEILING.store(bytes, Ordering::Release);
+    if bytes != 0 {
+        crate::events::install();
+    }
+}
+
+#[inline]
+pub(crate) fn ceiling_active() -> bool {
+    CEILING.load(Ordering::Relaxed) != 0
+}
+
+/// Compare the memory use left by the last collection with the ceiling.
+/// Cheap and free of user code, for the allocation path: anything more than
+/// aborting is left to [`act_on_breach`].
+pub(crate) fn check_ceiling() {
+    let ceiling = CEILING.load(Ordering::Acquire);
+    if ceiling == 0 {
+        return;
+    }
+    let used = memory_use();
+    if used <= ceiling {
+        BREACH.store(0, Ordering::Relaxed);
+        return;
+    }
+    if ACTION.load(Ordering::Relaxed) == 1 {
+        exceeded(used, ceiling);
+    }
+    BREACH.store(used, Ordering::Release);
+}
+
+/// Take the action for a breach [`check_ceiling`] found, if any. Only
+/// called where the post-collection hook runs.
+pub(crate) fn act_on_breach() {
+    let used = BREACH.swap(0, Ordering::Acquire);
+    let ceiling = CEILING.load(Ordering::Acquire);
+    if used == 0 || ceiling == 0 {
+        return;
+    }
+    match ACTION.load(Ordering::Relaxed) {
+        0 => {
+            crate::events::gcollect(crate::events::CollectionCause::Explicit);
+            let used = memory_use();
+            if used > ceiling {
+                exceeded(used, ceiling);
+            }
+        }
+        1 => exceeded(used, ceiling),
+        f => {
+            let f: fn() = unsafe { mem::transmute(f) };
+            f();
+        }
+    }
+}
+
+#[cold]
+fn exceeded(used: usize, ceiling: usize) -> ! {
+    let _ = writeln!(
+        crate::Stderr,
+        "bmalloc: memory ceiling exceeded: {} in use, ceiling {}",
+        crate::format_bytes(used),
+        crate::format_bytes(ceiling),
+    );
+    unsafe { libc::abort() }
+}
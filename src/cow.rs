@@ -0,0 +1,103 @@
+//! Borrowed-or-GC-owned values.
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::Deref;
+use core::ptr::{self, NonNull};
+
+/// Either a borrow or a value owned by the GC heap, like `Cow` with the
+/// owned case in collectable memory.
+///
+/// [`to_gc_owned`](GcCow::to_gc_owned) copies borrowed data into a new GC
+/// object, which then no longer depends on the borrow's lifetime. Cloning is
+/// cheap in both cases: the owned variant just copies the pointer. Like any
+/// pointer to GC memory, an owned `GcCow` must be kept where the collector
+/// scans it. Values that need dropping are dropped by a finalizer once
+/// unreachable.
+pub enum GcCow<'a, T: ?Sized> {
+    Borrowed(&'a T),
+    Owned(NonNull<T>),
+}
+
+impl<T: ?Sized> Clone for GcCow<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for GcCow<'_, T> {}
+
+impl<T: ?Sized> Deref for GcCow<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match *self {
+            GcCow::Borrowed(borrowed) => borrowed,
+            GcCow::Owned(owned) => unsafe { owned.as_ref() },
+        }
+    }
+}
+
+impl<T: ?Sized> GcCow<'_, T> {
+    pub fn is_owned(&self) -> bool {
+        matches!(self, GcCow::Owned(_))
+    }
+}
+
+impl<T: Clone> GcCow<'_, T> {
+    /// Return the GC-owned value, cloning a borrowed one into a new GC object.
+    /// Returns `None` if the allocation fails.
+    pub fn to_gc_owned(&self) -> Option<GcCow<'static, T>> {
+        let borrowed = match *self {
+            GcCow::Owned(owned) => return Some(GcCow::Owned(owned)),
+            GcCow::Borrowed(borrowed) => borrowed,
+        };
+        let layout = Layout::new::<T>();
+        let ptr = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            NonNull::new(unsafe { crate::gc_malloc(layout) })?.cast::<T>()
+        };
+        unsafe { ptr.as_ptr().write(borrowed.clone()) };
+        if layout.size() != 0 {
+            unsafe { drop_when_unreachable::<T>(ptr.as_ptr() as *mut u8, 1) };
+        }
+        Some(GcCow::Owned(ptr))
+    }
+}
+
+impl<T: Clone> GcCow<'_, [T]> {
+    /// Return the GC-owned slice, cloning a borrowed one into a new GC
+    /// object. Returns `None` if the allocation fails.
+    pub fn to_gc_owned(&self) -> Option<GcCow<'static, [T]>> {
+        let borrowed = match *self {
+            GcCow::Owned(owned) => return Some(GcCow::Owned(owned)),
+            GcCow::Borrowed(borrowed) => borrowed,
+        };
+        let (ptr, _) = crate::gc_vec_raw_parts::<T>(borrowed.len())?;
+        for (i, item) in borrowed.iter().enumerate() {
+            unsafe { ptr.as_ptr().add(i).write(item.clone()) };
+        }
+        if mem::size_of::<T>() != 0 && !borrowed.is_empty() {
+            unsafe { drop_when_unreachable::<T>(ptr.as_ptr() as *mut u8, borrowed.len()) };
+        }
+        Some(GcCow::Owned(NonNull::slice_from_raw_parts(ptr, borrowed.len())))
+    }
+}
+
+// Drops `len` values of `T` at the start of the object, with `len` passed as
+// the finalizer's data.
+struct DropSlice<T>(PhantomData<T>);
+
+impl<T> crate::finalize::DropGlue for DropSlice<T> {
+    unsafe fn drop(obj: *mut u8, len: *mut u8) {
+        unsafe { ptr::drop_in_place(ptr::slice_from_raw_parts_mut(obj as *mut T, len as usize)) };
+    }
+}
+
+unsafe fn drop_when_unreachable<T>(obj: *mut u8, len: usize) {
+    if mem::needs_drop::<T>() {
+        unsafe { crate::finalize::drop_when_unreachable::<DropSlice<T>>(obj, len as *mut u8) };
+    }
+}
@@ -0,0 +1,114 @@
+//! Extra guidance attached to the collector's fatal errors.
+
+use core::ffi::CStr;
+use core::fmt::Write;
+use core::mem;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static PREVIOUS: AtomicUsize = AtomicUsize::new(0);
+static POSTMORTEM_INSTALLED: AtomicBool = AtomicBool::new(false);
+static POSTMORTEM_PREVIOUS: AtomicUsize = AtomicUsize::new(0);
+
+// Fragments of abort messages that typically follow from the heap or the
+// thread table being corrupted by a thread the collector does not know
+// about.
+const MISUSE_SYMPTOMS: [&str; 6] =
+    ["unknown thread", "not registered", "corrupt", "Bad ", "Unexpected", "Smashed"];
+
+const REGISTRATION_HINT: &str = "did you allocate from, or keep GC pointers on, a thread \
+    that is not registered with the collector? Threads not created through the collector's \
+    pthread_create wrapper must call `ensure_thread_registered` (or run inside \
+    `call_with_gc_active`) first";
+
+/// Guidance to show alongside a fatal collector message (or a panic payload
+/// quoting one) that looks like the result of using the GC heap from an
+/// unregistered thread, the most common misuse. `None` if it does not.
+pub fn registration_hint(message: &str) -> Option<&'static str> {
+    MISUSE_SYMPTOMS.iter().any(|s| message.contains(s)).then_some(REGISTRATION_HINT)
+}
+
+/// Make fatal collector errors print [`registration_hint`] guidance before
+/// the process aborts, when the message looks like thread misuse. The
+/// previously installed abort handler (by default, the one printing the
+/// message) still runs afterwards.
+///
+/// Panics are reported by `std`, which this crate cannot hook; a `std`
+/// panic hook can call [`registration_hint`] on the payload instead.
+pub fn install_diagnostic_abort_hook() {
+    if INSTALLED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    unsafe {
+        PREVIOUS.store(crate::GC_get_abort_func() as usize, Ordering::Release);
+        crate::GC_set_abort_func(on_abort);
+    }
+}
+
+// May run with the allocator lock held: only write to stderr.
+unsafe extern "C" fn on_abort(msg: *const libc::c_char) {
+    if !msg.is_null() {
+        let msg = unsafe { CStr::from_ptr(msg) }.to_str().unwrap_or("");
+        if let Some(hint) = registration_hint(msg) {
+            let _ = writeln!(crate::Stderr, "bmalloc: GC abort ({}): {hint}", msg.trim_end());
+        }
+    }
+    let prev = PREVIOUS.load(Ordering::Acquire);
+    if prev != 0 {
+        let prev: unsafe extern "C" fn(*const libc::c_char) = unsafe { mem::transmute(prev) };
+        unsafe { prev(msg) };
+    }
+}
+
+/// Print a postmortem block to standard error when the collector aborts,
+/// typically on detecting heap corruption (more checks are made with the
+/// `gc-assertions` feature): the abort message, the heap statistics, the
+/// aborting thread, and, with the `backtrace` feature, the call stacks of
+/// the most recent allocations. The previously installed abort handler
+/// still runs afterwards, then the process aborts.
+///
+/// BDWGC has no way to list the registered threads, so only the aborting
+/// one is identified. With `core_dump`, the core file size limit is raised
+/// as far as allowed, so that the abort leaves a core dump to inspect.
+pub fn enable_corruption_postmortem(core_dump: bool) {
+    if core_dump {
+        unsafe {
+            let mut limit: libc::rlimit = mem::zeroed();
+            if libc::getrlimit(libc::RLIMIT_CORE, &mut limit) == 0 {
+                limit.rlim_cur = limit.rlim_max;
+                libc::setrlimit(libc::RLIMIT_CORE, &limit);
+            }
+        }
+    }
+    if POSTMORTEM_INSTALLED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    unsafe {
+        POSTMORTEM_PREVIOUS.store(crate::GC_get_abort_func() as usize, Ordering::Release);
+        crate::GC_set_abort_func(postmortem);
+    }
+}
+
+// Likely runs with the allocator lock held and the heap corrupt: read the
+// statistics without locking, take no other locks, and only write to stderr.
+unsafe extern "C" fn postmortem(msg: *const libc::c_char) {
+    let mut out = crate::Stderr;
+    let text =
+        if msg.is_null() { "" } else { unsafe { CStr::from_ptr(msg) }.to_str().unwrap_or("") };
+    let _ = writeln!(out, "bmalloc: GC abort postmortem: {}", text.trim_end());
+    let mut stats = crate::ProfileStats::default();
+    let size = mem::size_of::<crate::ProfileStats>();
+    if unsafe { crate::GC_get_prof_stats_unsafe(&mut stats, size) } == size {
+        let _ = writeln!(out, "{stats}");
+    }
+    let _ = writeln!(out, "aborting thread: {:#x}", unsafe { libc::pthread_self() });
+    #[cfg(feature = "backtrace")]
+    crate::backtrace::for_each_recent(|trace| {
+        let _ = writeln!(out, "recent allocation at {trace:?}");
+    });
+    let prev = POSTMORTEM_PREVIOUS.load(Ordering::Acquire);
+    if prev != 0 {
+        let prev: unsafe extern "C" fn(*const libc::c_char) = unsafe { mem::transmute(prev) };
+        unsafe { prev(msg) };
+    }
+}
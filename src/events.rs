@@ -0,0 +1,200 @@
+//! Fan-out of BDWGC's single collection event callback to the parts of the
+//! crate that need it.
+
+use core::{
+    mem,
+    sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
+};
+
+// From gc.h's `GC_EventType`.
+pub(crate) const GC_EVENT_START: i32 = 0;
+#[cfg(any(feature = "backtrace", feature = "detect-resurrection"))]
+pub(crate) const GC_EVENT_MARK_END: i32 = 2;
+pub(crate) const GC_EVENT_RECLAIM_END: i32 = 4;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+// The handler that was installed before ours, if any, as a function address.
+static PREVIOUS: AtomicUsize = AtomicUsize::new(0);
+// The post-collection hook as a function address, and the number of
+// collections it has not been run for yet.
+static HOOK: AtomicUsize = AtomicUsize::new(0);
+static HOOK_PENDING: AtomicUsize = AtomicUsize::new(0);
+// Whether a collection completed since the crate's own post-collection checks
+// (auto-tuning, memory ceiling, alerts, leak sampling) last ran.
+static CHECKS_PENDING: AtomicBool = AtomicBool::new(false);
+// The collection cause callback as a function address, and the cause of the
+// collection the crate is currently requesting (0 if none).
+static CAUSE_CALLBACK: AtomicUsize = AtomicUsize::new(0);
+static REQUESTED_CAUSE: AtomicU8 = AtomicU8::new(0);
+
+/// Why a collection ran, as far as the crate can tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectionCause {
+    /// Requested through [`crate::collect`] (or by the crate itself, e.g. to
+    /// enforce a memory ceiling).
+    Explicit = 1,
+    /// Run to make room for an allocation that had failed (see
+    /// [`crate::set_retry_on_oom`]).
+    AllocationRetry = 2,
+    /// Started by the collector itself because enough was allocated since
+    /// the previous collection.
+    HeapFull = 3,
+}
+
+/// Install the crate's collection event handler if it is not already. Must not
+/// be called with the allocator lock held.
+pub(crate) fn install() {
+    if INSTALLED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    unsafe {
+        let prev = crate::GC_get_on_collection_event();
+        PREVIOUS.store(prev.map_or(0, |f| f as usize), Ordering::Release);
+        crate::GC_set_on_collection_event(Some(on_collection_event));
+    }
+}
+
+// Runs with the allocator lock held, sometimes with the world stopped: nothing
+// called from here may allocate or block on a lock another thread could hold.
+unsafe extern "C" fn on_collection_event(event: i32) {
+    if event == GC_EVENT_START {
+        report_cause();
+    }
+    if event == GC_EVENT_RECLAIM_END {
+        crate::inflight::reset();
+    }
+    if event == GC_EVENT_RECLAIM_END && HOOK.load(Ordering::Relaxed) != 0 {
+        HOOK_PENDING.fetch_add(1, Ordering::Release);
+    }
+    if event == GC_EVENT_RECLAIM_END
+        && (crate::tuning::auto_tune_active()
+            || crate::ceiling::ceiling_active()
+            || crate::alert::alerts_active()
+            || crate::leak::sampling_active())
+    {
+        CHECKS_PENDING.store(true, Ordering::Release);
+    }
+
+    #[cfg(feature = "backtrace")]
+    if event == GC_EVENT_MARK_END {
+        crate::backtrace::prune_unmarked();
+    }
+    #[cfg(feature = "detect-resurrection")]
+    if event == GC_EVENT_MARK_END {
+        crate::resurrection::check_marked();
+    }
+
+    let prev = PREVIOUS.load(Ordering::Acquire);
+    if prev != 0 {
+        let prev: unsafe extern "C" fn(i32) = unsafe { mem::transmute(prev) };
+        unsafe { prev(event) };
+    }
+}
+
+/// Call `hook` once for every completed collection, e.g. to prune caches
+/// keyed by the identity of objects that may now be gone. Replaces any
+/// previous hook.
+///
+/// The collector reports the end of reclamation with its allocator lock
+/// held, where no allocation is possible, so the hook is not run from there.
+/// It runs instead, with the collection finished and allocation permitted,
+/// on the next thread to return from [`crate::collect`] (or another
+/// `collect*` function) or to call [`run_pending_gc_hooks`]. It is never run
+/// from inside an allocation, so it may allocate and take locks that
+/// allocating code holds. Collections the collector starts by itself are
+/// only caught up on at one of those points: a program that never collects
+/// explicitly should call [`run_pending_gc_hooks`] regularly, from a place
+/// where it holds no lock the hook needs.
+pub fn set_post_collection_hook(hook: fn()) {
+    install();
+    HOOK.store(hook as usize, Ordering::Release);
+}
+
+/// Remove the hook installed by [`set_post_collection_hook`]. Collections
+/// already completed may still run it once more.
+pub fn clear_post_collection_hook() {
+    HOOK.store(0, Ordering::Release);
+}
+
+/// Run the hook installed by [`set_post_collection_hook`] once for every
+/// collection completed since it last ran, on the calling thread. What the
+/// crate's own post-collection checks leave to do outside an allocation,
+/// such as the action of a breached [`crate::enforce_memory_ceiling`] or
+/// the callback of an [`crate::AlertMonitor`], is done first.
+pub fn run_pending_gc_hooks() {
+    run_post_collection_checks();
+    crate::ceiling::act_on_breach();
+    crate::alert::run_queued_alerts();
+    let pending = HOOK_PENDING.swap(0, Ordering::Acquire);
+    if pending == 0 {
+        return;
+    }
+    let hook = HOOK.load(Ordering::Acquire);
+    if hook == 0 {
+        return;
+    }
+    let hook: fn() = unsafe { mem::transmute(hook) };
+    for _ in 0..pending {
+        hook();
+    }
+}
+
+/// Run the crate's own post-collection checks if a collection completed
+/// since they last ran. Cheap enough for the allocation path, and runs no
+/// user code: what would is left to [`run_pending_gc_hooks`].
+#[inline]
+pub(crate) fn run_post_collection_checks() {
+    if CHECKS_PENDING.load(Ordering::Relaxed) && CHECKS_PENDING.swap(false, Ordering::Acquire) {
+        crate::tuning::auto_tune_step();
+        crate::ceiling::check_ceiling();
+        crate::alert::check_alerts();
+        crate::leak::sample();
+    }
+}
+
+/// Call `f` at the start of every collection with its cause.
+///
+/// BDWGC does not report why it collects, so the cause is inferred: a
+/// collection the crate asked for while running [`crate::collect`] or
+/// retrying a failed allocation is reported as such, and every other one
+/// as [`CollectionCause::HeapFull`]. A collection that another thread
+/// triggers at the same moment as an explicit one may be misattributed.
+///
+/// `f` is called with the allocator lock held, so it must not allocate from
+/// the GC heap or block; counting causes in atomics is the intended use.
+/// Replaces any previous callback.
+pub fn set_collection_cause_callback(f: fn(CollectionCause)) {
+    install();
+    CAUSE_CALLBACK.store(f as usize, Ordering::Release);
+}
+
+/// Remove the callback installed by [`set_collection_cause_callback`].
+pub fn clear_collection_cause_callback() {
+    CAUSE_CALLBACK.store(0, Ordering::Release);
+}
+
+/// Run a full collection, attributing it to `cause`.
+pub(crate) fn gcollect(cause: CollectionCause) {
+    collect_with(cause, || unsafe { crate::GC_gcollect() });
+}
+
+/// Run `collect`, attributing the collections it starts to `cause`.
+pub(crate) fn collect_with(cause: CollectionCause, collect: impl FnOnce()) {
+    REQUESTED_CAUSE.store(cause as u8, Ordering::Relaxed);
+    collect();
+    REQUESTED_CAUSE.store(0, Ordering::Relaxed);
+}
+
+fn report_cause() {
+    let callback = CAUSE_CALLBACK.load(Ordering::Acquire);
+    if callback == 0 {
+        return;
+    }
+    let cause = match REQUESTED_CAUSE.load(Ordering::Relaxed) {
+        1 => CollectionCause::Explicit,
+        2 => CollectionCause::AllocationRetry,
+        _ => CollectionCause::HeapFull,
+    };
+    let callback: fn(CollectionCause) = unsafe { mem::transmute(callback) };
+    callback(cause);
+}
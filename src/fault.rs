@@ -0,0 +1,36 @@
+//! Deterministic allocation failures for testing out-of-memory handling
+//! (`test-fault-injection` feature).
+
+use core::alloc::Layout;
+use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+// The injector as a `fn(Layout) -> bool` address, or 0 if none.
+static INJECTOR: AtomicUsize = AtomicUsize::new(0);
+
+/// Make every [`crate::GcAllocator`] allocation (and reallocation to a new
+/// size) for which `f` returns `true` fail as if the heap were exhausted,
+/// without touching the heap: the request is not retried after a collection,
+/// and is otherwise handled according to the [`crate::OomPolicy`]. This
+/// exercises `try_reserve` and [`crate::gc_try_new`] error paths reliably.
+///
+/// `f` is called on every allocation, from any thread, so it must not
+/// allocate through the global allocator. Replaces any previous injector.
+pub fn set_fault_injector(f: fn(Layout) -> bool) {
+    INJECTOR.store(f as usize, Ordering::Release);
+}
+
+/// Remove the injector installed by [`set_fault_injector`].
+pub fn clear_fault_injector() {
+    INJECTOR.store(0, Ordering::Release);
+}
+
+#[inline]
+pub(crate) fn should_fail(layout: Layout) -> bool {
+    let injector = INJECTOR.load(Ordering::Acquire);
+    if injector == 0 {
+        return false;
+    }
+    let injector: fn(Layout) -> bool = unsafe { mem::transmute(injector) };
+    injector(layout)
+}
@@ -0,0 +1,291 @@
+//! Tracking of the finalization queue, and the finalizer behind every GC
+//! object the crate drops.
+//!
+//! BDWGC does not report how many objects are waiting for their finalizers to
+//! run, only whether there are any. The depth is tracked here instead: the
+//! collector's await-finalize callback counts objects as they are queued, and
+//! [`invoke_finalizers`] subtracts the ones it ran.
+
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static PREVIOUS: AtomicUsize = AtomicUsize::new(0);
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+// Objects queued for finalization since counting started.
+static ENQUEUED: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) fn install() {
+    if INSTALLED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    unsafe {
+        let prev = crate::GC_get_await_finalize_proc();
+        PREVIOUS.store(prev.map_or(0, |f| f as usize), Ordering::Release);
+        crate::GC_set_await_finalize_proc(Some(on_enqueued));
+    }
+}
+
+// Called with the allocator lock held for every object moved to the
+// finalization queue.
+unsafe extern "C" fn on_enqueued(obj: *mut u8) {
+    PENDING.fetch_add(1, Ordering::Relaxed);
+    ENQUEUED.fetch_add(1, Ordering::Relaxed);
+
+    let prev = PREVIOUS.load(Ordering::Acquire);
+    if prev != 0 {
+        let prev: unsafe extern "C" fn(*mut u8) = unsafe { mem::transmute(prev) };
+        unsafe { prev(obj) };
+    }
+}
+
+/// Number of objects queued for finalization since counting started (see
+/// [`pending_finalizers`]).
+pub(crate) fn enqueued_total() -> usize {
+    install();
+    ENQUEUED.load(Ordering::Relaxed)
+}
+
+/// Approximate number of finalizers queued but not yet run.
+///
+/// A steadily growing value under `GC_set_finalize_on_demand` means
+/// [`invoke_finalizers`] is not being called often enough. Counting starts
+/// with [`crate::init`] or the first call to this function, whichever comes
+/// first. Finalizers run by the collector itself, or through a direct call
+/// to `GC_invoke_finalizers`, are not subtracted until the queue is seen to
+/// be empty, so the count may overstate the backlog in that case.
+pub fn pending_finalizers() -> usize {
+    install();
+    if unsafe { crate::GC_should_invoke_finalizers() } == 0 {
+        PENDING.store(0, Ordering::Relaxed);
+        return 0;
+    }
+    PENDING.load(Ordering::Relaxed)
+}
+
+/// Run all queued finalizers on the calling thread, returning how many ran.
+pub fn invoke_finalizers() -> usize {
+    let ran = unsafe { crate::GC_invoke_finalizers() } as usize;
+    let _ =
+        PENDING.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| Some(n.saturating_sub(ran)));
+    ran
+}
+
+/// Run queued finalizers on the calling thread one at a time until the queue
+/// is empty or `budget` has elapsed, returning how many ran. The clock is
+/// checked between finalizers, so a single slow finalizer can still overrun
+/// the budget; the rest stay queued for a later call.
+///
+/// Meant for spreading finalization over frames under
+/// `GC_set_finalize_on_demand`. The collector's per-call finalizer limit is
+/// lowered to one while this runs, which also limits `GC_invoke_finalizers`
+/// calls made concurrently by other threads.
+///
+/// BDWGC before 8.3 has no such limit (see [`crate::GcCapabilities`]); there
+/// this runs the whole queue in one `GC_invoke_finalizers` call, whatever
+/// the budget.
+pub fn invoke_finalizers_within(budget: Duration) -> usize {
+    if !crate::capabilities().interrupt_finalizers {
+        return invoke_finalizers();
+    }
+    let deadline = crate::now() + budget;
+    let limit = unsafe { crate::GC_get_interrupt_finalizers() };
+    unsafe { crate::GC_set_interrupt_finalizers(1) };
+    let mut ran = 0;
+    while crate::now() < deadline {
+        let n = unsafe { crate::GC_invoke_finalizers() } as usize;
+        if n == 0 {
+            break;
+        }
+        ran += n;
+    }
+    unsafe { crate::GC_set_interrupt_finalizers(limit) };
+    let _ =
+        PENDING.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| Some(n.saturating_sub(ran)));
+    ran
+}
+
+/// How the crate drops the contents of a GC object of some layout once it is
+/// unreachable (see [`drop_when_unreachable`]).
+pub(crate) trait DropGlue {
+    /// Drop what the object at `obj` holds. `data` is the value passed to
+    /// [`drop_when_unreachable`].
+    unsafe fn drop(obj: *mut u8, data: *mut u8);
+}
+
+/// Drops a single `T` at the start of the object.
+pub(crate) struct DropValue<T>(PhantomData<T>);
+
+impl<T> DropGlue for DropValue<T> {
+    unsafe fn drop(obj: *mut u8, _: *mut u8) {
+        unsafe { ptr::drop_in_place(obj as *mut T) };
+    }
+}
+
+/// Register a finalizer that drops the contents of the object at `obj` with
+/// `G` once it is unreachable, in no particular order relative to other
+/// finalizers. With `detect-resurrection`, the object is then watched for
+/// resurrection.
+///
+/// # Safety
+///
+/// `obj` must be the base address of an object allocated by the collector,
+/// holding what `G` expects.
+pub(crate) unsafe fn drop_when_unreachable<G: DropGlue>(obj: *mut u8, data: *mut u8) {
+    unsafe extern "C" fn finalize<G: DropGlue>(obj: *mut u8, data: *mut u8) {
+        unsafe { G::drop(obj, data) };
+        #[cfg(feature = "detect-resurrection")]
+        unsafe {
+            crate::watch_for_resurrection(obj)
+        };
+    }
+
+    unsafe {
+        crate::GC_register_finalizer_no_order(
+            obj,
+            Some(finalize::<G>),
+            data,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+    };
+}
+
+// State of the thread started by `start_metered_finalizer_thread`. At most
+// one runs at a time.
+static THREAD_RUNNING: AtomicBool = AtomicBool::new(false);
+static THREAD_STOP: AtomicBool = AtomicBool::new(false);
+static THREAD_INTERVAL_NANOS: AtomicU64 = AtomicU64::new(0);
+static METRIC_TICKS: AtomicU64 = AtomicU64::new(0);
+static METRIC_RUN: AtomicU64 = AtomicU64::new(0);
+static METRIC_LAST_RUN: AtomicU64 = AtomicU64::new(0);
+static METRIC_BUSY_NANOS: AtomicU64 = AtomicU64::new(0);
+static METRIC_MAX_TICK_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Counters kept by the thread started with
+/// [`start_metered_finalizer_thread`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FinalizerMetrics {
+    /// Wake-ups so far, including those that found nothing to run.
+    pub ticks: u64,
+    /// Finalizers run, in total.
+    pub finalizers_run: u64,
+    /// Finalizers run in the most recent tick.
+    pub last_tick_run: u64,
+    /// Time spent running finalizers, in total.
+    pub busy: Duration,
+    /// Time spent running finalizers in the slowest tick.
+    pub max_tick: Duration,
+}
+
+/// The running thread started with [`start_metered_finalizer_thread`].
+/// Dropping it stops the thread, as [`stop`](Self::stop) does.
+#[derive(Debug)]
+pub struct FinalizerThreadHandle {
+    native: libc::pthread_t,
+}
+
+impl FinalizerThreadHandle {
+    /// The counters so far. Each is read on its own, so they may be one tick
+    /// apart from each other.
+    pub fn metrics(&self) -> FinalizerMetrics {
+        FinalizerMetrics {
+            ticks: METRIC_TICKS.load(Ordering::Relaxed),
+            finalizers_run: METRIC_RUN.load(Ordering::Relaxed),
+            last_tick_run: METRIC_LAST_RUN.load(Ordering::Relaxed),
+            busy: Duration::from_nanos(METRIC_BUSY_NANOS.load(Ordering::Relaxed)),
+            max_tick: Duration::from_nanos(METRIC_MAX_TICK_NANOS.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Stop the thread and wait for it to exit. A tick in progress is
+    /// finished first.
+    pub fn stop(self) {
+        drop(self)
+    }
+}
+
+impl Drop for FinalizerThreadHandle {
+    fn drop(&mut self) {
+        THREAD_STOP.store(true, Ordering::Release);
+        unsafe { crate::GC_pthread_join(self.native, core::ptr::null_mut()) };
+        THREAD_RUNNING.store(false, Ordering::Release);
+    }
+}
+
+/// Start a thread, registered with the collector, that wakes every
+/// `interval` and runs the queued finalizers if the collector reports any
+/// (`GC_should_invoke_finalizers`), recording how many ran and how long
+/// they took.
+///
+/// Meant for services that call `GC_set_finalize_on_demand` and would
+/// otherwise have to schedule [`invoke_finalizers`] themselves. Returns
+/// `None` if `interval` is zero, if such a thread is already running or if
+/// the thread could not be created. The counters start from zero with each
+/// new thread.
+pub fn start_metered_finalizer_thread(interval: Duration) -> Option<FinalizerThreadHandle> {
+    if interval.is_zero() {
+        return None;
+    }
+    if THREAD_RUNNING.swap(true, Ordering::AcqRel) {
+        return None;
+    }
+    install();
+    for metric in
+        [&METRIC_TICKS, &METRIC_RUN, &METRIC_LAST_RUN, &METRIC_BUSY_NANOS, &METRIC_MAX_TICK_NANOS]
+    {
+        metric.store(0, Ordering::Relaxed);
+    }
+    let interval = u64::try_from(interval.as_nanos()).unwrap_or(u64::MAX);
+    THREAD_INTERVAL_NANOS.store(interval, Ordering::Relaxed);
+    THREAD_STOP.store(false, Ordering::Release);
+
+    let mut native = unsafe { mem::zeroed() };
+    let ret = unsafe {
+        crate::GC_pthread_create(
+            &mut native,
+            core::ptr::null(),
+            run_finalizer_thread,
+            core::ptr::null_mut(),
+        )
+    };
+    if ret != 0 {
+        THREAD_RUNNING.store(false, Ordering::Release);
+        return None;
+    }
+    Some(FinalizerThreadHandle { native })
+}
+
+extern "C" fn run_finalizer_thread(_: *mut libc::c_void) -> *mut libc::c_void {
+    // Sleep in short slices so that `stop` need not wait out a long interval.
+    const SLICE_NANOS: u64 = 10_000_000;
+
+    let interval = THREAD_INTERVAL_NANOS.load(Ordering::Relaxed);
+    loop {
+        let mut left = interval;
+        while left > 0 && !THREAD_STOP.load(Ordering::Acquire) {
+            let nanos = left.min(SLICE_NANOS);
+            let ts = libc::timespec { tv_sec: 0, tv_nsec: nanos as libc::c_long };
+            unsafe { libc::nanosleep(&ts, core::ptr::null_mut()) };
+            left -= nanos;
+        }
+        if THREAD_STOP.load(Ordering::Acquire) {
+            return core::ptr::null_mut();
+        }
+
+        let mut ran = 0;
+        if unsafe { crate::GC_should_invoke_finalizers() } != 0 {
+            let start = crate::now();
+            ran = invoke_finalizers() as u64;
+            let took = u64::try_from((crate::now() - start).as_nanos()).unwrap_or(u64::MAX);
+            METRIC_BUSY_NANOS.fetch_add(took, Ordering::Relaxed);
+            METRIC_MAX_TICK_NANOS.fetch_max(took, Ordering::Relaxed);
+        }
+        METRIC_RUN.fetch_add(ran, Ordering::Relaxed);
+        METRIC_LAST_RUN.store(ran, Ordering::Relaxed);
+        METRIC_TICKS.fetch_add(1, Ordering::Relaxed);
+    }
+}
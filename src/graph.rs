@@ -0,0 +1,241 @@
+//! Snapshot of the live object graph, for offline analysis.
+
+use core::{
+    mem,
+    ptr::{self, NonNull},
+    slice,
+};
+
+/// A live GC object and the range of its outgoing pointers in
+/// [`HeapGraph::edges`].
+#[derive(Debug, Clone, Copy)]
+pub struct HeapNode {
+    pub base: *const u8,
+    pub size: usize,
+    first_edge: usize,
+    n_edges: usize,
+}
+
+/// The live objects at the time of the snapshot, and for each one the
+/// (deduplicated) bases of the GC objects it holds pointers to.
+///
+/// Storage is mapped directly from the OS rather than the GC heap, so the
+/// snapshot can be built while the world is stopped and does not perturb
+/// the heap it describes.
+pub struct HeapGraph {
+    nodes: *mut HeapNode,
+    n_nodes: usize,
+    edges: *mut *const u8,
+    n_edges: usize,
+    map_len: usize,
+}
+
+impl HeapGraph {
+    pub fn nodes(&self) -> &[HeapNode] {
+        unsafe { slice_or_empty(self.nodes, self.n_nodes) }
+    }
+
+    /// All edges, grouped by source node.
+    pub fn edges(&self) -> &[*const u8] {
+        unsafe { slice_or_empty(self.edges, self.n_edges) }
+    }
+
+    /// Bases of the objects `node` points to.
+    pub fn edges_of(&self, node: &HeapNode) -> &[*const u8] {
+        &self.edges()[node.first_edge..node.first_edge + node.n_edges]
+    }
+}
+
+impl Drop for HeapGraph {
+    fn drop(&mut self) {
+        if self.map_len != 0 {
+            unsafe { libc::munmap(self.nodes as *mut libc::c_void, self.map_len) };
+        }
+    }
+}
+
+unsafe fn slice_or_empty<'a, T>(ptr: *mut T, len: usize) -> &'a [T] {
+    if len == 0 {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(ptr, len) }
+    }
+}
+
+/// Record every live object, its size, and the objects it points to.
+///
+/// "Live" means marked by the most recent collection; a collection is run
+/// first so that the snapshot is current. The world is then stopped for the
+/// duration of the walk. Every word of every object is treated as a
+/// potential pointer, exactly as the collector's own conservative scan does,
+/// so pointer-free ("atomic") objects may report spurious edges.
+///
+/// Returns `None` if the OS refuses the memory for the snapshot.
+pub fn snapshot_graph() -> Option<HeapGraph> {
+    crate::collect();
+    let _world = crate::stop_the_world();
+
+    let mut counts = (0usize, 0usize);
+    unsafe {
+        crate::GC_enumerate_reachable_objects_inner(count_object, &mut counts as *mut _ as *mut u8)
+    };
+    let (n_nodes, max_edges) = counts;
+
+    let nodes_len = n_nodes * mem::size_of::<HeapNode>();
+    let map_len = nodes_len + max_edges * mem::size_of::<*const u8>();
+    let mut graph = HeapGraph {
+        nodes: ptr::null_mut(),
+        n_nodes: 0,
+        edges: ptr::null_mut(),
+        n_edges: 0,
+        map_len: 0,
+    };
+    if map_len == 0 {
+        return Some(graph);
+    }
+    let base = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            map_len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+    if base == libc::MAP_FAILED {
+        return None;
+    }
+    graph.map_len = map_len;
+    graph.nodes = base as *mut HeapNode;
+    graph.edges = unsafe { (base as *mut u8).add(nodes_len) } as *mut *const u8;
+
+    unsafe {
+        crate::GC_enumerate_reachable_objects_inner(record_object, &mut graph as *mut _ as *mut u8)
+    };
+    Some(graph)
+}
+
+/// Call `f` with the base and size of every object that the most recent
+/// collection found reachable.
+///
+/// Unlike a walk of the heap blocks, objects that are garbage but not yet
+/// swept are skipped. Objects allocated since that collection are not
+/// visited, so call [`crate::collect`] first for an up-to-date view.
+///
+/// The walk runs with the world stopped: `f` must not allocate from the GC
+/// heap or take locks that another thread might hold.
+pub fn for_each_reachable<F: FnMut(NonNull<u8>, usize)>(f: F) {
+    let _world = crate::stop_the_world();
+    enumerate_reachable(f);
+}
+
+// Must be called with the world stopped.
+fn enumerate_reachable<F: FnMut(NonNull<u8>, usize)>(mut f: F) {
+    unsafe extern "C" fn visit<F: FnMut(NonNull<u8>, usize)>(
+        obj: *mut u8,
+        bytes: usize,
+        data: *mut u8,
+    ) {
+        let f = unsafe { &mut *(data as *mut F) };
+        if let Some(obj) = NonNull::new(obj) {
+            f(obj, bytes);
+        }
+    }
+
+    unsafe { crate::GC_enumerate_reachable_objects_inner(visit::<F>, &mut f as *mut F as *mut u8) };
+}
+
+unsafe extern "C" fn count_object(_: *mut u8, bytes: usize, data: *mut u8) {
+    let counts = unsafe { &mut *(data as *mut (usize, usize)) };
+    counts.0 += 1;
+    counts.1 += bytes / mem::size_of::<usize>();
+}
+
+unsafe extern "C" fn record_object(obj: *mut u8, bytes: usize, data: *mut u8) {
+    let graph = unsafe { &mut *(data as *mut HeapGraph) };
+    let first_edge = graph.n_edges;
+    let words = obj as *const usize;
+    for i in 0..bytes / mem::size_of::<usize>() {
+        let candidate = unsafe { words.add(i).read() } as *const u8;
+        if !crate::is_gc_heap_ptr(candidate) {
+            continue;
+        }
+        if let Some(target) = crate::base_of(candidate) {
+            unsafe { graph.edges.add(graph.n_edges).write(target.as_ptr()) };
+            graph.n_edges += 1;
+        }
+    }
+
+    // Outgoing pointers form a set: drop repeated targets.
+    let own = unsafe {
+        slice::from_raw_parts_mut(graph.edges.add(first_edge), graph.n_edges - first_edge)
+    };
+    own.sort_unstable();
+    let mut unique = 0;
+    for i in 0..own.len() {
+        if i == 0 || own[i] != own[unique - 1] {
+            own[unique] = own[i];
+            unique += 1;
+        }
+    }
+    graph.n_edges = first_edge + unique;
+
+    let node = HeapNode { base: obj, size: bytes, first_edge, n_edges: unique };
+    unsafe { graph.nodes.add(graph.n_nodes).write(node) };
+    graph.n_nodes += 1;
+}
+
+/// Read access to the heap while every other thread is suspended, handed to
+/// the closure passed to [`with_heap_frozen`]. Everything it returns borrows
+/// from it, and so cannot outlive the frozen scope.
+pub struct FrozenHeap<'a> {
+    world: &'a crate::WorldStoppedGuard,
+}
+
+impl FrozenHeap<'_> {
+    /// Call `f` with the base and size of every object the collection run
+    /// by [`with_heap_frozen`] found reachable, as [`for_each_reachable`]
+    /// does.
+    pub fn for_each_object<F: FnMut(NonNull<u8>, usize)>(&self, f: F) {
+        enumerate_reachable(f);
+    }
+
+    /// Number of objects [`for_each_object`](Self::for_each_object) visits.
+    pub fn object_count(&self) -> usize {
+        let mut n = 0;
+        self.for_each_object(|_, _| n += 1);
+        n
+    }
+
+    /// The base and size of the GC object `ptr` points into, or `None` if it
+    /// does not point into one.
+    pub fn resolve(&self, ptr: *const u8) -> Option<(NonNull<u8>, usize)> {
+        let base = crate::base_of(ptr)?;
+        Some((base, unsafe { crate::GC_size(base.as_ptr()) }))
+    }
+
+    /// The contents of the GC object `ptr` points into.
+    pub fn bytes(&self, ptr: *const u8) -> Option<&[u8]> {
+        let (base, size) = self.resolve(ptr)?;
+        Some(unsafe { slice::from_raw_parts(base.as_ptr(), size) })
+    }
+
+    pub fn heap_usage(&self) -> crate::HeapUsage {
+        self.world.heap_usage()
+    }
+}
+
+/// Run a full collection, then call `f` with every other registered thread
+/// suspended, for a consistent view of the heap (e.g. from a debugger).
+/// The world is restarted when `f` returns or unwinds.
+///
+/// `f` runs under the restrictions of [`crate::WorldStoppedGuard`]: it must
+/// not allocate from the GC heap, trigger a collection, or take a lock a
+/// suspended thread might hold. Threads trying to allocate meanwhile wait
+/// until `f` has returned.
+pub fn with_heap_frozen<R>(f: impl FnOnce(&FrozenHeap<'_>) -> R) -> R {
+    crate::collect();
+    let world = crate::stop_the_world();
+    f(&FrozenHeap { world: &world })
+}
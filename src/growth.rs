@@ -0,0 +1,86 @@
+//! Log lines marking heap growth, for observability without metrics.
+
+use core::{
+    mem,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static PREVIOUS: AtomicUsize = AtomicUsize::new(0);
+
+// Zero when growth logging is off.
+static DELTA: AtomicUsize = AtomicUsize::new(0);
+// Heap size when logging was (re)configured; growth is measured from here.
+static BASE: AtomicUsize = AtomicUsize::new(0);
+// Multiples of DELTA reached by the heap, and those already logged.
+static REACHED: AtomicUsize = AtomicUsize::new(0);
+static LOGGED: AtomicUsize = AtomicUsize::new(0);
+static LAST_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// Log a line (at `info` level, through the `log` crate) every time the heap
+/// has grown by another `bytes` since this call, e.g. at +64 MiB, +128 MiB,
+/// and so on. Passing 0 turns growth logging off.
+///
+/// The collector reports heap resizes with the world stopped, when logging
+/// could deadlock, so the lines are emitted afterwards by the next thread to
+/// return from an allocation through [`crate::GcAllocator`]. One line is
+/// logged per multiple of `bytes` crossed, even if a single expansion
+/// crossed several.
+pub fn set_growth_log_delta(bytes: usize) {
+    install();
+    DELTA.store(0, Ordering::Relaxed);
+    BASE.store(unsafe { crate::GC_get_heap_size() }, Ordering::Relaxed);
+    REACHED.store(0, Ordering::Relaxed);
+    LOGGED.store(0, Ordering::Relaxed);
+    DELTA.store(bytes, Ordering::Release);
+}
+
+fn install() {
+    if INSTALLED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    unsafe {
+        let prev = crate::GC_get_on_heap_resize();
+        PREVIOUS.store(prev.map_or(0, |f| f as usize), Ordering::Release);
+        crate::GC_set_on_heap_resize(Some(on_heap_resize));
+    }
+}
+
+// Runs with the allocator lock held and the world stopped.
+unsafe extern "C" fn on_heap_resize(new_size: usize) {
+    let delta = DELTA.load(Ordering::Acquire);
+    let base = BASE.load(Ordering::Relaxed);
+    if delta != 0 && new_size > base {
+        LAST_SIZE.store(new_size, Ordering::Relaxed);
+        REACHED.fetch_max((new_size - base) / delta, Ordering::Release);
+    }
+
+    let prev = PREVIOUS.load(Ordering::Acquire);
+    if prev != 0 {
+        let prev: unsafe extern "C" fn(usize) = unsafe { mem::transmute(prev) };
+        unsafe { prev(new_size) };
+    }
+}
+
+#[inline]
+pub(crate) fn log_pending() {
+    if REACHED.load(Ordering::Relaxed) != LOGGED.load(Ordering::Relaxed) {
+        log_growth();
+    }
+}
+
+#[cold]
+fn log_growth() {
+    let reached = REACHED.load(Ordering::Acquire);
+    let logged = LOGGED.load(Ordering::Relaxed);
+    if reached <= logged
+        || LOGGED.compare_exchange(logged, reached, Ordering::Relaxed, Ordering::Relaxed).is_err()
+    {
+        return;
+    }
+    let delta = DELTA.load(Ordering::Relaxed);
+    let size = LAST_SIZE.load(Ordering::Relaxed);
+    for step in logged + 1..=reached {
+        log::info!("GC heap grew by more than {} bytes (heap size now {size})", step * delta);
+    }
+}
@@ -0,0 +1,43 @@
+//! Power-of-two histogram of requested allocation sizes.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of buckets in [`alloc_histogram`]: one per power of two a `usize`
+/// can reach, plus one for sizes 0 and 1.
+pub const BUCKETS: usize = usize::BITS as usize + 1;
+
+static COUNTS: [AtomicU64; BUCKETS] = [const { AtomicU64::new(0) }; BUCKETS];
+
+/// Index of the bucket counting requests of `size` bytes: bucket `k` holds
+/// sizes in `(2^(k-1), 2^k]`, bucket 0 holds sizes 0 and 1.
+pub const fn histogram_bucket(size: usize) -> usize {
+    if size <= 1 {
+        0
+    } else {
+        (usize::BITS - (size - 1).leading_zeros()) as usize
+    }
+}
+
+#[inline]
+pub(crate) fn record(size: usize) {
+    COUNTS[histogram_bucket(size)].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of allocation requests seen per size bucket (see
+/// [`histogram_bucket`]) since start-up or the last
+/// [`reset_alloc_histogram`]. Counters are read one at a time, so the
+/// snapshot is not atomic with respect to concurrent allocations.
+pub fn alloc_histogram() -> [u64; BUCKETS] {
+    let mut out = [0; BUCKETS];
+    for (out, count) in out.iter_mut().zip(&COUNTS) {
+        *out = count.load(Ordering::Relaxed);
+    }
+    out
+}
+
+/// Set every bucket back to zero.
+pub fn reset_alloc_histogram() {
+    for count in &COUNTS {
+        count.store(0, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,95 @@
+//! Bump allocation of data that lives until the process exits.
+
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::ptr::NonNull;
+
+/// A bump allocator over uncollectable slabs, for data that is never freed
+/// (configuration loaded at start-up, interned tables, ...).
+///
+/// Slabs are never reclaimed, so objects in them are never collected and
+/// their destructors never run; dropping the arena does not free them
+/// either, which is what makes the `&'static` references handed out sound.
+/// A scanned arena ([`new`](ImmortalArena::new)) keeps the targets of GC
+/// pointers stored in it alive, like a root. A pointer-free arena
+/// ([`new_atomic`](ImmortalArena::new_atomic)) is not scanned at all, which
+/// removes its contents from the cost of every collection.
+pub struct ImmortalArena {
+    slab_size: usize,
+    atomic: bool,
+    top: Cell<NonNull<u8>>,
+    end: Cell<NonNull<u8>>,
+}
+
+impl ImmortalArena {
+    /// Create a scanned arena that allocates slabs of `slab_size` bytes (or
+    /// larger, for objects that do not fit). No memory is taken up front.
+    pub fn new(slab_size: usize) -> Self {
+        ImmortalArena {
+            slab_size,
+            atomic: false,
+            top: Cell::new(NonNull::dangling()),
+            end: Cell::new(NonNull::dangling()),
+        }
+    }
+
+    /// Create an arena whose slabs are never scanned.
+    ///
+    /// # Safety
+    ///
+    /// Nothing stored in the arena may be the only pointer keeping a GC
+    /// object alive: the collector does not see pointers in it.
+    pub unsafe fn new_atomic(slab_size: usize) -> Self {
+        ImmortalArena { atomic: true, ..ImmortalArena::new(slab_size) }
+    }
+
+    /// Move `value` into the arena for the rest of the process. Returns
+    /// `None` if a new slab is needed and cannot be allocated.
+    pub fn alloc<T: 'static>(&self, value: T) -> Option<&'static T> {
+        let ptr = self.alloc_layout(Layout::new::<T>())?.cast::<T>();
+        unsafe {
+            ptr.as_ptr().write(value);
+            Some(&*ptr.as_ptr())
+        }
+    }
+
+    /// Bump-allocate zeroed memory for `layout`, starting a new slab if the
+    /// current one is full.
+    pub fn alloc_layout(&self, layout: Layout) -> Option<NonNull<u8>> {
+        if layout.size() == 0 {
+            return Some(unsafe { NonNull::new_unchecked(layout.align() as *mut u8) });
+        }
+        if let Some(ptr) = self.bump(layout) {
+            return Some(ptr);
+        }
+        let size = self.slab_size.max(layout.size().checked_add(layout.align() - 1)?);
+        let slab = NonNull::new(unsafe {
+            if self.atomic {
+                // Unlike the scanned kind, this one is not cleared.
+                let slab = crate::GC_malloc_atomic_uncollectable(size);
+                if !slab.is_null() {
+                    slab.write_bytes(0, size);
+                }
+                slab
+            } else {
+                crate::GC_malloc_uncollectable(size)
+            }
+        })?;
+        self.top.set(slab);
+        self.end.set(unsafe { slab.add(size) });
+        self.bump(layout)
+    }
+
+    #[inline]
+    fn bump(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let top = self.top.get().as_ptr() as usize;
+        let start = top.checked_next_multiple_of(layout.align())?;
+        let end = start.checked_add(layout.size())?;
+        if end > self.end.get().as_ptr() as usize {
+            return None;
+        }
+        let start = unsafe { self.top.get().add(start - top) };
+        self.top.set(unsafe { start.add(layout.size()) });
+        Some(start)
+    }
+}
@@ -0,0 +1,63 @@
+//! A cap on large allocations made between two collections.
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Allocations of at least this many bytes count towards the limit set with
+/// [`set_large_alloc_concurrency_limit`].
+pub const LARGE_ALLOC_THRESHOLD: usize = 1 << 20;
+
+// Zero when no limit is set.
+static LIMIT: AtomicUsize = AtomicUsize::new(0);
+// Bytes of large allocations made since the last collection.
+static INFLIGHT: AtomicUsize = AtomicUsize::new(0);
+// Set while a thread runs the collection the limit asked for.
+static COLLECTING: AtomicBool = AtomicBool::new(false);
+
+/// Run a full collection before an allocation of at least
+/// [`LARGE_ALLOC_THRESHOLD`] bytes would take the large allocations made
+/// since the last collection past `max_inflight_bytes`. Zero removes the
+/// limit.
+///
+/// A burst of large allocations from many threads can otherwise grow the
+/// heap (and the process's resident memory) faster than the collector's own
+/// schedule reacts. The accounting is approximate: there is no telling when
+/// a large object becomes garbage, so the count is only reset by a
+/// collection, whatever started it. While one thread collects, others that
+/// cross the limit allocate without waiting for it.
+pub fn set_large_alloc_concurrency_limit(max_inflight_bytes: usize) {
+    LIMIT.store(max_inflight_bytes, Ordering::Release);
+    if max_inflight_bytes != 0 {
+        crate::events::install();
+    }
+}
+
+/// Bytes of large allocations counted since the last collection.
+pub fn large_alloc_inflight_bytes() -> usize {
+    INFLIGHT.load(Ordering::Relaxed)
+}
+
+/// Account for an allocation of `size` bytes about to be made, collecting
+/// first if it would exceed the limit.
+#[inline]
+pub(crate) fn admit(size: usize) {
+    if size < LARGE_ALLOC_THRESHOLD {
+        return;
+    }
+    let limit = LIMIT.load(Ordering::Relaxed);
+    if limit == 0 {
+        return;
+    }
+    let inflight = INFLIGHT.fetch_add(size, Ordering::Relaxed) + size;
+    if inflight > limit && !COLLECTING.swap(true, Ordering::Acquire) {
+        crate::events::gcollect(crate::CollectionCause::Explicit);
+        COLLECTING.store(false, Ordering::Release);
+        // The collection reset the count; this allocation is still to come.
+        INFLIGHT.fetch_add(size, Ordering::Relaxed);
+    }
+}
+
+/// Called at the end of every collection, with the allocator lock held.
+#[inline]
+pub(crate) fn reset() {
+    INFLIGHT.store(0, Ordering::Relaxed);
+}
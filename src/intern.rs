@@ -0,0 +1,305 @@
+//! Weak interning of byte strings and other immutable values.
+
+use core::alloc::Layout;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::{self, NonNull};
+use core::slice;
+
+use crate::finalize::DropValue;
+
+#[repr(C)]
+struct Slot {
+    // A disappearing link: cleared by the collector once the object dies.
+    ptr: *mut u8,
+    // Length of an interned byte string; unused for other values.
+    len: usize,
+    hash: u64,
+    // Whether the slot was ever filled. A used slot whose `ptr` has been
+    // cleared is a tombstone: lookups probe past it, inserts reuse it.
+    used: bool,
+}
+
+const MIN_CAPACITY: usize = 16;
+
+// An open-addressing table of weakly held objects. The table lives in
+// uncollectable, unscanned memory, freed on drop; callers consult it with
+// collection disabled so that links cannot be cleared mid-lookup.
+struct WeakTable {
+    slots: *mut Slot,
+    capacity: usize,
+    used: usize,
+}
+
+impl WeakTable {
+    const fn new() -> Self {
+        WeakTable { slots: ptr::null_mut(), capacity: 0, used: 0 }
+    }
+
+    fn live(&self) -> usize {
+        self.slots().iter().filter(|slot| !slot.ptr.is_null()).count()
+    }
+
+    fn slots(&self) -> &[Slot] {
+        if self.slots.is_null() {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.slots, self.capacity) }
+    }
+
+    unsafe fn find(&self, hash: u64, mut eq: impl FnMut(&Slot) -> bool) -> Option<&Slot> {
+        if self.capacity == 0 {
+            return None;
+        }
+        let mask = self.capacity - 1;
+        let mut i = hash as usize & mask;
+        loop {
+            let slot = unsafe { &*self.slots.add(i) };
+            if !slot.used {
+                return None;
+            }
+            if !slot.ptr.is_null() && slot.hash == hash && eq(slot) {
+                return Some(slot);
+            }
+            i = (i + 1) & mask;
+        }
+    }
+
+    /// Make room for one more entry. Returns `None` if the table needs to
+    /// grow and cannot.
+    unsafe fn reserve_one(&mut self) -> Option<()> {
+        if (self.used + 1) * 4 > self.capacity * 3 {
+            unsafe { self.rebuild() }
+        } else {
+            Some(())
+        }
+    }
+
+    /// Add an entry in the first free slot. Returns `None`, leaving the
+    /// table as it was, if the collector cannot register the link.
+    unsafe fn insert(&mut self, obj: *mut u8, len: usize, hash: u64) -> Option<()> {
+        let mask = self.capacity - 1;
+        let mut i = hash as usize & mask;
+        loop {
+            let slot = unsafe { &mut *self.slots.add(i) };
+            if slot.ptr.is_null() {
+                let was_used = slot.used;
+                *slot = Slot { ptr: obj, len, hash, used: true };
+                // 0 is GC_SUCCESS; anything else leaves the link unregistered.
+                if unsafe { crate::GC_general_register_disappearing_link(&mut slot.ptr, obj) } != 0
+                {
+                    slot.ptr = ptr::null_mut();
+                    slot.used = was_used;
+                    return None;
+                }
+                if !was_used {
+                    self.used += 1;
+                }
+                return Some(());
+            }
+            i = (i + 1) & mask;
+        }
+    }
+
+    /// Move the live entries to a fresh table sized for them, dropping the
+    /// tombstones.
+    unsafe fn rebuild(&mut self) -> Option<()> {
+        let capacity = ((self.live() + 1) * 2).next_power_of_two().max(MIN_CAPACITY);
+        let bytes = capacity.checked_mul(mem::size_of::<Slot>())?;
+        let slots = unsafe { crate::GC_malloc_atomic_uncollectable(bytes) } as *mut Slot;
+        if slots.is_null() {
+            return None;
+        }
+        unsafe { ptr::write_bytes(slots, 0, capacity) };
+
+        let old = mem::replace(self, WeakTable { slots, capacity, used: 0 });
+        for slot in old.slots() {
+            if !slot.ptr.is_null()
+                && unsafe { self.insert(slot.ptr, slot.len, slot.hash) }.is_none()
+            {
+                // Keep the old table; dropping the new one unregisters the
+                // links moved so far.
+                drop(mem::replace(self, old));
+                return None;
+            }
+        }
+        // Dropping `old` unregisters its links and frees its table.
+        Some(())
+    }
+}
+
+impl Drop for WeakTable {
+    fn drop(&mut self) {
+        if self.slots.is_null() {
+            return;
+        }
+        for i in 0..self.capacity {
+            let slot = unsafe { &mut *self.slots.add(i) };
+            if !slot.ptr.is_null() {
+                unsafe { crate::GC_unregister_disappearing_link(&mut slot.ptr) };
+            }
+        }
+        unsafe { crate::GC_free(self.slots as *mut u8) };
+    }
+}
+
+/// A set of interned byte strings, so that equal contents share a single
+/// pointer-free GC object.
+///
+/// The table only holds its entries weakly: an interned string that is no
+/// longer referenced elsewhere is collected as usual, and its entry is
+/// dropped. The table itself lives in uncollectable, unscanned memory
+/// (freed when the interner is dropped) and is consulted with collection
+/// briefly disabled, so entries cannot be cleared half-way through a
+/// lookup.
+pub struct ByteInterner {
+    table: WeakTable,
+}
+
+impl ByteInterner {
+    pub const fn new() -> Self {
+        ByteInterner { table: WeakTable::new() }
+    }
+
+    /// Return the interned copy of `bytes`, allocating it (see
+    /// [`crate::gc_bytes`]) if no live copy exists. Returns `None` if an
+    /// allocation, or registering the new entry with the collector, fails.
+    pub fn intern(&mut self, bytes: &[u8]) -> Option<NonNull<[u8]>> {
+        let mut hasher = Fnv::new();
+        hasher.write(bytes);
+        let hash = hasher.finish();
+        without_collection(|| unsafe {
+            let found = self.table.find(hash, |slot| {
+                slot.len == bytes.len() && slice::from_raw_parts(slot.ptr, slot.len) == bytes
+            });
+            if let Some(slot) = found {
+                return Some(NonNull::slice_from_raw_parts(
+                    NonNull::new_unchecked(slot.ptr),
+                    slot.len,
+                ));
+            }
+            self.table.reserve_one()?;
+            let obj = crate::gc_bytes(bytes)?;
+            self.table.insert(obj.cast::<u8>().as_ptr(), bytes.len(), hash)?;
+            Some(obj)
+        })
+    }
+
+    /// Number of interned strings still alive.
+    pub fn len(&self) -> usize {
+        without_collection(|| self.table.live())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for ByteInterner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A set of interned values, so that equal values share a single GC object
+/// and can be compared by address.
+///
+/// Like [`ByteInterner`], the table holds its entries weakly: a value that
+/// is no longer referenced elsewhere is collected, its destructor run by a
+/// finalizer, and its entry pruned. Interned values are shared, so they must
+/// not be mutated.
+pub struct GcInterner<T> {
+    table: WeakTable,
+    _values: PhantomData<T>,
+}
+
+impl<T: Eq + Hash> GcInterner<T> {
+    pub const fn new() -> Self {
+        GcInterner { table: WeakTable::new(), _values: PhantomData }
+    }
+
+    /// Return the interned value equal to `value`, moving `value` into a new
+    /// GC object if no live one exists (and dropping it otherwise). Returns
+    /// `None`, dropping `value`, if an allocation or registering the new
+    /// entry with the collector fails.
+    pub fn intern(&mut self, value: T) -> Option<NonNull<T>> {
+        let mut hasher = Fnv::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        without_collection(|| unsafe {
+            let found = self.table.find(hash, |slot| *(slot.ptr as *const T) == value);
+            if let Some(slot) = found {
+                return Some(NonNull::new_unchecked(slot.ptr as *mut T));
+            }
+            self.table.reserve_one()?;
+            let layout = Layout::new::<T>();
+            // Every interned value needs an address of its own.
+            let size = layout.size().max(1);
+            let obj = crate::gc_malloc(Layout::from_size_align_unchecked(size, layout.align()));
+            if obj.is_null() {
+                return None;
+            }
+            // Entered before the value is moved in, so that failing drops
+            // `value` here rather than leaving it to a finalizer.
+            self.table.insert(obj, 0, hash)?;
+            (obj as *mut T).write(value);
+            if mem::needs_drop::<T>() {
+                crate::finalize::drop_when_unreachable::<DropValue<T>>(obj, ptr::null_mut());
+            }
+            Some(NonNull::new_unchecked(obj as *mut T))
+        })
+    }
+
+    /// Number of interned values still alive.
+    pub fn len(&self) -> usize {
+        without_collection(|| self.table.live())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Eq + Hash> Default for GcInterner<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Keeps the collector from clearing links while `f` reads them.
+fn without_collection<R>(f: impl FnOnce() -> R) -> R {
+    struct Enable;
+
+    impl Drop for Enable {
+        fn drop(&mut self) {
+            unsafe { crate::GC_enable() };
+        }
+    }
+
+    unsafe { crate::GC_disable() };
+    let _enable = Enable;
+    f()
+}
+
+// FNV-1a.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
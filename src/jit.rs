@@ -0,0 +1,118 @@
+//! GC-allocated buffers for generated machine code.
+
+/// A buffer of machine code in the GC heap that is filled while writable and
+/// then frozen before being executed.
+///
+/// The collector maps its heap read-write-execute when [`pages_executable`]
+/// is on, so the hardware does not enforce W^X for GC memory. `JitBuffer`
+/// enforces it logically instead: after [`finalize`] no more writes are
+/// accepted, so code that may be running is never modified.
+///
+/// The code is pointer-free and allocated as such, so the collector never
+/// scans it. The buffer is kept alive by the `JitBuffer` itself, which must
+/// therefore be stored somewhere the collector scans (the stack, or GC
+/// memory), as must any raw pointer to the code that outlives it.
+///
+/// [`pages_executable`]: crate::pages_executable
+/// [`finalize`]: JitBuffer::finalize
+pub struct JitBuffer {
+    ptr: *mut u8,
+    capacity: usize,
+    len: usize,
+    finalized: bool,
+}
+
+impl JitBuffer {
+    /// Allocate an empty, writable buffer of `capacity` bytes. Returns `None`
+    /// if GC pages are not executable or the allocation fails.
+    pub fn new(capacity: usize) -> Option<Self> {
+        if !crate::pages_executable() {
+            return None;
+        }
+        let ptr = unsafe { crate::GC_malloc_atomic(capacity.max(1)) };
+        if ptr.is_null() {
+            return None;
+        }
+        Some(JitBuffer { ptr, capacity, len: 0, finalized: false })
+    }
+
+    /// Append `code` to the buffer.
+    ///
+    /// # Panics
+    ///
+    /// If the buffer has been finalized or `code` does not fit.
+    pub fn write(&mut self, code: &[u8]) {
+        assert!(!self.finalized, "write to a JitBuffer after it was finalized");
+        assert!(code.len() <= self.capacity - self.len, "JitBuffer overflow");
+        unsafe { self.ptr.add(self.len).copy_from_nonoverlapping(code.as_ptr(), code.len()) };
+        self.len += code.len();
+    }
+
+    /// Freeze the buffer, make the written code visible to instruction
+    /// fetch, and return the address of its first instruction. Further calls
+    /// return the same address.
+    pub fn finalize(&mut self) -> *const u8 {
+        if !self.finalized {
+            unsafe { flush_icache(self.ptr, self.len) };
+            self.finalized = true;
+        }
+        self.ptr
+    }
+
+    pub fn is_finalized(&self) -> bool {
+        self.finalized
+    }
+
+    /// Number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+/// Synchronise the instruction cache with data just written to
+/// `start..start + len`, the equivalent of `__builtin___clear_cache`.
+///
+/// x86_64 keeps instruction fetch coherent with stores on its own; only the
+/// compiler has to be kept from sinking the stores past this point.
+#[cfg(target_arch = "x86_64")]
+unsafe fn flush_icache(_start: *mut u8, _len: usize) {
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+/// Synchronise the instruction cache with data just written to
+/// `start..start + len`, the equivalent of `__builtin___clear_cache`: clean
+/// the data cache lines to the point of unification, invalidate the
+/// instruction cache lines, and resynchronise the pipeline.
+#[cfg(target_arch = "aarch64")]
+unsafe fn flush_icache(start: *mut u8, len: usize) {
+    use core::arch::asm;
+
+    let ctr: u64;
+    unsafe { asm!("mrs {}, ctr_el0", out(reg) ctr, options(nomem, nostack)) };
+    let dline = 4usize << ((ctr >> 16) & 0xf);
+    let iline = 4usize << (ctr & 0xf);
+    let start = start as usize;
+    let end = start + len;
+
+    let mut addr = start & !(dline - 1);
+    while addr < end {
+        unsafe { asm!("dc cvau, {}", in(reg) addr, options(nostack)) };
+        addr += dline;
+    }
+    unsafe { asm!("dsb ish", options(nostack)) };
+
+    let mut addr = start & !(iline - 1);
+    while addr < end {
+        unsafe { asm!("ic ivau, {}", in(reg) addr, options(nostack)) };
+        addr += iline;
+    }
+    unsafe { asm!("dsb ish", "isb", options(nostack)) };
+}
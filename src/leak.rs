@@ -0,0 +1,131 @@
+//! Leak detection from the trend of the live set across collections.
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Number of post-collection samples a [`LeakTrendMonitor`] keeps.
+pub const LEAK_TREND_SAMPLES: usize = 64;
+
+/// A ring buffer of live-set sizes, one per collection, with a least-squares
+/// estimate of how fast the live set is growing.
+///
+/// A program whose live set keeps rising from one collection to the next is
+/// usually leaking, whatever the absolute numbers. Samples can be fed by hand
+/// with [`record`](Self::record), or taken automatically after every
+/// collection with [`start_sampling`](Self::start_sampling) and read back
+/// with [`sampled`](Self::sampled).
+#[derive(Debug, Clone, Copy)]
+pub struct LeakTrendMonitor {
+    samples: [usize; LEAK_TREND_SAMPLES],
+    // Total number of samples recorded; the newest is at `(recorded - 1) %
+    // LEAK_TREND_SAMPLES`.
+    recorded: usize,
+}
+
+static SAMPLES: [AtomicUsize; LEAK_TREND_SAMPLES] =
+    [const { AtomicUsize::new(0) }; LEAK_TREND_SAMPLES];
+static RECORDED: AtomicUsize = AtomicUsize::new(0);
+static SAMPLING: AtomicBool = AtomicBool::new(false);
+
+impl LeakTrendMonitor {
+    pub const fn new() -> Self {
+        LeakTrendMonitor { samples: [0; LEAK_TREND_SAMPLES], recorded: 0 }
+    }
+
+    /// Add the live-set size after a collection, dropping the oldest sample
+    /// once [`LEAK_TREND_SAMPLES`] are held.
+    pub fn record(&mut self, live_bytes: usize) {
+        self.samples[self.recorded % LEAK_TREND_SAMPLES] = live_bytes;
+        self.recorded += 1;
+    }
+
+    /// Number of samples currently held.
+    pub fn len(&self) -> usize {
+        self.recorded.min(LEAK_TREND_SAMPLES)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recorded == 0
+    }
+
+    /// The held samples, oldest first.
+    pub fn samples(&self) -> impl Iterator<Item = usize> + '_ {
+        let start = self.recorded - self.len();
+        (start..self.recorded).map(|i| self.samples[i % LEAK_TREND_SAMPLES])
+    }
+
+    /// Growth of the live set in bytes per collection, as the slope of the
+    /// least-squares line through the held samples. Zero with fewer than two
+    /// samples.
+    pub fn slope(&self) -> f64 {
+        let n = self.len();
+        if n < 2 {
+            return 0.0;
+        }
+        let mean_x = (n - 1) as f64 / 2.0;
+        let mean_y = self.samples().map(|y| y as f64).sum::<f64>() / n as f64;
+        let (mut cov, mut var) = (0.0, 0.0);
+        for (x, y) in self.samples().enumerate() {
+            let dx = x as f64 - mean_x;
+            cov += dx * (y as f64 - mean_y);
+            var += dx * dx;
+        }
+        cov / var
+    }
+
+    /// Whether the live set is growing by more than
+    /// `threshold_bytes_per_collection` bytes per collection on average.
+    /// Always false with fewer than two samples.
+    pub fn is_leaking(&self, threshold_bytes_per_collection: f64) -> bool {
+        self.len() >= 2 && self.slope() > threshold_bytes_per_collection
+    }
+
+    /// Sample the live set (heap size minus free bytes) after every
+    /// collection from now on. The sample is taken on the first allocation
+    /// after the collection, or on return from [`crate::collect`] or
+    /// [`crate::run_pending_gc_hooks`] if that comes first, not during it,
+    /// so collections that complete before then share one sample.
+    pub fn start_sampling() {
+        SAMPLING.store(true, Ordering::Release);
+        crate::events::install();
+    }
+
+    /// Stop the sampling begun by [`start_sampling`](Self::start_sampling).
+    /// The samples taken so far are kept.
+    pub fn stop_sampling() {
+        SAMPLING.store(false, Ordering::Release);
+    }
+
+    /// A copy of the samples taken since [`start_sampling`](Self::start_sampling).
+    /// Samples are read one at a time, so one taken concurrently may be
+    /// missing or duplicated.
+    pub fn sampled() -> Self {
+        let mut monitor = LeakTrendMonitor::new();
+        monitor.recorded = RECORDED.load(Ordering::Acquire);
+        for (out, sample) in monitor.samples.iter_mut().zip(&SAMPLES) {
+            *out = sample.load(Ordering::Relaxed);
+        }
+        monitor
+    }
+}
+
+impl Default for LeakTrendMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline]
+pub(crate) fn sampling_active() -> bool {
+    SAMPLING.load(Ordering::Relaxed)
+}
+
+pub(crate) fn sample() {
+    if !sampling_active() {
+        return;
+    }
+    let usage = crate::heap_usage();
+    let live = usage.heap_size.saturating_sub(usage.free_bytes);
+    // Claim the slot first so that concurrent samples land in different ones.
+    let i = RECORDED.fetch_add(1, Ordering::AcqRel);
+    SAMPLES[i % LEAK_TREND_SAMPLES].store(live, Ordering::Relaxed);
+}
@@ -1,14 +1,133 @@
 #![feature(allocator_api)]
 #![feature(alloc_layout_extra)]
+#![feature(thread_local)]
+
+#[cfg(all(feature = "parallel-mark", target_os = "linux"))]
+mod affinity;
+mod alert;
+mod any;
+#[cfg(feature = "allocator-api2")]
+mod api2;
+mod arena;
+#[cfg(feature = "backtrace")]
+mod backtrace;
+mod capabilities;
+mod ceiling;
+mod cow;
+mod diagnostic;
+mod events;
+#[cfg(feature = "test-fault-injection")]
+mod fault;
+mod finalize;
+mod graph;
+#[cfg(feature = "log")]
+mod growth;
+#[cfg(feature = "alloc-histogram")]
+mod histogram;
+mod immortal;
+mod inflight;
+mod intern;
+mod jit;
+mod leak;
+mod mark;
+mod nursery;
+mod pointer_free;
+#[cfg(feature = "poison-on-reclaim")]
+mod poison;
+mod project;
+#[cfg(target_os = "linux")]
+mod resident;
+#[cfg(feature = "detect-resurrection")]
+mod resurrection;
+mod signals;
+mod split;
+mod stats;
+pub mod testing;
+mod thread;
+mod tuning;
+mod warn;
+mod weak;
+mod writer;
+
+#[cfg(all(feature = "parallel-mark", target_os = "linux"))]
+pub use affinity::set_marker_affinity;
+pub use alert::{Alert, AlertMetric, AlertMonitor, ALERT_HYSTERESIS, MAX_ALERT_RULES};
+pub use any::GcAny;
+pub use arena::GcArena;
+#[cfg(feature = "backtrace")]
+pub use backtrace::{allocation_backtrace, Backtrace};
+pub use capabilities::{capabilities, gc_version, GcCapabilities};
+pub use ceiling::{enforce_memory_ceiling, memory_use, CeilingAction};
+pub use cow::GcCow;
+pub use diagnostic::{
+    enable_corruption_postmortem, install_diagnostic_abort_hook, registration_hint,
+};
+pub use events::{
+    clear_collection_cause_callback, clear_post_collection_hook, run_pending_gc_hooks,
+    set_collection_cause_callback, set_post_collection_hook, CollectionCause,
+};
+#[cfg(feature = "test-fault-injection")]
+pub use fault::{clear_fault_injector, set_fault_injector};
+pub use finalize::{
+    invoke_finalizers, invoke_finalizers_within, pending_finalizers, start_metered_finalizer_thread,
+    FinalizerMetrics, FinalizerThreadHandle,
+};
+pub use graph::{
+    for_each_reachable, snapshot_graph, with_heap_frozen, FrozenHeap, HeapGraph, HeapNode,
+};
+#[cfg(feature = "log")]
+pub use growth::set_growth_log_delta;
+#[cfg(feature = "alloc-histogram")]
+pub use histogram::{alloc_histogram, histogram_bucket, reset_alloc_histogram, BUCKETS};
+pub use immortal::ImmortalArena;
+pub use inflight::{
+    large_alloc_inflight_bytes, set_large_alloc_concurrency_limit, LARGE_ALLOC_THRESHOLD,
+};
+pub use intern::{ByteInterner, GcInterner};
+pub use jit::JitBuffer;
+pub use leak::{LeakTrendMonitor, LEAK_TREND_SAMPLES};
+pub use mark::{register_mark_proc, MarkProcKind, Marker};
+pub use nursery::Nursery;
+pub use pointer_free::{gc_new_atomic, NoGcPointers};
+#[cfg(feature = "poison-on-reclaim")]
+pub use poison::POISON_BYTE;
+pub use project::GcProjected;
+#[cfg(feature = "detect-resurrection")]
+pub use resurrection::{resurrection_count, watch_for_resurrection};
+pub use signals::{
+    reserve_signals, restart_signal, set_gc_signals, suspend_signal, SignalConflict,
+};
+pub use split::SplitAlloc;
+pub use stats::{
+    format_bytes, health, memory_breakdown, GcHealth, GcSnapshot, HealthStatus, MemoryBreakdown,
+    HEALTH_CRITICAL_FREE_PERCENT, HEALTH_WARN_FREE_PERCENT,
+};
+pub use thread::{
+    auto_register_current_thread, blocking_read, blocking_write, call_with_gc_active, do_blocking,
+    ensure_thread_registered, flush_thread_cache, thread_is_registered,
+};
+pub use tuning::{apply_preset, auto_tune_overhead, GcPreset};
+pub use warn::{
+    recent_warnings, set_classified_warn_handler, set_warning_buffer_capacity, with_warn_handler,
+    GcWarning, MAX_BUFFERED_WARNINGS,
+};
+pub use weak::{gc_new_cyclic, GcWeak};
+pub use writer::GcWriter;
+
+#[cfg(target_os = "linux")]
+pub use resident::{resident_report, ResidentReport};
 
 use core::{
     alloc::{AllocError, Allocator, GlobalAlloc, Layout},
     cmp::self,
+    fmt,
     ptr::{self, NonNull},
+    sync::atomic::{AtomicBool, AtomicU32, AtomicU8, AtomicUsize, Ordering},
+    time::Duration,
 };
 
 #[repr(C)]
-#[derive(Default)]
+#[derive(Debug, Default, Clone, Copy)]
 pub struct ProfileStats {
     /// Heap size in bytes (including area unmapped to OS).
     pub heapsize_full: usize,
@@ -37,6 +156,28 @@ pub struct ProfileStats {
     pub expl_freed_bytes_since_gc: usize,
 }
 
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HeapUsage {
+    /// Heap size in bytes (including area unmapped to OS).
+    pub heap_size: usize,
+    /// Total bytes contained in free and unmapped blocks.
+    pub free_bytes: usize,
+    /// Amount of memory unmapped to OS.
+    pub unmapped_bytes: usize,
+    /// Number of bytes allocated since the recent collection.
+    pub bytes_since_gc: usize,
+    /// Total number of bytes allocated in this process.
+    /// The value may wrap.
+    pub total_bytes: usize,
+}
+
+/// The cold end of a thread's stack (`struct GC_stack_base`).
+#[repr(C)]
+#[derive(Debug)]
+pub struct StackBase {
+    pub mem_base: *mut u8,
+}
+
 #[link(name = "gc")]
 extern "C" {
     pub fn GC_malloc(nbytes: usize) -> *mut u8;
@@ -95,11 +236,259 @@ extern "C" {
     pub fn GC_invoke_finalizers() -> u64;
 
     pub fn GC_get_gc_no() -> u64;
+
+    pub fn GC_clear_stack(arg: *mut u8) -> *mut u8;
+
+    pub fn GC_call_with_alloc_lock(
+        f: unsafe extern "C" fn(*mut u8) -> *mut u8,
+        client_data: *mut u8,
+    ) -> *mut u8;
+
+    pub fn GC_set_disable_automatic_collection(value: i32);
+
+    pub fn GC_get_disable_automatic_collection() -> i32;
+
+    pub fn GC_stop_world_external();
+
+    pub fn GC_start_world_external();
+
+    pub fn GC_get_heap_usage_safe(
+        heap_size: *mut usize,
+        free_bytes: *mut usize,
+        unmapped_bytes: *mut usize,
+        bytes_since_gc: *mut usize,
+        total_bytes: *mut usize,
+    );
+
+    pub fn GC_get_heap_size() -> usize;
+
+    pub fn GC_get_free_bytes() -> usize;
+
+    pub fn GC_get_unmapped_bytes() -> usize;
+
+    pub fn GC_get_bytes_since_gc() -> usize;
+
+    pub fn GC_get_total_bytes() -> usize;
+
+    pub fn GC_malloc_atomic_uncollectable(nbytes: usize) -> *mut u8;
+
+    pub fn GC_general_register_disappearing_link(link: *mut *mut u8, obj: *const u8) -> i32;
+
+    pub fn GC_unregister_disappearing_link(link: *mut *mut u8) -> i32;
+
+    pub fn GC_is_heap_ptr(ptr: *const u8) -> i32;
+
+    pub fn GC_size(ptr: *const u8) -> usize;
+
+    pub fn GC_enumerate_reachable_objects_inner(
+        f: unsafe extern "C" fn(*mut u8, usize, *mut u8),
+        client_data: *mut u8,
+    );
+
+    pub static GC_least_plausible_heap_addr: *mut u8;
+
+    pub static GC_greatest_plausible_heap_addr: *mut u8;
+
+    pub fn GC_new_free_list() -> *mut *mut u8;
+
+    pub fn GC_new_kind(free_list: *mut *mut u8, descr: usize, adjust: i32, clear: i32) -> u32;
+
+    pub fn GC_new_proc(
+        f: unsafe extern "C" fn(*mut usize, *mut u8, *mut u8, usize) -> *mut u8,
+    ) -> u32;
+
+    pub fn GC_generic_malloc(nbytes: usize, kind: i32) -> *mut u8;
+
+    pub fn GC_mark_and_push(
+        obj: *mut u8,
+        mark_stack_top: *mut u8,
+        mark_stack_limit: *mut u8,
+        src: *mut *mut u8,
+    ) -> *mut u8;
+
+    pub fn GC_is_marked(ptr: *const u8) -> i32;
+
+    pub fn GC_set_on_collection_event(f: Option<unsafe extern "C" fn(i32)>);
+
+    pub fn GC_get_on_collection_event() -> Option<unsafe extern "C" fn(i32)>;
+
+    pub fn GC_get_prof_stats(stats: *mut ProfileStats, stats_size: usize) -> usize;
+
+    pub fn GC_register_altstack(
+        normstack: *mut u8,
+        normstack_size: usize,
+        altstack: *mut u8,
+        altstack_size: usize,
+    );
+
+    pub fn GC_set_warn_proc(f: unsafe extern "C" fn(*const libc::c_char, usize));
+
+    pub fn GC_get_warn_proc() -> unsafe extern "C" fn(*const libc::c_char, usize);
+
+    pub fn GC_set_max_heap_size(nbytes: usize);
+
+    pub fn GC_start_mark_threads();
+
+    pub fn GC_get_parallel() -> i32;
+
+    pub fn GC_allow_register_threads();
+
+    pub fn GC_malloc_atomic(nbytes: usize) -> *mut u8;
+
+    pub fn GC_set_pages_executable(value: i32);
+
+    pub fn GC_get_pages_executable() -> i32;
+
+    pub fn GC_malloc_uncollectable(nbytes: usize) -> *mut u8;
+
+    pub fn GC_set_free_space_divisor(value: usize);
+
+    pub fn GC_get_free_space_divisor() -> usize;
+
+    pub fn GC_set_full_freq(value: i32);
+
+    pub fn GC_get_full_freq() -> i32;
+
+    pub fn GC_set_time_limit(ms: libc::c_ulong);
+
+    pub fn GC_get_time_limit() -> libc::c_ulong;
+
+    pub fn GC_enable_incremental();
+
+    pub fn GC_is_incremental_mode() -> i32;
+
+    pub fn GC_set_await_finalize_proc(f: Option<unsafe extern "C" fn(*mut u8)>);
+
+    pub fn GC_get_await_finalize_proc() -> Option<unsafe extern "C" fn(*mut u8)>;
+
+    pub fn GC_collect_a_little() -> i32;
+
+    pub fn GC_is_init_called() -> i32;
+
+    pub fn GC_set_suspend_signal(sig: i32);
+
+    pub fn GC_get_suspend_signal() -> i32;
+
+    pub fn GC_set_thr_restart_signal(sig: i32);
+
+    pub fn GC_get_thr_restart_signal() -> i32;
+
+    pub fn GC_get_all_interior_pointers() -> i32;
+
+    pub fn GC_set_handle_fork(value: i32);
+
+    pub fn GC_get_stack_base(sb: *mut StackBase) -> i32;
+
+    pub fn GC_register_my_thread(sb: *const StackBase) -> i32;
+
+    pub fn GC_unregister_my_thread() -> i32;
+
+    pub fn GC_start_incremental_collection();
+
+    pub fn GC_set_on_heap_resize(f: Option<unsafe extern "C" fn(usize)>);
+
+    pub fn GC_get_on_heap_resize() -> Option<unsafe extern "C" fn(usize)>;
+
+    pub fn GC_ptr_store_and_dirty(p: *mut u8, q: *const u8);
+
+    pub fn GC_disable();
+
+    pub fn GC_enable();
+
+    pub fn GC_do_blocking(f: unsafe extern "C" fn(*mut u8) -> *mut u8, data: *mut u8) -> *mut u8;
+
+    pub fn GC_call_with_gc_active(
+        f: unsafe extern "C" fn(*mut u8) -> *mut u8,
+        data: *mut u8,
+    ) -> *mut u8;
+
+    pub fn GC_set_abort_func(f: unsafe extern "C" fn(*const libc::c_char));
+
+    pub fn GC_get_abort_func() -> unsafe extern "C" fn(*const libc::c_char);
+
+    pub fn GC_set_oom_fn(f: unsafe extern "C" fn(usize) -> *mut u8);
+
+    pub fn GC_get_oom_fn() -> unsafe extern "C" fn(usize) -> *mut u8;
+
+    pub fn GC_malloc_atomic_ignore_off_page(nbytes: usize) -> *mut u8;
+
+    pub fn GC_get_version() -> u32;
+
+    pub fn GC_expand_hp(nbytes: usize) -> i32;
+
+    pub fn GC_get_prof_stats_unsafe(stats: *mut ProfileStats, stats_size: usize) -> usize;
+
+    pub fn GC_get_obtained_from_os_bytes() -> usize;
+
+    pub fn GC_atfork_prepare();
+
+    pub fn GC_atfork_parent();
+
+    pub fn GC_atfork_child();
+
+    pub fn GC_set_interrupt_finalizers(value: u32);
+
+    pub fn GC_get_interrupt_finalizers() -> u32;
+
+    pub fn GC_gcollect_and_unmap();
+
+    pub fn GC_dump_named(name: *const libc::c_char);
+
+    pub fn GC_register_disclaim_proc(
+        kind: i32,
+        proc_: unsafe extern "C" fn(*mut u8) -> libc::c_int,
+        mark_from_all: i32,
+    );
+
+    pub fn GC_foreach_heap_section_inner(
+        f: unsafe extern "C" fn(*mut u8, *mut u8, libc::c_int, *mut u8),
+        client_data: *mut u8,
+    );
 }
 
+// With `interpose-threads`, the object in libgc.a that defines `GC_pthread_create`
+// also defines the interposing `pthread_create`. Referencing it here makes sure
+// that archive member is linked, and a definition from a regular object takes
+// precedence over libc's regardless of link order.
+#[cfg(feature = "interpose-threads")]
+#[used]
+static FORCE_PTHREAD_WRAPPERS: unsafe extern "C" fn(
+    *mut libc::pthread_t,
+    *const libc::pthread_attr_t,
+    extern "C" fn(*mut libc::c_void) -> *mut libc::c_void,
+    *mut libc::c_void,
+) -> libc::c_int = GC_pthread_create;
+
 // Fast-path for low alignment values
 pub const MIN_ALIGN: usize = 8;
 
+/// The alignment `GC_malloc` guarantees: one granule, two pointers.
+pub const GC_MALLOC_ALIGN: usize = 2 * core::mem::size_of::<usize>();
+
+static MIN_ALIGN_THRESHOLD: AtomicUsize = AtomicUsize::new(MIN_ALIGN);
+
+/// Set the largest alignment served by the plain `GC_malloc` fast path;
+/// requests with a larger alignment go through `GC_posix_memalign`. The
+/// default is [`MIN_ALIGN`].
+///
+/// Lowering it (down to 1) sends more requests through the aligned path.
+/// Raising it trusts `GC_malloc` for more alignments, which is only correct
+/// up to what `GC_malloc` actually guarantees, [`GC_MALLOC_ALIGN`]; larger
+/// values are rejected.
+///
+/// # Panics
+///
+/// If `align` is not a power of two or exceeds [`GC_MALLOC_ALIGN`].
+pub fn set_min_align_threshold(align: usize) {
+    assert!(align.is_power_of_two(), "alignment threshold must be a power of two");
+    assert!(align <= GC_MALLOC_ALIGN, "GC_malloc only guarantees {GC_MALLOC_ALIGN}-byte alignment");
+    MIN_ALIGN_THRESHOLD.store(align, Ordering::Relaxed);
+}
+
+pub fn min_align_threshold() -> usize {
+    MIN_ALIGN_THRESHOLD.load(Ordering::Relaxed)
+}
+
 #[derive(Debug)]
 pub struct GcAllocator;
 
@@ -122,8 +511,49 @@ unsafe impl GlobalAlloc for GcAllocator {
 
 #[inline]
 unsafe fn gc_malloc(layout: Layout) -> *mut u8 {
-    if layout.align() <= MIN_ALIGN && layout.align() <= layout.size() {
-        unsafe { crate::GC_malloc(layout.size()) as *mut u8 }
+    unsafe { gc_malloc_with(layout, gc_malloc_untracked) }
+}
+
+#[inline]
+unsafe fn gc_malloc_atomic_layout(layout: Layout) -> *mut u8 {
+    unsafe { gc_malloc_with(layout, gc_malloc_atomic_untracked) }
+}
+
+// Everything an allocation entry point does around the collector call
+// `untracked` itself.
+#[inline(always)]
+unsafe fn gc_malloc_with(layout: Layout, untracked: unsafe fn(Layout) -> *mut u8) -> *mut u8 {
+    #[cfg(feature = "alloc-histogram")]
+    histogram::record(layout.size());
+    #[cfg(feature = "test-fault-injection")]
+    if fault::should_fail(layout) {
+        return out_of_memory(layout);
+    }
+    inflight::admit(layout.size());
+    let mut ptr = unsafe { untracked(layout) };
+    if ptr.is_null() && RETRY_ON_OOM.load(Ordering::Relaxed) {
+        events::gcollect(events::CollectionCause::AllocationRetry);
+        ptr = unsafe { untracked(layout) };
+    }
+    if ptr.is_null() {
+        return out_of_memory(layout);
+    }
+    #[cfg(feature = "backtrace")]
+    backtrace::record(ptr);
+    events::run_post_collection_checks();
+    #[cfg(feature = "log")]
+    growth::log_pending();
+    ptr
+}
+
+#[inline]
+unsafe fn gc_malloc_untracked(layout: Layout) -> *mut u8 {
+    if layout.align() <= min_align_threshold() && layout.align() <= layout.size() {
+        #[cfg(feature = "poison-on-reclaim")]
+        let ptr = poison::malloc(layout.size());
+        #[cfg(not(feature = "poison-on-reclaim"))]
+        let ptr = unsafe { crate::GC_malloc(layout.size()) as *mut u8 };
+        ptr
     } else {
         let mut out = ptr::null_mut();
         // posix_memalign requires that the alignment be a multiple of `sizeof(void*)`.
@@ -136,10 +566,44 @@ unsafe fn gc_malloc(layout: Layout) -> *mut u8 {
     }
 }
 
+// `GC_malloc_atomic` has no aligned variant: over-aligned requests fall back
+// to scanned memory.
+#[inline]
+unsafe fn gc_malloc_atomic_untracked(layout: Layout) -> *mut u8 {
+    if layout.align() <= min_align_threshold() && layout.align() <= layout.size() {
+        #[cfg(feature = "poison-on-reclaim")]
+        let ptr = poison::malloc_atomic(layout.size());
+        #[cfg(not(feature = "poison-on-reclaim"))]
+        let ptr = unsafe { gc_malloc_atomic_sized(layout) };
+        ptr
+    } else {
+        unsafe { gc_malloc_untracked(layout) }
+    }
+}
+
 #[inline]
 unsafe fn gc_realloc(ptr: *mut u8, old_layout: Layout, new_size: usize) -> *mut u8 {
-    if old_layout.align() <= MIN_ALIGN && old_layout.align() <= new_size {
-        unsafe { crate::GC_realloc(ptr, new_size) as *mut u8 }
+    if old_layout.align() <= min_align_threshold() && old_layout.align() <= new_size {
+        #[cfg(feature = "test-fault-injection")]
+        {
+            let new_layout =
+                unsafe { Layout::from_size_align_unchecked(new_size, old_layout.align()) };
+            if fault::should_fail(new_layout) {
+                return out_of_memory(new_layout);
+            }
+        }
+        let new_ptr = unsafe { crate::GC_realloc(ptr, new_size) as *mut u8 };
+        if new_ptr.is_null() {
+            // GC_realloc leaves the old object untouched when it fails.
+            return out_of_memory(unsafe {
+                Layout::from_size_align_unchecked(new_size, old_layout.align())
+            });
+        }
+        #[cfg(feature = "backtrace")]
+        if new_ptr != ptr {
+            backtrace::record(new_ptr);
+        }
+        new_ptr
     } else {
         unsafe {
             let new_layout = Layout::from_size_align_unchecked(new_size, old_layout.align());
@@ -176,4 +640,1368 @@ unsafe impl Allocator for GcAllocator {
     }
 
     unsafe fn deallocate(&self, _: NonNull<u8>, _: Layout) {}
+
+    /// Shrink in place: the object keeps its size and the tail is simply no
+    /// longer handed out. Freeing it would take a copy into a smaller
+    /// object, and `deallocate` does not free anyway.
+    #[inline]
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(new_layout.size() <= old_layout.size());
+        if new_layout.align() <= old_layout.align() {
+            return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+        }
+        let new = self.allocate(new_layout)?;
+        unsafe { ptr::copy_nonoverlapping(ptr.as_ptr(), new.cast().as_ptr(), new_layout.size()) };
+        Ok(new)
+    }
+}
+
+/// An [`Allocator`] handing out pointer-free GC memory, which the collector
+/// never scans: for buffers of plain data (bytes, numbers) such as
+/// `Vec<u8, AtomicGcAllocator>`, which would otherwise be scanned for
+/// pointers on every collection. Memory is not cleared. Nothing allocated
+/// through it may hold the only pointer keeping a GC object alive.
+///
+/// Like [`GcAllocator`], deallocation is left to the collector, unless the
+/// `explicit-free` feature is enabled: `deallocate` then returns the memory
+/// with `GC_free` at once, which reclaims large buffers promptly and counts
+/// towards [`ProfileStats::expl_freed_bytes_since_gc`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AtomicGcAllocator;
+
+unsafe impl Allocator for AtomicGcAllocator {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        match layout.size() {
+            0 => Ok(NonNull::slice_from_raw_parts(layout.dangling(), 0)),
+            size => unsafe {
+                let ptr = NonNull::new(gc_malloc_atomic_layout(layout)).ok_or(AllocError)?;
+                Ok(NonNull::slice_from_raw_parts(ptr, size))
+            },
+        }
+    }
+
+    #[cfg(not(feature = "explicit-free"))]
+    unsafe fn deallocate(&self, _: NonNull<u8>, _: Layout) {}
+
+    #[cfg(feature = "explicit-free")]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            unsafe { gc_free(ptr.as_ptr(), layout) }
+        }
+    }
+}
+
+impl AtomicGcAllocator {
+    /// Allocate a pointer-free buffer of `len` uninitialised bytes, e.g. a
+    /// scratch buffer reused for zero-copy parsing.
+    ///
+    /// `GC_malloc_atomic` does not clear what it returns, so unlike
+    /// [`gc_uninit_array`] nothing is zeroed here and large buffers cost no
+    /// more than small ones. That is safe only because the collector never
+    /// scans the buffer: stale bytes in it cannot keep anything alive. Use
+    /// `allocate_zeroed` when the contents must start out cleared.
+    pub fn allocate_uninit_atomic(
+        &self,
+        len: usize,
+    ) -> Result<NonNull<[core::mem::MaybeUninit<u8>]>, AllocError> {
+        let ptr = self.allocate(Layout::array::<u8>(len).map_err(|_| AllocError)?)?;
+        Ok(NonNull::slice_from_raw_parts(ptr.cast(), len))
+    }
+}
+
+// Stack zeroed below the caller by every `clear_stack`.
+const CLEAR_STACK_BYTES: usize = 16 * 1024;
+
+/// Scrub the inactive part of the calling thread's stack.
+///
+/// Stale pointers left below the current stack pointer by a returned deep
+/// call tree are still seen by the conservative scanner and can keep dead
+/// objects alive. This zeroes the 16 KiB below the caller, then lets
+/// `GC_clear_stack` clear whatever more it sees fit (in multithreaded
+/// builds, only a larger region on an occasional call). Frames deeper than
+/// that are not reached. The cost is a `memset` over at least 16 KiB of
+/// stack per call, so avoid calling it on hot paths.
+#[inline(never)]
+pub fn clear_stack() {
+    #[inline(never)]
+    fn scrub() {
+        let mut region = core::mem::MaybeUninit::<[u8; CLEAR_STACK_BYTES]>::uninit();
+        unsafe { ptr::write_bytes(region.as_mut_ptr(), 0, 1) };
+        core::hint::black_box(&mut region);
+    }
+
+    scrub();
+    unsafe {
+        GC_clear_stack(ptr::null_mut());
+    }
+}
+
+/// Run `f`, then scrub the stack region its frames occupied, as far as
+/// [`clear_stack`] reaches.
+///
+/// Useful around deeply recursive computations whose intermediate frames
+/// held pointers to short-lived GC objects. Has the same cost as
+/// [`clear_stack`], paid once after `f` returns.
+#[inline(never)]
+pub fn with_stack_cleared<R>(f: impl FnOnce() -> R) -> R {
+    // `clear_stack` cannot zero the few words its own frames occupy right
+    // below the caller. Run `f` below a zeroed pad covering them.
+    #[inline(never)]
+    fn below_pad<R>(f: impl FnOnce() -> R) -> R {
+        let pad = [0usize; 16];
+        core::hint::black_box(&pad);
+        f()
+    }
+
+    let ret = below_pad(f);
+    clear_stack();
+    ret
+}
+
+/// Returns whether threads created with a plain `pthread_create` (e.g. by
+/// `std::thread::spawn` or a third-party crate) are registered with the
+/// collector automatically.
+///
+/// This is the case when built with the `interpose-threads` feature, which
+/// makes BDWGC export its own `pthread_create`, `pthread_join`,
+/// `pthread_detach` and `pthread_exit`. Known limitations:
+///
+/// * Fully static binaries (e.g. musl) have no `dlsym(RTLD_NEXT, ...)`, so
+///   the real functions cannot be found and thread creation aborts.
+/// * Shared libraries that bind `pthread_create` to their own copy (static
+///   libc, `-Bsymbolic`, or versioned direct binding) bypass the wrapper, as
+///   do libraries `dlopen`ed with `RTLD_DEEPBIND`.
+/// * With `link-shared`, interposition depends on how the system libgc was
+///   built.
+///
+/// The check spawns and joins a short-lived thread.
+pub fn foreign_threads_intercepted() -> bool {
+    extern "C" fn probe(_: *mut libc::c_void) -> *mut libc::c_void {
+        unsafe { GC_thread_is_registered() as usize as *mut libc::c_void }
+    }
+
+    unsafe {
+        let mut native = core::mem::zeroed();
+        if libc::pthread_create(&mut native, ptr::null(), probe, ptr::null_mut()) != 0 {
+            return false;
+        }
+        let mut registered = ptr::null_mut();
+        libc::pthread_join(native, &mut registered);
+        !registered.is_null()
+    }
+}
+
+/// Perform a full, stop-the-world collection.
+#[inline]
+pub fn collect() {
+    events::gcollect(events::CollectionCause::Explicit);
+    events::run_pending_gc_hooks();
+}
+
+/// Perform a full collection, then return as much of the free heap to the
+/// OS as possible.
+pub fn collect_and_unmap() {
+    events::collect_with(events::CollectionCause::Explicit, || unsafe {
+        GC_gcollect_and_unmap()
+    });
+    events::run_pending_gc_hooks();
+}
+
+/// A guard that runs a full collection when dropped, to reclaim the garbage
+/// of a phase as soon as it ends:
+///
+/// ```ignore
+/// {
+///     let _collect = bmalloc::CollectOnDrop::new();
+///     build_and_discard_lots_of_objects();
+/// } // collected here
+/// ```
+#[derive(Debug)]
+#[must_use = "the collection runs when the guard is dropped"]
+pub struct CollectOnDrop {
+    unmap: bool,
+}
+
+impl CollectOnDrop {
+    pub fn new() -> Self {
+        CollectOnDrop { unmap: false }
+    }
+
+    /// Also return the free heap to the OS afterwards (see
+    /// [`collect_and_unmap`]).
+    pub fn with_unmap() -> Self {
+        CollectOnDrop { unmap: true }
+    }
+}
+
+impl Default for CollectOnDrop {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for CollectOnDrop {
+    fn drop(&mut self) {
+        if self.unmap {
+            collect_and_unmap();
+        } else {
+            collect();
+        }
+    }
+}
+
+/// Number of collections completed since start-up. The value may wrap.
+pub fn gc_count() -> u64 {
+    unsafe { GC_get_gc_no() }
+}
+
+/// Enable or disable collections triggered by allocation. Explicit calls to
+/// [`collect`] still run while automatic collection is disabled.
+pub fn set_automatic_collection(enabled: bool) {
+    unsafe { GC_set_disable_automatic_collection(!enabled as i32) }
+}
+
+/// Returns whether allocation may trigger a collection.
+pub fn automatic_collection() -> bool {
+    unsafe { GC_get_disable_automatic_collection() == 0 }
+}
+
+// With `deterministic-test`, automatic collection is off from process start so
+// that object liveness only changes at explicit `collect()` calls. The setter
+// only takes the (statically initialised) allocator lock, so it is fine to call
+// before the collector is initialised.
+//
+// The constructor is only emitted for ELF (Linux, Android, the BSDs) and
+// Mach-O; elsewhere the feature still provides `with_manual_gc`, but
+// automatic collection stays on until the program turns it off.
+#[cfg(all(
+    feature = "deterministic-test",
+    any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "macos",
+        target_os = "ios",
+    )
+))]
+#[used]
+#[cfg_attr(any(target_os = "macos", target_os = "ios"), link_section = "__DATA,__mod_init_func")]
+#[cfg_attr(not(any(target_os = "macos", target_os = "ios")), link_section = ".init_array")]
+static DISABLE_AUTOMATIC_COLLECTION: extern "C" fn() = {
+    extern "C" fn disable() {
+        set_automatic_collection(false);
+    }
+    disable
+};
+
+/// Run `f` with automatic collection disabled, restoring the previous setting
+/// afterwards.
+///
+/// In single-threaded code this makes collections happen only at explicit
+/// [`collect`] calls, so which objects are reclaimed is a function of where
+/// those calls are placed rather than of the collector's heuristics. Other
+/// threads calling [`set_automatic_collection`] concurrently will race with
+/// the restore.
+#[cfg(feature = "deterministic-test")]
+pub fn with_manual_gc<R>(f: impl FnOnce() -> R) -> R {
+    struct Restore(bool);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            set_automatic_collection(self.0);
+        }
+    }
+
+    let _restore = Restore(automatic_collection());
+    set_automatic_collection(false);
+    f()
+}
+
+/// Atomically read the collector's heap usage counters.
+pub fn heap_usage() -> HeapUsage {
+    let mut usage = HeapUsage::default();
+    unsafe {
+        GC_get_heap_usage_safe(
+            &mut usage.heap_size,
+            &mut usage.free_bytes,
+            &mut usage.unmapped_bytes,
+            &mut usage.bytes_since_gc,
+            &mut usage.total_bytes,
+        );
+    }
+    usage
+}
+
+/// Keeps every other registered thread suspended until dropped.
+///
+/// The current thread holds the collector's allocator lock for the lifetime
+/// of the guard. While it is alive, the current thread must not allocate
+/// from the GC heap, trigger a collection, or call anything else that takes
+/// the allocator lock (including [`heap_usage`]); doing so deadlocks. Other
+/// threads may be suspended while holding arbitrary locks, so avoid
+/// anything that might contend with them (e.g. `std` I/O).
+pub struct WorldStoppedGuard {
+    // The allocator lock must be released by the thread that took it.
+    _not_send: core::marker::PhantomData<*mut ()>,
+}
+
+/// Suspend all other threads registered with the collector, e.g. to take a
+/// consistent snapshot of the heap. See [`WorldStoppedGuard`] for what may
+/// be done while the world is stopped.
+pub fn stop_the_world() -> WorldStoppedGuard {
+    unsafe { GC_stop_world_external() };
+    WorldStoppedGuard { _not_send: core::marker::PhantomData }
+}
+
+impl WorldStoppedGuard {
+    /// Read the heap usage counters. Consistent without further locking
+    /// because no other thread can run the allocator.
+    pub fn heap_usage(&self) -> HeapUsage {
+        unsafe {
+            HeapUsage {
+                heap_size: GC_get_heap_size(),
+                free_bytes: GC_get_free_bytes(),
+                unmapped_bytes: GC_get_unmapped_bytes(),
+                bytes_since_gc: GC_get_bytes_since_gc(),
+                total_bytes: GC_get_total_bytes(),
+            }
+        }
+    }
+}
+
+impl Drop for WorldStoppedGuard {
+    fn drop(&mut self) {
+        unsafe { GC_start_world_external() }
+    }
+}
+
+/// Returns whether `ptr` points somewhere into the GC heap.
+#[inline]
+pub fn is_gc_heap_ptr<T>(ptr: *const T) -> bool {
+    unsafe { GC_is_heap_ptr(ptr as *const u8) != 0 }
+}
+
+/// Returns the base address of the GC object containing `ptr`, or `None` if
+/// `ptr` does not point into an allocated GC object.
+#[inline]
+pub fn base_of<T>(ptr: *const T) -> Option<NonNull<u8>> {
+    NonNull::new(unsafe { GC_base(ptr as *mut u8) })
+}
+
+/// Why a [`ProfileStats`] read back from the collector cannot be trusted.
+///
+/// Either way the likely cause is that [`ProfileStats`] no longer matches
+/// `struct GC_prof_stats_s` of the linked library, e.g. a different BDWGC
+/// version picked up through `link-shared`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileStatsError {
+    /// The library filled in fewer bytes than `ProfileStats` has.
+    Truncated { filled: usize },
+    /// The values break an invariant that always holds (named here).
+    Inconsistent(&'static str),
+}
+
+impl fmt::Display for ProfileStatsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ProfileStatsError::Truncated { filled } => write!(
+                f,
+                "GC_get_prof_stats filled {filled} of {} bytes; \
+                 likely a GC library version mismatch",
+                core::mem::size_of::<ProfileStats>()
+            ),
+            ProfileStatsError::Inconsistent(what) => write!(
+                f,
+                "GC profile statistics violate `{what}`; \
+                 likely a struct layout or GC library version mismatch"
+            ),
+        }
+    }
+}
+
+impl ProfileStats {
+    /// Check the invariants between fields that any correct snapshot
+    /// satisfies.
+    pub fn validate(&self) -> Result<(), ProfileStatsError> {
+        if self.free_bytes_full > self.heapsize_full {
+            return Err(ProfileStatsError::Inconsistent("free_bytes_full <= heapsize_full"));
+        }
+        if self.unmapped_bytes > self.heapsize_full {
+            return Err(ProfileStatsError::Inconsistent("unmapped_bytes <= heapsize_full"));
+        }
+        if self.unmapped_bytes > self.free_bytes_full {
+            return Err(ProfileStatsError::Inconsistent("unmapped_bytes <= free_bytes_full"));
+        }
+        Ok(())
+    }
+}
+
+/// Atomically read the collector's statistics, checking them with
+/// [`ProfileStats::validate`] so that a layout mismatch with the linked
+/// library is reported instead of producing nonsense.
+pub fn get_prof_stats() -> Result<ProfileStats, ProfileStatsError> {
+    let mut stats = ProfileStats::default();
+    let size = core::mem::size_of::<ProfileStats>();
+    let filled = unsafe { GC_get_prof_stats(&mut stats, size) };
+    if filled < size {
+        return Err(ProfileStatsError::Truncated { filled });
+    }
+    stats.validate()?;
+    Ok(stats)
+}
+
+/// Average number of bytes allocated per collection over the lifetime of the
+/// process: higher means collections are rarer relative to allocation.
+/// Returns infinity if no collection has happened yet.
+///
+/// Both counters come from a single [`get_prof_stats`] snapshot, so they are
+/// consistent with each other.
+pub fn collection_efficiency() -> Result<f64, ProfileStatsError> {
+    let stats = get_prof_stats()?;
+    if stats.gc_no == 0 {
+        return Ok(f64::INFINITY);
+    }
+    let allocated = stats.allocd_bytes_before_gc.wrapping_add(stats.bytes_allocd_since_gc);
+    Ok(allocated as f64 / stats.gc_no as f64)
+}
+
+/// Tell the collector about the calling thread's alternate signal stack, if
+/// one is installed with `sigaltstack`, so that a thread suspended while
+/// running a signal handler has both its normal stack and the alternate stack
+/// scanned. Call it after installing (or changing) the alternate stack on a
+/// thread registered with the collector.
+///
+/// Returns `false` if the thread has no alternate stack enabled or its
+/// normal stack bounds could not be determined.
+#[cfg(target_os = "linux")]
+pub fn register_altstack() -> bool {
+    unsafe {
+        let mut alt: libc::stack_t = core::mem::zeroed();
+        if libc::sigaltstack(ptr::null(), &mut alt) != 0 || alt.ss_flags & libc::SS_DISABLE != 0 {
+            return false;
+        }
+
+        let mut attr: libc::pthread_attr_t = core::mem::zeroed();
+        if libc::pthread_getattr_np(libc::pthread_self(), &mut attr) != 0 {
+            return false;
+        }
+        let mut stack = ptr::null_mut();
+        let mut stack_size = 0;
+        let ret = libc::pthread_attr_getstack(&attr, &mut stack, &mut stack_size);
+        libc::pthread_attr_destroy(&mut attr);
+        if ret != 0 {
+            return false;
+        }
+
+        GC_register_altstack(stack as *mut u8, stack_size, alt.ss_sp as *mut u8, alt.ss_size);
+        true
+    }
+}
+
+/// Move the `T` at `ptr` into a fresh GC allocation and return its new
+/// address, or `None` (leaving the object where it is) if allocation fails
+/// or the object has a finalizer registered.
+///
+/// BDWGC never moves objects, but fragmentation can be reduced by copying
+/// long-lived objects into memory allocated together, e.g. right after a
+/// collection. The value is moved bitwise; the old allocation is left for
+/// the collector to reclaim once unreachable. A finalizer would stay with
+/// the old allocation and drop the moved value a second time, so objects
+/// made by e.g. [`gc_new_cyclic`], [`GcInterner`], [`GcArena`] or
+/// [`SplitAlloc`] are refused.
+///
+/// # Safety
+///
+/// `ptr` must point to a valid `T` in memory allocated by the collector.
+/// Every other pointer to the old object is stale afterwards: reads through
+/// it see a moved-from value and writes are lost.
+pub unsafe fn defragment<T>(ptr: NonNull<T>) -> Option<NonNull<T>> {
+    let layout = Layout::new::<T>();
+    if layout.size() == 0 {
+        return Some(ptr);
+    }
+    unsafe {
+        // BDWGC cannot look a finalizer up without removing it, so put back
+        // any that was found. The crate registers them all in no particular
+        // order.
+        let base = GC_base(ptr.as_ptr() as *mut u8);
+        let mut old: Option<unsafe extern "C" fn(*mut u8, *mut u8)> = None;
+        let mut old_data = ptr::null_mut();
+        GC_register_finalizer_no_order(
+            base,
+            None,
+            ptr::null_mut(),
+            &mut old as *mut _ as *mut extern "C" fn(*mut u8, *mut u8),
+            &mut old_data,
+        );
+        if old.is_some() {
+            GC_register_finalizer_no_order(base, old, old_data, ptr::null_mut(), ptr::null_mut());
+            return None;
+        }
+        let new = NonNull::new(gc_malloc(layout) as *mut T)?;
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new.as_ptr(), 1);
+        Some(new)
+    }
+}
+
+// BDWGC has no getter for the heap limit, so remember the last one we set.
+static MAX_HEAP_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// Limit the heap to `bytes` (0 means unlimited). Allocations that would grow
+/// the heap past the limit fail once collecting cannot free enough space.
+pub fn set_max_heap_size(bytes: usize) {
+    MAX_HEAP_SIZE.store(bytes, Ordering::Relaxed);
+    unsafe { GC_set_max_heap_size(bytes) }
+}
+
+/// The heap limit last set with [`set_max_heap_size`], if any.
+pub fn max_heap_size() -> Option<usize> {
+    match MAX_HEAP_SIZE.load(Ordering::Relaxed) {
+        0 => None,
+        n => Some(n),
+    }
+}
+
+static ELASTIC_HARD: AtomicUsize = AtomicUsize::new(0);
+static ELASTIC_GROWTH: AtomicU32 = AtomicU32::new(0);
+static ELASTIC_INSTALLED: AtomicBool = AtomicBool::new(false);
+static ELASTIC_PREVIOUS_OOM: AtomicUsize = AtomicUsize::new(0);
+
+/// Limit the heap to `soft` bytes, but let the limit stretch under
+/// pressure: when an allocation fails at the limit, it is raised by a
+/// factor of `growth` (and at least by the size of the request), up to
+/// `hard`, and the allocation is retried (see [`set_retry_on_oom`], which
+/// must stay enabled). Once the limit has reached `hard`, allocations fail
+/// as they would under [`set_max_heap_size`].
+///
+/// The raised limit stays in place; call this again to go back to `soft`.
+///
+/// # Panics
+///
+/// If `soft` is 0 or above `hard`, or `growth` is not above 1.
+pub fn set_elastic_heap_limit(soft: usize, hard: usize, growth: f32) {
+    assert!(soft > 0 && soft <= hard, "elastic heap limit needs 0 < soft <= hard");
+    assert!(growth > 1.0, "elastic heap limit growth factor must be above 1");
+    ELASTIC_HARD.store(hard, Ordering::Relaxed);
+    ELASTIC_GROWTH.store(growth.to_bits(), Ordering::Relaxed);
+    set_max_heap_size(soft);
+    if !ELASTIC_INSTALLED.swap(true, Ordering::AcqRel) {
+        unsafe {
+            ELASTIC_PREVIOUS_OOM.store(GC_get_oom_fn() as usize, Ordering::Release);
+            GC_set_oom_fn(relax_heap_limit);
+        }
+    }
+}
+
+// Called by the collector, without the allocator lock, when it cannot
+// satisfy a request of `bytes`.
+unsafe extern "C" fn relax_heap_limit(bytes: usize) -> *mut u8 {
+    let hard = ELASTIC_HARD.load(Ordering::Relaxed);
+    let current = MAX_HEAP_SIZE.load(Ordering::Relaxed);
+    if current != 0 && current < hard {
+        let growth = f32::from_bits(ELASTIC_GROWTH.load(Ordering::Relaxed));
+        let grown = (current as f64 * growth as f64) as usize;
+        set_max_heap_size(grown.max(current.saturating_add(bytes)).min(hard));
+        // Null makes the caller fail this attempt; the retry sees the new limit.
+        return ptr::null_mut();
+    }
+    let prev: unsafe extern "C" fn(usize) -> *mut u8 =
+        unsafe { core::mem::transmute(ELASTIC_PREVIOUS_OOM.load(Ordering::Acquire)) };
+    unsafe { prev(bytes) }
+}
+
+/// Unbuffered writer to the process's standard error.
+pub(crate) struct Stderr;
+
+impl fmt::Write for Stderr {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut bytes = s.as_bytes();
+        while !bytes.is_empty() {
+            let n = unsafe { libc::write(2, bytes.as_ptr() as *const libc::c_void, bytes.len()) };
+            if n <= 0 {
+                return Err(fmt::Error);
+            }
+            bytes = &bytes[n as usize..];
+        }
+        Ok(())
+    }
+}
+
+/// An allocation error hook that reports the state of the GC heap alongside
+/// the failed request, so that out-of-memory aborts can be diagnosed.
+///
+/// Install it from code that has `std` with
+/// `std::alloc::set_alloc_error_hook(bmalloc::gc_alloc_error_hook)`; the
+/// runtime aborts after the hook returns.
+pub fn gc_alloc_error_hook(layout: Layout) {
+    use fmt::Write;
+
+    let usage = heap_usage();
+    let gc_no = unsafe { GC_get_gc_no() };
+    let mut out = Stderr;
+    let _ = write!(
+        out,
+        "memory allocation of {} bytes (align {}) failed\n\
+         GC heap: size {}, free {}, unmapped {}, allocated since last GC {}; collections {}",
+        layout.size(),
+        layout.align(),
+        usage.heap_size,
+        usage.free_bytes,
+        usage.unmapped_bytes,
+        usage.bytes_since_gc,
+        gc_no,
+    );
+    let _ = match max_heap_size() {
+        Some(max) => writeln!(out, "; max heap {max}"),
+        None => writeln!(out, "; no max heap"),
+    };
+}
+
+/// Start the parallel marker threads now, if the collector was built with
+/// `parallel-mark` and they are not running yet.
+///
+/// BDWGC otherwise defers starting them until the first thread is created
+/// through it, and the markers inherit the signal mask of whichever thread
+/// starts them. Call this after the collector is initialised (and after
+/// [`allow_register_threads`] where that is needed), from a thread whose
+/// signal mask the markers should have.
+pub fn start_mark_threads() {
+    unsafe { GC_start_mark_threads() }
+}
+
+/// Number of parallel marker threads running, excluding the thread that
+/// initiates a collection. Zero when marking is not parallel (yet).
+pub fn marker_thread_count() -> usize {
+    unsafe { GC_get_parallel() as usize }
+}
+
+/// Initialise the collector and allow other threads to register with it.
+///
+/// Calling this from the main thread before any other thread touches the GC
+/// heap is recommended; it is safe to call more than once.
+pub fn init() {
+    unsafe { GC_init() };
+    allow_register_threads();
+    finalize::install();
+}
+
+/// Allow threads other than the initialising one to register themselves with
+/// the collector (`GC_register_my_thread`). Some configurations reject such
+/// registrations until this has been called, leaving threads spawned outside
+/// the collector's `pthread_create` wrapper silently unregistered.
+///
+/// Must be called from the main thread (or an already registered one) after
+/// the collector is initialised; [`init`] does this. It is implied when the
+/// collector is built with `GC_ALWAYS_MULTITHREADED`, as by default.
+pub fn allow_register_threads() {
+    unsafe { GC_allow_register_threads() }
+}
+
+/// Make the collector allocate its heap with execute permission. Only has an
+/// effect before the collector is initialised, and not on every platform;
+/// check [`pages_executable`] afterwards.
+pub fn set_pages_executable(enabled: bool) {
+    unsafe { GC_set_pages_executable(enabled as i32) }
+}
+
+/// Returns whether GC heap pages are mapped executable.
+pub fn pages_executable() -> bool {
+    unsafe { GC_get_pages_executable() != 0 }
+}
+
+/// Returns whether the collector sweeps lazily.
+///
+/// By default BDWGC sweeps a block only when allocation next needs free
+/// objects of its size, so the first allocations after a collection pay for
+/// sweeping. Building with the `eager-sweep` feature sweeps everything at the
+/// end of each collection instead, making collections longer but allocation
+/// latency afterwards more uniform. BDWGC has no runtime switch for this; with
+/// `link-shared` it depends on how the system libgc was built, which this
+/// function cannot detect.
+pub const fn lazy_sweep() -> bool {
+    cfg!(not(feature = "eager-sweep"))
+}
+
+/// Run `f` with the GC object at `ptr` rooted, so it cannot be collected
+/// while `f` runs even if no scanned memory refers to it, e.g. while it is
+/// only referenced from C code running on an unregistered thread.
+///
+/// The object is rooted by storing a pointer to it in a small uncollectable
+/// allocation for the duration of the call. This prevents collection, not
+/// mutation: nothing stops other code from writing to the object meanwhile.
+///
+/// # Panics
+///
+/// If the root cell cannot be allocated.
+pub fn with_pinned<T, R>(ptr: NonNull<T>, f: impl FnOnce(*const T) -> R) -> R {
+    struct Unpin(*mut *const u8);
+    impl Drop for Unpin {
+        fn drop(&mut self) {
+            unsafe { GC_free(self.0 as *mut u8) };
+        }
+    }
+
+    let cell = unsafe { GC_malloc_uncollectable(core::mem::size_of::<*const u8>()) };
+    assert!(!cell.is_null(), "out of memory pinning a GC object");
+    let cell = cell as *mut *const u8;
+    unsafe { cell.write(ptr.as_ptr() as *const u8) };
+    let _unpin = Unpin(cell);
+    f(ptr.as_ptr())
+}
+
+/// Value of [`time_limit`] meaning incremental collections are not bounded by
+/// a pause-time target.
+pub const TIME_UNLIMITED: u64 = 999_999;
+
+/// Set how much the heap may grow between collections: at least
+/// `N / divisor` bytes are allocated between collections, where `N`
+/// approximates the tracing work of a collection. Larger values use less
+/// space but more collection time. Must be positive.
+pub fn set_free_space_divisor(divisor: usize) {
+    assert!(divisor > 0, "free space divisor must be positive");
+    unsafe { GC_set_free_space_divisor(divisor) }
+}
+
+pub fn free_space_divisor() -> usize {
+    unsafe { GC_get_free_space_divisor() }
+}
+
+/// Set the number of partial collections between full ones. Only matters in
+/// incremental mode.
+pub fn set_full_freq(freq: i32) {
+    unsafe { GC_set_full_freq(freq) }
+}
+
+pub fn full_freq() -> i32 {
+    unsafe { GC_get_full_freq() }
+}
+
+/// Set the pause-time target, in milliseconds, for incremental collections.
+/// Not a hard bound. [`TIME_UNLIMITED`] keeps generational collection but
+/// disables the pause-time checks.
+pub fn set_time_limit(ms: u64) {
+    unsafe { GC_set_time_limit(ms as libc::c_ulong) }
+}
+
+pub fn time_limit() -> u64 {
+    unsafe { GC_get_time_limit() as u64 }
+}
+
+/// Switch to incremental/generational collection. This cannot be undone.
+pub fn enable_incremental() {
+    unsafe { GC_enable_incremental() }
+}
+
+pub fn is_incremental() -> bool {
+    unsafe { GC_is_incremental_mode() != 0 }
+}
+
+/// Allocate a scanned array of `len` uninitialised `T`s on the GC heap.
+///
+/// Memory that holds GC pointers is scanned conservatively, including slots
+/// that were never initialised. If those slots contained leftover bytes, the
+/// collector could read them as pointers and keep unrelated objects alive.
+/// The backing memory is therefore zeroed before it is returned: a null slot
+/// is never treated as a reference, so it is safe to collect while the
+/// array is only partly initialised.
+///
+/// The array is reclaimed once it is unreachable. Returns `None` if `len`
+/// elements of `T` overflow a `Layout` or the allocation fails.
+pub fn gc_uninit_array<T>(len: usize) -> Option<NonNull<[core::mem::MaybeUninit<T>]>> {
+    let layout = Layout::array::<T>(len).ok()?;
+    if layout.size() == 0 {
+        return Some(NonNull::slice_from_raw_parts(NonNull::dangling(), len));
+    }
+    let ptr = NonNull::new(unsafe { gc_malloc(layout) })?;
+    unsafe { ptr::write_bytes(ptr.as_ptr(), 0, layout.size()) };
+    Some(NonNull::slice_from_raw_parts(ptr.cast(), len))
+}
+
+/// What the allocator does when the collector cannot satisfy a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OomPolicy {
+    /// Return null from [`GlobalAlloc`] and `AllocError` from [`Allocator`],
+    /// leaving the decision to the caller. The default.
+    ReturnNull,
+    /// Report the failed request and the heap state as
+    /// [`gc_alloc_error_hook`] does, then abort the process.
+    Abort,
+}
+
+static OOM_POLICY: AtomicU8 = AtomicU8::new(OomPolicy::ReturnNull as u8);
+
+/// Choose how every allocation entry point of [`GcAllocator`] reacts to
+/// running out of memory.
+///
+/// Whatever the path taken (plain `GC_malloc`, the over-aligned
+/// `GC_posix_memalign`, or `GC_realloc`), a failed allocation is
+/// recognised the same way and handled according to this policy; a
+/// non-null pointer is only ever returned for a successful allocation.
+pub fn set_oom_policy(policy: OomPolicy) {
+    OOM_POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+pub fn oom_policy() -> OomPolicy {
+    match OOM_POLICY.load(Ordering::Relaxed) {
+        x if x == OomPolicy::Abort as u8 => OomPolicy::Abort,
+        _ => OomPolicy::ReturnNull,
+    }
+}
+
+static RETRY_ON_OOM: AtomicBool = AtomicBool::new(true);
+
+/// Whether a failed allocation is retried once after a full collection
+/// before it is reported as out of memory. On by default.
+///
+/// Under a [`set_max_heap_size`] cap a request can fail while the heap is
+/// full of garbage that the next collection would free; the retry turns
+/// such transient failures (e.g. from `try_reserve`) into successes. It
+/// applies to every allocation entry point, ahead of the [`OomPolicy`].
+pub fn set_retry_on_oom(enabled: bool) {
+    RETRY_ON_OOM.store(enabled, Ordering::Relaxed);
+}
+
+pub fn retry_on_oom() -> bool {
+    RETRY_ON_OOM.load(Ordering::Relaxed)
+}
+
+#[cold]
+#[inline(never)]
+fn out_of_memory(layout: Layout) -> *mut u8 {
+    if oom_policy() == OomPolicy::Abort {
+        gc_alloc_error_hook(layout);
+        unsafe { libc::abort() };
+    }
+    ptr::null_mut()
+}
+
+/// Allocate backing storage for a `Vec<T, GcAllocator>` of at least
+/// `capacity` elements and return it with the capacity actually available.
+///
+/// The collector rounds every request up to a size class, so the object it
+/// hands out is usually larger than asked for. The returned capacity counts
+/// every `T` that fits in the whole object, letting the vector use that
+/// slack before its first reallocation:
+///
+/// ```ignore
+/// let (ptr, cap) = bmalloc::gc_vec_raw_parts::<u32>(10).unwrap();
+/// let v = unsafe { Vec::from_raw_parts_in(ptr.as_ptr(), 0, cap, GcAllocator) };
+/// ```
+///
+/// Over-aligned element types are not rounded. Returns `None` if the
+/// allocation fails.
+pub fn gc_vec_raw_parts<T>(capacity: usize) -> Option<(NonNull<T>, usize)> {
+    let elem = core::mem::size_of::<T>();
+    if elem == 0 {
+        return Some((NonNull::dangling(), usize::MAX));
+    }
+    if capacity == 0 {
+        return Some((NonNull::dangling(), 0));
+    }
+    let layout = Layout::array::<T>(capacity).ok()?;
+    let ptr = NonNull::new(unsafe { gc_malloc(layout) })?;
+    if layout.align() > min_align_threshold() {
+        return Some((ptr.cast(), capacity));
+    }
+    // With interior pointers recognised the collector pads each request by
+    // a byte so that a one-past-the-end pointer does not hit the next object.
+    let padding = unsafe { GC_get_all_interior_pointers() } as usize;
+    let usable = unsafe { GC_size(ptr.as_ptr()) } - padding;
+    Some((ptr.cast(), cmp::max(capacity, usable / elem)))
+}
+
+/// Make the collector install `pthread_atfork` handlers so that it can keep
+/// being used in the child of a `fork`. Only has an effect before the
+/// collector is initialised.
+pub fn set_handle_fork(enabled: bool) {
+    unsafe { GC_set_handle_fork(enabled as i32) }
+}
+
+/// Bring the collector back to full operation in the child of a `fork`.
+///
+/// Only the forking thread survives in the child, and the parallel markers
+/// do not: this registers the calling thread if the fork handlers did not
+/// keep it registered, and restarts the markers under `parallel-mark`. Call
+/// it first thing in the child, after the collector's own fork handling
+/// (see [`set_handle_fork`]) has run. Calling it more than once is harmless.
+///
+/// Returns whether the calling thread is registered afterwards.
+pub fn after_fork_child() -> bool {
+    let registered = thread::register_current_thread() != thread::Registration::Failed;
+    start_mark_threads();
+    registered
+}
+
+static ATFORK_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Register `pthread_atfork` handlers that make any `fork` safe for the
+/// collector, however the host application calls it: the parent takes the
+/// collector's locks and waits for a collection in progress before the
+/// fork, and the child resets the collector's state afterwards, so that it
+/// can allocate and collect. Only the first call registers the handlers.
+/// Returns whether they are registered.
+///
+/// Unlike [`set_handle_fork`], this works after the collector has been
+/// initialised. Where the collector installed its own fork handlers, these
+/// ones do nothing, so installing both is harmless. As with any fork of a
+/// multithreaded process, only the forking thread exists in the child;
+/// call [`after_fork_child`] there to restart the parallel markers.
+pub fn install_atfork_handlers() -> bool {
+    extern "C" fn prepare() {
+        unsafe { GC_atfork_prepare() }
+    }
+    extern "C" fn parent() {
+        unsafe { GC_atfork_parent() }
+    }
+    extern "C" fn child() {
+        unsafe { GC_atfork_child() }
+    }
+
+    if ATFORK_INSTALLED.swap(true, Ordering::AcqRel) {
+        return true;
+    }
+    if unsafe { libc::pthread_atfork(Some(prepare), Some(parent), Some(child)) } != 0 {
+        ATFORK_INSTALLED.store(false, Ordering::Release);
+        return false;
+    }
+    true
+}
+
+/// Run a full collection in slices of roughly `budget_per_slice`, calling
+/// `yield_fn` between slices so that a cooperative scheduler can run other
+/// tasks, until the collection has finished.
+///
+/// Requires incremental mode (see [`enable_incremental`]) and BDWGC 8.2 or
+/// later (see [`GcCapabilities`]); otherwise the collection cannot be
+/// started in slices, so this falls back to a single [`collect`] and never
+/// calls `yield_fn`. Slices are made of
+/// `GC_collect_a_little` steps and the budget is checked between steps,
+/// so a slice may overrun it by one step.
+///
+/// The collector first tries to mark everything with the world stopped,
+/// for as long as its [`time_limit`] allows, so the limit is lowered to the
+/// budget (in whole milliseconds) while this runs and restored afterwards.
+pub fn collect_incrementally(budget_per_slice: Duration, mut yield_fn: impl FnMut()) {
+    if !is_incremental() || !capabilities().start_incremental_collection {
+        collect();
+        return;
+    }
+    // Restores the limit even if `yield_fn` panics.
+    struct Restore(u64);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            set_time_limit(self.0);
+        }
+    }
+
+    let restore = Restore(time_limit());
+    set_time_limit(budget_per_slice.as_millis().min(TIME_UNLIMITED as u128 - 1) as u64);
+    events::collect_with(events::CollectionCause::Explicit, || {
+        unsafe { GC_start_incremental_collection() };
+        loop {
+            let slice_end = now() + budget_per_slice;
+            loop {
+                if unsafe { GC_collect_a_little() } == 0 {
+                    return;
+                }
+                if now() >= slice_end {
+                    break;
+                }
+            }
+            yield_fn();
+        }
+    });
+    drop(restore);
+    events::run_pending_gc_hooks();
+}
+
+// Number of `GC_collect_a_little` steps the last collection driven by
+// `collect_incremental_with_progress` took, as the estimate for the next.
+static LAST_COLLECTION_STEPS: AtomicUsize = AtomicUsize::new(0);
+
+/// Run a full collection as a series of `GC_collect_a_little` steps,
+/// calling `progress` after each with the estimated fraction done, e.g. to
+/// drive a progress bar.
+///
+/// BDWGC does not report how far a collection has got, so the estimate is
+/// the number of steps taken against the number the previous such
+/// collection needed (a fixed guess the first time). The reported values
+/// never decrease, stay below 1.0 while the collection runs over the
+/// estimate, and end with exactly 1.0. Without incremental mode (see
+/// [`enable_incremental`]), or with BDWGC before 8.2, this is a single
+/// [`collect`] followed by 1.0.
+pub fn collect_incremental_with_progress(mut progress: impl FnMut(f32)) {
+    const FIRST_ESTIMATE: usize = 64;
+
+    if !is_incremental() || !capabilities().start_incremental_collection {
+        collect();
+        progress(1.0);
+        return;
+    }
+    let estimate = match LAST_COLLECTION_STEPS.load(Ordering::Relaxed) {
+        0 => FIRST_ESTIMATE,
+        n => n,
+    } as f32;
+    let mut steps = 0usize;
+    events::collect_with(events::CollectionCause::Explicit, || {
+        unsafe { GC_start_incremental_collection() };
+        while unsafe { GC_collect_a_little() } != 0 {
+            steps += 1;
+            let steps = steps as f32;
+            // Linear up to 95% at the estimate, then creeping towards 99%.
+            let fraction = if steps < estimate {
+                0.95 * steps / estimate
+            } else {
+                0.95 + 0.04 * (1.0 - estimate / steps)
+            };
+            progress(fraction);
+        }
+    });
+    LAST_COLLECTION_STEPS.store(steps.max(1), Ordering::Relaxed);
+    events::run_pending_gc_hooks();
+    progress(1.0);
+}
+
+/// `pthread_once`, whose init routine the libc bindings type differently on
+/// Apple platforms.
+pub(crate) unsafe fn pthread_once(once: *mut libc::pthread_once_t, init: extern "C" fn()) {
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    let init = Some(init as unsafe extern "C" fn());
+    unsafe { libc::pthread_once(once, init) };
+}
+
+pub(crate) fn now() -> Duration {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Cancel the finalizer registered for the object at `obj`, if any, e.g.
+/// because ownership of the object moved somewhere that must not see it
+/// finalized. Returns whether a finalizer was registered.
+///
+/// An object that has already been queued for finalization (see
+/// [`pending_finalizers`]) is not affected.
+///
+/// # Safety
+///
+/// `obj` must be the base address of an object allocated by the collector.
+pub unsafe fn unregister_finalizer(obj: *const u8) -> bool {
+    let mut old: Option<extern "C" fn(*mut u8, *mut u8)> = None;
+    let mut old_data = ptr::null_mut();
+    unsafe {
+        GC_register_finalizer(
+            obj as *mut u8,
+            None,
+            ptr::null_mut(),
+            &mut old as *mut _ as *mut extern "C" fn(*mut u8, *mut u8),
+            &mut old_data,
+        );
+    }
+    old.is_some()
+}
+
+/// Allocate a slice of at least `min_len` clones of `value`, extended to
+/// fill the whole object the collector hands out after size-class rounding
+/// (see [`gc_vec_raw_parts`]), for use as a `Box<[T], GcAllocator>`:
+///
+/// ```ignore
+/// let slice = bmalloc::gc_slice_filled(0u8, 100).unwrap();
+/// let boxed = unsafe { Box::from_raw_in(slice.as_ptr(), GcAllocator) };
+/// ```
+///
+/// Returns `None` if the allocation fails.
+pub fn gc_slice_filled<T: Clone>(value: T, min_len: usize) -> Option<NonNull<[T]>> {
+    let (ptr, capacity) = gc_vec_raw_parts::<T>(min_len)?;
+    let len = if core::mem::size_of::<T>() == 0 { min_len } else { capacity };
+    if len > 0 {
+        for i in 0..len - 1 {
+            unsafe { ptr.as_ptr().add(i).write(value.clone()) };
+        }
+        unsafe { ptr.as_ptr().add(len - 1).write(value) };
+    }
+    Some(NonNull::slice_from_raw_parts(ptr, len))
+}
+
+/// Store `value` into `field`, a pointer field inside a GC object, and tell
+/// the collector that the object changed.
+///
+/// In incremental mode only objects recorded as dirty are rescanned by a
+/// partial collection. With page protection the hardware records stores on
+/// its own, but in manual dirty-bit mode (`GC_set_manual_vdb_allowed`) it
+/// does not: a pointer stored into an old object without this barrier is
+/// invisible to partial collections, and the young object it points to can
+/// be reclaimed while still referenced. Outside manual mode this is a plain
+/// store.
+#[inline]
+pub fn gc_store<T>(field: &mut *mut T, value: *mut T) {
+    unsafe { GC_ptr_store_and_dirty(field as *mut *mut T as *mut u8, value as *const u8) }
+}
+
+/// Copy `bytes` into a new pointer-free GC object, which the collector never
+/// scans. Returns `None` if the allocation fails, as
+/// [`gc_malloc_atomic`] does.
+pub fn gc_bytes(bytes: &[u8]) -> Option<NonNull<[u8]>> {
+    let ptr = gc_malloc_atomic(bytes.len().max(1))?;
+    unsafe { ptr.as_ptr().copy_from_nonoverlapping(bytes.as_ptr(), bytes.len()) };
+    Some(NonNull::slice_from_raw_parts(ptr, bytes.len()))
+}
+
+/// Best-effort warm-up of the free lists for objects of `size` bytes, so
+/// that a latency-sensitive loop allocating them does not have to carve up
+/// fresh heap blocks.
+///
+/// `count` objects of each kind (scanned and pointer-free) are allocated and
+/// immediately freed explicitly, which leaves them on the collector's free
+/// lists for that size class, from which per-thread caches refill cheaply.
+/// A later collection, or another thread allocating the same size, may use
+/// them up before the loop runs.
+pub fn prewarm(size: usize, count: usize) {
+    let size = size.max(core::mem::size_of::<*mut u8>());
+    unsafe {
+        // A collection must not reclaim the pointer-free objects, which are
+        // only reachable through each other, before they are freed.
+        GC_disable();
+        for alloc in [GC_malloc as unsafe extern "C" fn(usize) -> *mut u8, GC_malloc_atomic] {
+            let mut list: *mut u8 = ptr::null_mut();
+            for _ in 0..count {
+                let obj = alloc(size);
+                if obj.is_null() {
+                    break;
+                }
+                (obj as *mut *mut u8).write(list);
+                list = obj;
+            }
+            while !list.is_null() {
+                let next = (list as *mut *mut u8).read();
+                GC_free(list);
+                list = next;
+            }
+        }
+        GC_enable();
+    }
+}
+
+/// Move `value` into a new GC object, returning an error instead of
+/// aborting if the heap is exhausted (e.g. under [`set_max_heap_size`]).
+///
+/// `value` is consumed only on success; on failure it is dropped before
+/// returning, so nothing is leaked or dropped twice. The object is not
+/// finalized: wrap it with `Box::from_raw_in(ptr.as_ptr(), GcAllocator)` to
+/// have `T` dropped. Under [`OomPolicy::Abort`] a failed allocation still
+/// aborts, as for every other entry point.
+pub fn gc_try_new<T>(value: T) -> Result<NonNull<T>, AllocError> {
+    let layout = Layout::new::<T>();
+    let ptr = GcAllocator.allocate(layout)?.cast::<T>();
+    unsafe { ptr.as_ptr().write(value) };
+    Ok(ptr)
+}
+
+/// Clone `items` into a new GC slice, returning an error instead of
+/// aborting if the heap is exhausted, as [`gc_try_new`] does. No clone is
+/// made unless the allocation succeeds.
+pub fn gc_try_new_slice<T: Clone>(items: &[T]) -> Result<NonNull<[T]>, AllocError> {
+    let layout = Layout::array::<T>(items.len()).map_err(|_| AllocError)?;
+    let ptr = GcAllocator.allocate(layout)?.cast::<T>();
+    for (i, item) in items.iter().enumerate() {
+        unsafe { ptr.as_ptr().add(i).write(item.clone()) };
+    }
+    Ok(NonNull::slice_from_raw_parts(ptr, items.len()))
+}
+
+static ATOMIC_IGNORE_OFF_PAGE_THRESHOLD: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Allocate pointer-free objects of more than `threshold` bytes from
+/// [`gc_malloc_atomic`] (and so [`gc_bytes`]) with
+/// `GC_malloc_atomic_ignore_off_page`. Off (`usize::MAX`) by default.
+///
+/// The collector then skips registering every page of a large buffer as a
+/// valid target for interior pointers, which makes allocating
+/// multi-gigabyte buffers cheaper and stops stray integers from retaining
+/// them. In exchange, only a pointer to (or near) the start of the buffer
+/// keeps it alive: callers must hold on to the returned base pointer for as
+/// long as any part of the buffer is used, not just to a pointer into it.
+pub fn set_atomic_ignore_off_page_threshold(threshold: usize) {
+    ATOMIC_IGNORE_OFF_PAGE_THRESHOLD.store(threshold, Ordering::Relaxed);
+}
+
+pub fn atomic_ignore_off_page_threshold() -> usize {
+    ATOMIC_IGNORE_OFF_PAGE_THRESHOLD.load(Ordering::Relaxed)
+}
+
+/// Allocate a pointer-free GC object of `size` bytes, which the collector
+/// never scans. Its contents are not cleared. Above
+/// [`atomic_ignore_off_page_threshold`] only a pointer near its start keeps
+/// it alive. Returns `None` if the allocation fails, after the retry of
+/// [`set_retry_on_oom`]; under [`OomPolicy::Abort`] it aborts instead, as
+/// every other entry point does.
+pub fn gc_malloc_atomic(size: usize) -> Option<NonNull<u8>> {
+    let layout = Layout::from_size_align(size, 1).ok()?;
+    NonNull::new(unsafe { gc_malloc_with(layout, gc_malloc_atomic_sized) })
+}
+
+// The collector call behind `gc_malloc_atomic`, which only looks at the
+// size.
+unsafe fn gc_malloc_atomic_sized(layout: Layout) -> *mut u8 {
+    if layout.size() > atomic_ignore_off_page_threshold() {
+        unsafe { GC_malloc_atomic_ignore_off_page(layout.size()) }
+    } else {
+        unsafe { GC_malloc_atomic(layout.size()) }
+    }
+}
+
+/// Grow the heap by `bytes` and fault in every page of the new space, so
+/// that allocations served from it later do not take first-touch page
+/// faults. Trades start-up time (and resident memory) for steady-state
+/// latency. Returns `false` if the heap could not be expanded.
+///
+/// The pages are touched through ordinary allocations rather than by
+/// walking the heap: the free space is taken in large pointer-free chunks,
+/// every page of each chunk is written, and the chunks are freed again, so
+/// the collector's own metadata is never written. Pages the collector later
+/// returns to the OS after long disuse will fault again when reused.
+pub fn pretouch_heap(bytes: usize) -> bool {
+    const CHUNK: usize = 1 << 20;
+
+    if unsafe { GC_expand_hp(bytes) } == 0 {
+        return false;
+    }
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    unsafe {
+        // The chunks are only reachable through each other, from unscanned
+        // memory, until they are freed.
+        GC_disable();
+        let mut list: *mut u8 = ptr::null_mut();
+        let mut touched = 0;
+        while touched < bytes {
+            let chunk = GC_malloc_atomic(CHUNK);
+            if chunk.is_null() {
+                break;
+            }
+            for offset in (0..CHUNK).step_by(page) {
+                ptr::write_volatile(chunk.add(offset), 0);
+            }
+            (chunk as *mut *mut u8).write(list);
+            list = chunk;
+            touched += CHUNK;
+        }
+        while !list.is_null() {
+            let next = (list as *mut *mut u8).read();
+            GC_free(list);
+            list = next;
+        }
+        GC_enable();
+    }
+    true
+}
+
+/// Turn a pointer handed back from foreign code into a reference, if it
+/// still designates the start of an allocated GC object big enough for a
+/// `T`. Returns `None` for null, misaligned and non-heap pointers, pointers
+/// into the middle of an object, and objects too small for `T`.
+///
+/// The check is as strong as the collector allows, which is weaker than
+/// "live": BDWGC does not record which slots of a small-object block are in
+/// use, so a pointer to a small object that has been collected still passes
+/// (and may by now refer to a new, unrelated object), as does one to an
+/// unreachable object not yet swept. Only large objects, which are given
+/// whole blocks, are reliably rejected once reclaimed. The result is
+/// meaningful only while the object is known to be kept alive elsewhere,
+/// e.g. by a root registered when the pointer was handed out.
+///
+/// # Safety
+///
+/// If the checks pass, the object must hold an initialised `T` that is not
+/// mutated for as long as the reference is used.
+pub unsafe fn deref_if_live<T>(ptr: *const T) -> Option<&'static T> {
+    require_gc_ptr(ptr).ok().map(|ptr| unsafe { &*ptr.as_ptr() })
+}
+
+/// Why [`require_gc_ptr`] rejected a pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForeignPointerError {
+    Null,
+    /// Not aligned for the pointee type.
+    Misaligned { addr: usize },
+    /// Outside the GC heap, e.g. memory from `malloc` or the global
+    /// allocator, or a free block of the heap.
+    NotGcHeap { addr: usize },
+    /// Inside a GC object, `offset` bytes past its start.
+    Interior { addr: usize, offset: usize },
+    /// At the start of a GC object of `size` bytes, which is too small for
+    /// the pointee type.
+    TooSmall { addr: usize, size: usize },
+}
+
+impl fmt::Display for ForeignPointerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ForeignPointerError::Null => write!(f, "null pointer"),
+            ForeignPointerError::Misaligned { addr } => {
+                write!(f, "pointer {addr:#x} is misaligned")
+            }
+            ForeignPointerError::NotGcHeap { addr } => {
+                write!(f, "pointer {addr:#x} is not to an object on the GC heap")
+            }
+            ForeignPointerError::Interior { addr, offset } => {
+                write!(f, "pointer {addr:#x} is {offset} bytes into a GC object")
+            }
+            ForeignPointerError::TooSmall { addr, size } => {
+                write!(f, "pointer {addr:#x} is to a GC object of only {size} bytes")
+            }
+        }
+    }
+}
+
+/// Check that a pointer received from foreign code is to the start of a GC
+/// object big enough for a `T`, and say why not otherwise. Use it to reject
+/// pointers a C library allocated with its own `malloc` before treating
+/// them as GC objects.
+///
+/// The checks are those of [`deref_if_live`], with its caveat: a pointer to
+/// a small object that has since been collected is not detected.
+pub fn require_gc_ptr<T>(ptr: *const T) -> Result<NonNull<T>, ForeignPointerError> {
+    let addr = ptr as usize;
+    if ptr.is_null() {
+        return Err(ForeignPointerError::Null);
+    }
+    if !ptr.is_aligned() {
+        return Err(ForeignPointerError::Misaligned { addr });
+    }
+    let base = base_of(ptr).ok_or(ForeignPointerError::NotGcHeap { addr })?;
+    if base.as_ptr() as usize != addr {
+        let offset = addr - base.as_ptr() as usize;
+        return Err(ForeignPointerError::Interior { addr, offset });
+    }
+    let size = unsafe { GC_size(base.as_ptr()) };
+    if size < core::mem::size_of::<T>() {
+        return Err(ForeignPointerError::TooSmall { addr, size });
+    }
+    Ok(base.cast())
+}
+
+/// Print the collector's internal state (heap sections, free lists, roots,
+/// ...) to its log, standard output by default, headed by `label` so that
+/// dumps from different subsystems or phases can be told apart.
+///
+/// `label` is cut at 127 bytes and at any NUL. The dump is taken with the
+/// allocator lock held, so no allocation can change the heap under it.
+pub fn dump_named(label: &str) {
+    unsafe extern "C" fn dump_locked(name: *mut u8) -> *mut u8 {
+        unsafe { GC_dump_named(name as *const libc::c_char) };
+        ptr::null_mut()
+    }
+
+    let mut name = [0u8; 128];
+    let label = label.as_bytes();
+    let len = label.iter().position(|&b| b == 0).unwrap_or(label.len()).min(name.len() - 1);
+    name[..len].copy_from_slice(&label[..len]);
+    unsafe { GC_call_with_alloc_lock(dump_locked, name.as_mut_ptr()) };
 }
@@ -0,0 +1,99 @@
+//! Object kinds whose pointers are found by a user-supplied mark procedure
+//! rather than by conservative scanning.
+
+use core::{
+    ptr,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+// From gc_mark.h.
+const GC_DS_TAG_BITS: usize = 2;
+const GC_DS_PROC: usize = 2;
+const GC_LOG_MAX_MARK_PROCS: usize = 6;
+
+// Index of `mark_trampoline` in BDWGC's mark procedure table, plus one (zero
+// means not yet registered). Every kind shares the trampoline; the Rust
+// callback is carried in the descriptor's environment bits instead.
+static TRAMPOLINE_PROC: AtomicU32 = AtomicU32::new(0);
+
+/// Handed to a mark procedure to report the objects it references.
+pub struct Marker {
+    top: *mut u8,
+    limit: *mut u8,
+}
+
+impl Marker {
+    /// Mark the GC object `ptr` points to (it may be an interior pointer) and
+    /// queue it for scanning. Pointers outside the GC heap are ignored.
+    #[inline]
+    pub fn push(&mut self, ptr: *const u8) {
+        let ptr = ptr as *mut u8;
+        unsafe {
+            if crate::GC_least_plausible_heap_addr < ptr
+                && ptr < crate::GC_greatest_plausible_heap_addr
+            {
+                self.top = crate::GC_mark_and_push(ptr, self.top, self.limit, ptr::null_mut());
+            }
+        }
+    }
+}
+
+/// An object kind scanned by a custom mark procedure.
+#[derive(Debug, Clone, Copy)]
+pub struct MarkProcKind {
+    kind: u32,
+}
+
+impl MarkProcKind {
+    /// Allocate a zeroed object of `size` bytes of this kind. Returns null on
+    /// failure.
+    #[inline]
+    pub fn malloc(&self, size: usize) -> *mut u8 {
+        unsafe { crate::GC_generic_malloc(size, self.kind as i32) }
+    }
+}
+
+/// Create a new object kind whose objects are traced by `f` instead of being
+/// scanned conservatively. This lets the collector follow pointers stored in
+/// forms it cannot recognise, such as tagged pointers: `f` receives the base
+/// of an object and must [`Marker::push`] every object it references.
+///
+/// `f` runs during marking, with the world stopped and possibly on several
+/// marker threads at once. It must not allocate, take locks, or panic. It may
+/// also be called on an object sitting on a free list: such objects are
+/// zeroed except for the first word, which holds a free-list link.
+///
+/// Each call creates a new kind. BDWGC supports only a small, fixed number of
+/// kinds, so register once per type of object rather than per object.
+pub fn register_mark_proc(f: fn(*mut u8, &mut Marker)) -> MarkProcKind {
+    let proc_index = match TRAMPOLINE_PROC.load(Ordering::Acquire) {
+        0 => {
+            // Racing registrations waste a table slot but agree on a winner.
+            let index = unsafe { crate::GC_new_proc(mark_trampoline) } + 1;
+            match TRAMPOLINE_PROC.compare_exchange(0, index, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => index,
+                Err(winner) => winner,
+            }
+        }
+        index => index,
+    } - 1;
+
+    let env = f as usize;
+    debug_assert!(env >> (usize::BITS as usize - GC_LOG_MAX_MARK_PROCS - GC_DS_TAG_BITS) == 0);
+    let descr =
+        (((env << GC_LOG_MAX_MARK_PROCS) | proc_index as usize) << GC_DS_TAG_BITS) | GC_DS_PROC;
+    let kind = unsafe { crate::GC_new_kind(crate::GC_new_free_list(), descr, 0, 1) };
+    MarkProcKind { kind }
+}
+
+unsafe extern "C" fn mark_trampoline(
+    addr: *mut usize,
+    mark_stack_top: *mut u8,
+    mark_stack_limit: *mut u8,
+    env: usize,
+) -> *mut u8 {
+    let f: fn(*mut u8, &mut Marker) = unsafe { core::mem::transmute(env) };
+    let mut marker = Marker { top: mark_stack_top, limit: mark_stack_limit };
+    f(addr as *mut u8, &mut marker);
+    marker.top
+}
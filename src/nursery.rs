@@ -0,0 +1,125 @@
+//! A bump-allocated region for short-lived objects.
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::ptr::{self, NonNull};
+
+/// A fixed-size slab that hands out memory by bumping a pointer, for
+/// allocation-heavy loops whose objects almost all die young.
+///
+/// BDWGC does not move objects, so there is no copying minor collection.
+/// Instead the owner decides when every object in the nursery is dead and
+/// calls [`reset`], which reuses the whole slab at once; the few objects that
+/// must outlive that point are copied out to the main heap with [`promote`]
+/// beforehand. Objects that die in the nursery never touch the main heap, so
+/// they neither grow it nor count towards triggering a collection.
+///
+/// The slab is uncollectable and scanned, so GC pointers stored in nursery
+/// objects keep their targets alive. It is freed when the `Nursery` is
+/// dropped. A `Nursery` is meant to be owned by a single thread.
+///
+/// [`reset`]: Nursery::reset
+/// [`promote`]: Nursery::promote
+pub struct Nursery {
+    slab: NonNull<u8>,
+    capacity: usize,
+    top: usize,
+    _not_send: PhantomData<*mut u8>,
+}
+
+impl Nursery {
+    /// Allocate a nursery of `capacity` bytes. Returns `None` if the
+    /// allocation fails.
+    pub fn new(capacity: usize) -> Option<Self> {
+        let slab = NonNull::new(unsafe { crate::GC_malloc_uncollectable(capacity.max(1)) })?;
+        Some(Nursery { slab, capacity, top: 0, _not_send: PhantomData })
+    }
+
+    /// Bump-allocate zeroed memory for `layout`. Returns `None` when the
+    /// nursery is full; the caller should then [`reset`](Nursery::reset) it
+    /// or allocate from the main heap.
+    #[inline]
+    pub fn alloc(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let base = self.slab.as_ptr() as usize;
+        let start = (base + self.top).checked_next_multiple_of(layout.align())? - base;
+        let end = start.checked_add(layout.size())?;
+        if end > self.capacity {
+            return None;
+        }
+        self.top = end;
+        Some(unsafe { NonNull::new_unchecked(self.slab.as_ptr().add(start)) })
+    }
+
+    /// Move `value` into the nursery, handing it back if it does not fit.
+    pub fn alloc_value<T>(&mut self, value: T) -> Result<NonNull<T>, T> {
+        match self.alloc(Layout::new::<T>()) {
+            Some(ptr) => {
+                let ptr = ptr.cast::<T>();
+                unsafe { ptr.as_ptr().write(value) };
+                Ok(ptr)
+            }
+            None => Err(value),
+        }
+    }
+
+    /// Copy the object at `obj` out to the main GC heap and return the copy.
+    /// Returns `None` if the allocation fails.
+    ///
+    /// # Safety
+    ///
+    /// `obj` must point to an initialised `T` in this nursery. The copy is a
+    /// bitwise move: the original must not be used afterwards, and pointers
+    /// to it must be updated to the copy before the nursery is reset.
+    pub unsafe fn promote<T>(&self, obj: NonNull<T>) -> Option<NonNull<T>> {
+        debug_assert!(self.contains(obj.as_ptr() as *const u8));
+        let layout = Layout::new::<T>();
+        if layout.size() == 0 {
+            return Some(NonNull::dangling());
+        }
+        let copy = NonNull::new(unsafe { crate::gc_malloc(layout) })?.cast::<T>();
+        unsafe { ptr::copy_nonoverlapping(obj.as_ptr(), copy.as_ptr(), 1) };
+        Some(copy)
+    }
+
+    /// Discard every object in the nursery and make the whole slab available
+    /// again.
+    ///
+    /// The used part of the slab is zeroed so that stale pointers in it no
+    /// longer keep main-heap objects alive, and the collector is then given a
+    /// small slice of incremental work (`GC_collect_a_little`), making this
+    /// the natural point for a "minor collection" in the owner's loop.
+    ///
+    /// # Safety
+    ///
+    /// No pointer into the nursery may be used after this call. Survivors
+    /// must have been [`promote`](Nursery::promote)d first. Destructors of
+    /// the discarded objects are not run.
+    pub unsafe fn reset(&mut self) {
+        unsafe {
+            ptr::write_bytes(self.slab.as_ptr(), 0, self.top);
+            crate::GC_collect_a_little();
+        }
+        self.top = 0;
+    }
+
+    /// Whether `ptr` points into the used part of the nursery.
+    pub fn contains(&self, ptr: *const u8) -> bool {
+        let base = self.slab.as_ptr() as usize;
+        (base..base + self.top).contains(&(ptr as usize))
+    }
+
+    /// Bytes handed out since the last reset, including alignment padding.
+    pub fn used(&self) -> usize {
+        self.top
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl Drop for Nursery {
+    fn drop(&mut self) {
+        unsafe { crate::GC_free(self.slab.as_ptr()) };
+    }
+}
@@ -0,0 +1,52 @@
+//! Single pointer-free values on the GC heap.
+
+use core::alloc::{Allocator, Layout};
+use core::ptr::NonNull;
+
+use crate::AtomicGcAllocator;
+
+/// Types whose values never hold a pointer the collector must see, and so
+/// may live in memory it does not scan.
+///
+/// # Safety
+///
+/// No value of the type may contain a pointer (or a reference, or an
+/// integer standing in for one) that is the only thing keeping a GC object
+/// alive. Plain numbers, `bool`, `char`, and arrays and tuples of such
+/// types qualify.
+pub unsafe trait NoGcPointers {}
+
+macro_rules! no_gc_pointers {
+    ($($t:ty),*) => {
+        $(unsafe impl NoGcPointers for $t {})*
+    };
+}
+
+no_gc_pointers!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+no_gc_pointers!(f32, f64, bool, char, ());
+
+unsafe impl<T: NoGcPointers, const N: usize> NoGcPointers for [T; N] {}
+
+macro_rules! no_gc_pointers_tuple {
+    ($($t:ident),+) => {
+        unsafe impl<$($t: NoGcPointers),+> NoGcPointers for ($($t,)+) {}
+    };
+}
+
+no_gc_pointers_tuple!(A);
+no_gc_pointers_tuple!(A, B);
+no_gc_pointers_tuple!(A, B, C);
+no_gc_pointers_tuple!(A, B, C, D);
+
+/// Move `value` into a new GC object that the collector never scans, the
+/// single-value counterpart of [`AtomicGcAllocator`]. Worth it for large
+/// plain-data values such as `[f64; 4096]`, which would otherwise be scanned
+/// word by word on every collection. Returns `None` if the heap is
+/// exhausted.
+///
+/// The object is reclaimed once unreachable; `T` is not dropped.
+pub fn gc_new_atomic<T: NoGcPointers>(value: T) -> Option<NonNull<T>> {
+    let ptr = AtomicGcAllocator.allocate(Layout::new::<T>()).ok()?.cast::<T>();
+    unsafe { ptr.as_ptr().write(value) };
+    Some(ptr)
+}
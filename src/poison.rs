@@ -0,0 +1,86 @@
+//! Poisoning of unreachable objects, to expose use-after-collection.
+//!
+//! Objects allocated through [`crate::GcAllocator`] (except over-aligned
+//! ones) come from two object kinds of their own, scanned and pointer-free,
+//! whose disclaim procedure the collector calls on every unreachable object
+//! as it sweeps it. The first time, the object is overwritten with
+//! [`POISON_BYTE`] and kept for one more cycle, so a raw pointer kept past
+//! that point reads an obviously bogus pattern instead of plausible stale
+//! data. The next collection that finds it still poisoned reclaims it, and
+//! the collector zeroes it for reuse.
+//!
+//! Poisoning at sweep time rather than from a finalizer keeps it out of the
+//! way of the crate's own finalizers: an object still referenced from an
+//! object queued for finalization is marked by the collector, so it is
+//! never poisoned before that finalizer has run and dropped its owner.
+//! Nothing is registered per object, so explicit frees and `GC_realloc` need
+//! no special care. Large pointer-free buffers are not allocated with
+//! `GC_malloc_atomic_ignore_off_page` while poisoning is on.
+
+use core::{mem, slice};
+
+/// The byte written over objects found unreachable.
+pub const POISON_BYTE: u8 = 0xDD;
+
+const POISON_WORD: usize = usize::from_ne_bytes([POISON_BYTE; mem::size_of::<usize>()]);
+
+// From gc_mark.h: a length descriptor with the object size added covers the
+// whole object, zero covers none of it.
+const GC_DS_LENGTH: usize = 0;
+
+static mut KINDS_ONCE: libc::pthread_once_t = libc::PTHREAD_ONCE_INIT;
+static mut SCANNED_KIND: i32 = 0;
+static mut ATOMIC_KIND: i32 = 0;
+
+fn kinds() -> (i32, i32) {
+    extern "C" fn create_kinds() {
+        unsafe {
+            crate::GC_init();
+            let scanned = crate::GC_new_kind(crate::GC_new_free_list(), GC_DS_LENGTH, 1, 1) as i32;
+            let atomic = crate::GC_new_kind(crate::GC_new_free_list(), GC_DS_LENGTH, 0, 1) as i32;
+            crate::GC_register_disclaim_proc(scanned, poison, 0);
+            crate::GC_register_disclaim_proc(atomic, poison, 0);
+            SCANNED_KIND = scanned;
+            ATOMIC_KIND = atomic;
+        }
+    }
+
+    unsafe {
+        crate::pthread_once(&raw mut KINDS_ONCE, create_kinds);
+        (SCANNED_KIND, ATOMIC_KIND)
+    }
+}
+
+/// Allocate a zeroed, scanned object of `size` bytes that is poisoned once
+/// unreachable. Returns null on failure.
+#[inline]
+pub(crate) fn malloc(size: usize) -> *mut u8 {
+    unsafe { crate::GC_generic_malloc(size, kinds().0) }
+}
+
+/// Like [`malloc`], but the object is never scanned for pointers.
+#[inline]
+pub(crate) fn malloc_atomic(size: usize) -> *mut u8 {
+    unsafe { crate::GC_generic_malloc(size, kinds().1) }
+}
+
+// Called with the allocator lock held for every unmarked object of the
+// poisoning kinds as its block is swept. Returns nonzero to keep the object
+// for another cycle.
+unsafe extern "C" fn poison(obj: *mut u8) -> libc::c_int {
+    let words = unsafe {
+        slice::from_raw_parts_mut(obj as *mut usize, crate::GC_size(obj) / mem::size_of::<usize>())
+    };
+    // Poisoned by an earlier sweep: let it be reclaimed.
+    if words.iter().all(|&w| w == POISON_WORD) {
+        return 0;
+    }
+    // Slots on a free list are swept too. They hold a link in the first word
+    // and zeros after it; poisoning one would break the list. An object that
+    // happens to look the same is simply reclaimed unpoisoned.
+    if words.get(1..).is_none_or(|rest| rest.iter().all(|&w| w == 0)) {
+        return 0;
+    }
+    words.fill(POISON_WORD);
+    1
+}
@@ -0,0 +1,65 @@
+//! Handles to a part of a GC object that keep the whole object alive.
+
+use core::ops::Deref;
+use core::ptr::NonNull;
+
+/// A pointer to a field (or any other part) of a GC object, which keeps the
+/// whole containing object alive.
+///
+/// The handle stores only the interior pointer. It relies on the collector
+/// recognising interior pointers (`GC_all_interior_pointers`, on by
+/// default): where that is switched off, the projection does not root the
+/// object and the field can be reclaimed under it. Like any pointer to GC
+/// memory, the handle must be kept where the collector scans it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct GcProjected<U: ?Sized> {
+    ptr: NonNull<U>,
+}
+
+impl<U: ?Sized> Clone for GcProjected<U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<U: ?Sized> Copy for GcProjected<U> {}
+
+impl<U: ?Sized> GcProjected<U> {
+    /// Project `obj` to the part of it selected by `f`, which must return a
+    /// reference into `*obj` rather than to somewhere else.
+    ///
+    /// # Safety
+    ///
+    /// `obj` must point to an initialised value inside a live GC object, and
+    /// the value must not be mutated or dropped while the projection is used.
+    pub unsafe fn new<T: ?Sized>(obj: NonNull<T>, f: impl FnOnce(&T) -> &U) -> Self {
+        assert!(
+            unsafe { crate::GC_get_all_interior_pointers() } != 0,
+            "GcProjected requires interior pointer recognition"
+        );
+        let field = f(unsafe { obj.as_ref() });
+        debug_assert_eq!(
+            crate::base_of(field as *const U as *const u8),
+            crate::base_of(obj.as_ptr() as *const u8),
+            "projection left the containing GC object"
+        );
+        GcProjected { ptr: NonNull::from(field) }
+    }
+
+    pub fn as_ptr(self) -> NonNull<U> {
+        self.ptr
+    }
+
+    /// The base address of the containing object.
+    pub fn base(self) -> Option<NonNull<u8>> {
+        crate::base_of(self.ptr.as_ptr() as *const u8)
+    }
+}
+
+impl<U: ?Sized> Deref for GcProjected<U> {
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        unsafe { self.ptr.as_ref() }
+    }
+}
@@ -0,0 +1,139 @@
+//! Correlate the GC heap with the pages the OS actually has resident.
+
+use core::{cmp, mem, ptr};
+
+// Values of BDWGC's `GC_heap_section_type` that we care about.
+const GC_HEAP_SECTION_TYPE_UNMAPPED: libc::c_int = 3;
+const GC_HEAP_SECTION_TYPE_WHOLE_SECT: libc::c_int = 5;
+
+// Number of pages queried per `mincore` call. The residency vector lives on
+// the stack because the walk runs with the allocator lock held.
+const MINCORE_CHUNK_PAGES: usize = 4096;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ResidentReport {
+    /// Total size of all GC heap sections, including unmapped blocks.
+    pub gc_heap_bytes: usize,
+    /// Bytes of the GC heap currently backed by resident pages.
+    pub gc_resident_bytes: usize,
+    /// Bytes of the GC heap that are not resident (never touched, swapped
+    /// out, or unmapped).
+    pub gc_non_resident_bytes: usize,
+    /// Bytes of the GC heap in blocks the collector has unmapped.
+    pub gc_unmapped_bytes: usize,
+    /// Resident set size of the whole process.
+    pub process_rss_bytes: usize,
+    /// Resident bytes outside the GC heap (`process_rss_bytes` minus
+    /// `gc_resident_bytes`).
+    pub other_rss_bytes: usize,
+    /// Page size used for the residency queries.
+    pub page_size: usize,
+}
+
+/// Break down the process's resident memory into GC heap and everything else.
+///
+/// The heap sections are walked with the allocator lock held, so no GC
+/// allocation (or collection) can happen concurrently. The walk itself does
+/// not allocate. Process RSS is read from `/proc/self/statm` afterwards, so
+/// the two halves of the report are not an atomic snapshot.
+///
+/// BDWGC before 8.3 cannot enumerate its heap sections (see
+/// [`crate::GcCapabilities`]); there the GC heap fields are all zero and
+/// the whole RSS is reported as `other_rss_bytes`.
+pub fn resident_report() -> ResidentReport {
+    let mut report = ResidentReport {
+        page_size: unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize },
+        ..Default::default()
+    };
+    if crate::capabilities().foreach_heap_section {
+        unsafe {
+            crate::GC_call_with_alloc_lock(walk_locked, &mut report as *mut _ as *mut u8);
+        }
+    }
+    report.process_rss_bytes = process_rss_pages().unwrap_or(0) * report.page_size;
+    report.other_rss_bytes = report.process_rss_bytes.saturating_sub(report.gc_resident_bytes);
+    report
+}
+
+unsafe extern "C" fn walk_locked(data: *mut u8) -> *mut u8 {
+    unsafe { crate::GC_foreach_heap_section_inner(visit_section, data) };
+    ptr::null_mut()
+}
+
+unsafe extern "C" fn visit_section(
+    start: *mut u8,
+    finish: *mut u8,
+    ty: libc::c_int,
+    data: *mut u8,
+) {
+    let report = unsafe { &mut *(data as *mut ResidentReport) };
+    let len = finish as usize - start as usize;
+    match ty {
+        GC_HEAP_SECTION_TYPE_WHOLE_SECT => {
+            let resident = resident_bytes(start, len, report.page_size);
+            report.gc_heap_bytes += len;
+            report.gc_resident_bytes += resident;
+            report.gc_non_resident_bytes += len - resident;
+        }
+        GC_HEAP_SECTION_TYPE_UNMAPPED => report.gc_unmapped_bytes += len,
+        _ => {}
+    }
+}
+
+/// Count resident bytes in `[start, start + len)`. Ranges `mincore` rejects
+/// (e.g. because they are no longer mapped) count as non-resident.
+fn resident_bytes(start: *mut u8, len: usize, page_size: usize) -> usize {
+    let mut vec = [0u8; MINCORE_CHUNK_PAGES];
+    let begin = start as usize & !(page_size - 1);
+    let end = start as usize + len;
+    let mut resident = 0;
+    let mut addr = begin;
+    while addr < end {
+        let pages = cmp::min((end - addr).div_ceil(page_size), MINCORE_CHUNK_PAGES);
+        let ret = unsafe {
+            libc::mincore(addr as *mut libc::c_void, pages * page_size, vec.as_mut_ptr())
+        };
+        if ret == 0 {
+            for (i, v) in vec[..pages].iter().enumerate() {
+                if v & 1 != 0 {
+                    // Only count the part of the page that lies within the range.
+                    let lo = cmp::max(addr + i * page_size, start as usize);
+                    let hi = cmp::min(addr + (i + 1) * page_size, end);
+                    resident += hi - lo;
+                }
+            }
+        }
+        addr += pages * page_size;
+    }
+    resident
+}
+
+/// Read the resident page count (second field) from `/proc/self/statm`.
+pub(crate) fn process_rss_pages() -> Option<usize> {
+    let mut buf = [0u8; 128];
+    let n = unsafe {
+        let fd = libc::open(c"/proc/self/statm".as_ptr(), libc::O_RDONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return None;
+        }
+        let n = libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, mem::size_of_val(&buf));
+        libc::close(fd);
+        n
+    };
+    if n <= 0 {
+        return None;
+    }
+    let mut fields = buf[..n as usize].split(|b| b.is_ascii_whitespace());
+    fields.next()?;
+    let rss = fields.next()?;
+    if rss.is_empty() {
+        return None;
+    }
+    rss.iter().try_fold(0usize, |acc, &b| {
+        if b.is_ascii_digit() {
+            Some(acc * 10 + (b - b'0') as usize)
+        } else {
+            None
+        }
+    })
+}
@@ -0,0 +1,68 @@
+//! Detection of objects made reachable again by their own finalizer.
+//!
+//! In Rust terms a finalized object has been dropped, so an object that its
+//! finalizer stashes somewhere reachable ("resurrects") is a use-after-free
+//! waiting to happen. After a crate finalizer has run, the object is
+//! remembered here; at the end of the next mark phase it should be
+//! unmarked, and a warning is printed if it is not.
+//!
+//! Marking is conservative, so a stale copy of the address on some stack
+//! can produce a false report; a resurrected object is reported at every
+//! collection until it dies for good.
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+const SLOTS: usize = 256;
+
+// Watched objects, by address with all bits flipped (as `GC_HIDE_POINTER`
+// does) so that the collector, which scans this static, does not see them;
+// zero marks a free slot.
+static WATCHED: [AtomicUsize; SLOTS] = [const { AtomicUsize::new(0) }; SLOTS];
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+static RESURRECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Watch the object at `obj`, whose finalizer has just run, for
+/// resurrection. Call it from a finalizer after the object's destructor
+/// has run. If too many objects are being watched at once, the object is
+/// not watched.
+///
+/// # Safety
+///
+/// `obj` must be the base address of an object allocated by the collector.
+pub unsafe fn watch_for_resurrection(obj: *const u8) {
+    crate::events::install();
+    for slot in &WATCHED {
+        if slot.compare_exchange(0, !(obj as usize), Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+            return;
+        }
+    }
+    DROPPED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of finalized objects found reachable again so far.
+pub fn resurrection_count() -> usize {
+    RESURRECTIONS.load(Ordering::Relaxed)
+}
+
+/// Check every watched object against the mark bits. Called at the end of
+/// the mark phase with the allocator lock held: nothing here may allocate.
+pub(crate) fn check_marked() {
+    for slot in &WATCHED {
+        let hidden = slot.load(Ordering::Acquire);
+        if hidden == 0 {
+            continue;
+        }
+        let obj = !hidden;
+        if unsafe { crate::GC_is_marked(obj as *const u8) } == 0 {
+            // Dead for good: it is reclaimed by this collection.
+            slot.store(0, Ordering::Release);
+            continue;
+        }
+        RESURRECTIONS.fetch_add(1, Ordering::Relaxed);
+        let _ = writeln!(
+            crate::Stderr,
+            "bmalloc: object {obj:#x} is reachable again after its finalizer ran (resurrected)"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Choice of the signals used to stop and restart the world.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Bit `n - 1` is set when signal `n` is reserved by the application.
+static RESERVED: AtomicU64 = AtomicU64::new(0);
+
+/// Why [`set_gc_signals`] refused a pair of signals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalConflict {
+    /// The collector is already initialised and no longer accepts new
+    /// signals.
+    AlreadyInitialized,
+    /// Not a signal number the collector can install a handler for.
+    Invalid(i32),
+    /// Suspend and restart were both given as this signal.
+    SameSignal(i32),
+    /// The signal was reserved with [`reserve_signals`].
+    Reserved(i32),
+}
+
+impl fmt::Display for SignalConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SignalConflict::AlreadyInitialized => {
+                f.write_str("GC signals must be set before the collector is initialised")
+            }
+            SignalConflict::Invalid(sig) => write!(f, "{sig} is not a usable signal number"),
+            SignalConflict::SameSignal(sig) => {
+                write!(f, "signal {sig} requested for both thread suspend and restart")
+            }
+            SignalConflict::Reserved(sig) => {
+                write!(f, "signal {sig} is reserved by the application")
+            }
+        }
+    }
+}
+
+/// Declare signals the application uses itself (e.g. `SIGALRM` for timers),
+/// so that [`set_gc_signals`] rejects them. Adds to any earlier reservation.
+pub fn reserve_signals(signals: &[i32]) {
+    let mut mask = 0;
+    for &sig in signals {
+        if let Some(bit) = bit(sig) {
+            mask |= bit;
+        }
+    }
+    RESERVED.fetch_or(mask, Ordering::Relaxed);
+}
+
+/// Use `suspend` and `restart` as the signals that stop and resume threads
+/// during a collection, instead of BDWGC's defaults.
+///
+/// Both signals are checked against each other and against the set passed
+/// to [`reserve_signals`] before anything is applied. The collector only
+/// honours the choice if it has not been initialised yet, so this must run
+/// before [`crate::init`] and before the first allocation.
+pub fn set_gc_signals(suspend: i32, restart: i32) -> Result<(), SignalConflict> {
+    if unsafe { crate::GC_is_init_called() } != 0 {
+        return Err(SignalConflict::AlreadyInitialized);
+    }
+    let reserved = RESERVED.load(Ordering::Relaxed);
+    for sig in [suspend, restart] {
+        let bit = bit(sig).ok_or(SignalConflict::Invalid(sig))?;
+        if reserved & bit != 0 {
+            return Err(SignalConflict::Reserved(sig));
+        }
+    }
+    if suspend == restart {
+        return Err(SignalConflict::SameSignal(suspend));
+    }
+    unsafe {
+        crate::GC_set_suspend_signal(suspend);
+        crate::GC_set_thr_restart_signal(restart);
+    }
+    Ok(())
+}
+
+/// The signal used to suspend threads, or -1 where signals are not used.
+pub fn suspend_signal() -> i32 {
+    unsafe { crate::GC_get_suspend_signal() }
+}
+
+/// The signal used to restart threads, or -1 where signals are not used.
+pub fn restart_signal() -> i32 {
+    unsafe { crate::GC_get_thr_restart_signal() }
+}
+
+fn bit(sig: i32) -> Option<u64> {
+    if !(1..=64).contains(&sig) || sig == libc::SIGKILL || sig == libc::SIGSTOP {
+        return None;
+    }
+    Some(1 << (sig - 1))
+}
@@ -0,0 +1,98 @@
+//! Objects split into a scanned header and a pointer-free body.
+
+use core::alloc::Layout;
+use core::mem;
+use core::ptr::{self, NonNull};
+
+#[repr(C)]
+struct Header<H, B> {
+    // Keeps the body alive: the header is scanned, the body is not.
+    body: NonNull<B>,
+    value: H,
+}
+
+/// An object whose pointer-bearing part `H` lives in scanned memory and
+/// whose bulk, pointer-free part `B` lives in a separate atomic object.
+///
+/// Allocating a struct with a large inline buffer as one scanned object
+/// makes every collection scan the buffer for pointers it cannot contain.
+/// Here only the header is scanned; it holds the sole pointer to the body,
+/// which keeps the body alive for as long as the header is. If either part
+/// needs dropping, a finalizer on the header drops both once the header is
+/// unreachable. Like any pointer to GC memory, the handle must be kept where
+/// the collector scans it.
+pub struct SplitAlloc<H, B> {
+    header: NonNull<Header<H, B>>,
+}
+
+impl<H, B> Clone for SplitAlloc<H, B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<H, B> Copy for SplitAlloc<H, B> {}
+
+impl<H, B> SplitAlloc<H, B> {
+    /// Move `header` and `body` into a new pair of GC objects. Returns
+    /// `None` if an allocation fails.
+    ///
+    /// # Safety
+    ///
+    /// `body` is not scanned: it must not hold the only pointer keeping a GC
+    /// object alive.
+    pub unsafe fn new(header: H, body: B) -> Option<Self> {
+        let split = unsafe { Self::new_zeroed(header)? };
+        unsafe { split.body().as_ptr().write(body) };
+        Some(split)
+    }
+
+    /// Like [`new`](Self::new), but with the body zero-filled in place
+    /// instead of moved in, for bodies too large to build on the stack.
+    ///
+    /// # Safety
+    ///
+    /// As for [`new`](Self::new); in addition, all zeroes must be a valid
+    /// `B`.
+    pub unsafe fn new_zeroed(header: H) -> Option<Self> {
+        let layout = Layout::new::<B>();
+        assert!(
+            layout.align() <= crate::GC_MALLOC_ALIGN,
+            "SplitAlloc body alignment exceeds what GC_malloc_atomic provides"
+        );
+        let body = crate::gc_malloc_atomic(layout.size().max(1))?;
+        unsafe { ptr::write_bytes(body.as_ptr(), 0, layout.size()) };
+        let header_ptr = NonNull::new(unsafe { crate::gc_malloc(Layout::new::<Header<H, B>>()) })?
+            .cast::<Header<H, B>>();
+        unsafe {
+            header_ptr.as_ptr().write(Header { body: body.cast(), value: header });
+            if mem::needs_drop::<H>() || mem::needs_drop::<B>() {
+                crate::finalize::drop_when_unreachable::<Header<H, B>>(
+                    header_ptr.as_ptr() as *mut u8,
+                    ptr::null_mut(),
+                );
+            }
+        }
+        Some(SplitAlloc { header: header_ptr })
+    }
+
+    /// The scanned, pointer-bearing part.
+    pub fn header(self) -> NonNull<H> {
+        unsafe { NonNull::new_unchecked(&raw mut (*self.header.as_ptr()).value) }
+    }
+
+    /// The unscanned, pointer-free part.
+    pub fn body(self) -> NonNull<B> {
+        unsafe { (*self.header.as_ptr()).body }
+    }
+}
+
+impl<H, B> crate::finalize::DropGlue for Header<H, B> {
+    unsafe fn drop(obj: *mut u8, _: *mut u8) {
+        let header = obj as *mut Header<H, B>;
+        unsafe {
+            ptr::drop_in_place((*header).body.as_ptr());
+            ptr::drop_in_place(&raw mut (*header).value);
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! Aggregations over the collector's statistics.
+
+use core::fmt;
+
+use crate::{HeapUsage, ProfileStats, ProfileStatsError};
+
+/// Free space (as a percentage of the heap) below which [`health`] reports
+/// [`HealthStatus::Warn`].
+pub const HEALTH_WARN_FREE_PERCENT: f64 = 10.0;
+
+/// Free space (as a percentage of the heap) below which [`health`] reports
+/// [`HealthStatus::Critical`].
+pub const HEALTH_CRITICAL_FREE_PERCENT: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    Ok,
+    Warn,
+    Critical,
+}
+
+impl HealthStatus {
+    /// Classify a heap by the percentage of it that is free.
+    pub fn from_free_percent(free_percent: f64) -> Self {
+        if free_percent < HEALTH_CRITICAL_FREE_PERCENT {
+            HealthStatus::Critical
+        } else if free_percent < HEALTH_WARN_FREE_PERCENT {
+            HealthStatus::Warn
+        } else {
+            HealthStatus::Ok
+        }
+    }
+}
+
+/// A summary of the collector's state, e.g. for a health-check endpoint.
+#[derive(Debug, Clone, Copy)]
+pub struct GcHealth {
+    /// Heap size in bytes (including area unmapped to OS).
+    pub heap_size: usize,
+    /// Free and unmapped bytes as a percentage of the heap.
+    pub free_percent: f64,
+    /// Unmapped bytes as a percentage of the heap.
+    pub unmapped_percent: f64,
+    /// Number of collections so far.
+    pub collections: usize,
+    /// Approximate number of bytes reclaimed by the most recent collection.
+    pub last_reclaimed_bytes: usize,
+    pub status: HealthStatus,
+}
+
+/// Summarise the collector's health.
+///
+/// Everything is derived from a single [`get_prof_stats`] snapshot (which
+/// carries the same heap counters as [`heap_usage`]), so the figures are
+/// consistent with each other. An empty heap counts as entirely free. Fails
+/// if that snapshot does not pass validation.
+///
+/// [`get_prof_stats`]: crate::get_prof_stats
+/// [`heap_usage`]: crate::heap_usage
+pub fn health() -> Result<GcHealth, ProfileStatsError> {
+    let stats = crate::get_prof_stats()?;
+    let percent_of_heap = |bytes: usize, if_empty: f64| {
+        if stats.heapsize_full == 0 {
+            if_empty
+        } else {
+            bytes as f64 * 100.0 / stats.heapsize_full as f64
+        }
+    };
+    let free_percent = percent_of_heap(stats.free_bytes_full, 100.0);
+    Ok(GcHealth {
+        heap_size: stats.heapsize_full,
+        free_percent,
+        unmapped_percent: percent_of_heap(stats.unmapped_bytes, 0.0),
+        collections: stats.gc_no,
+        last_reclaimed_bytes: stats.bytes_reclaimed_since_gc,
+        status: HealthStatus::from_free_percent(free_percent),
+    })
+}
+
+/// Render a byte count with a binary unit (B, KiB, MiB, GiB), e.g.
+/// `format!("{}", format_bytes(3 << 20))` gives `3.0 MiB`.
+pub fn format_bytes(bytes: usize) -> impl fmt::Display {
+    struct Bytes(usize);
+
+    impl fmt::Display for Bytes {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+            if self.0 < 1024 {
+                return write!(f, "{} B", self.0);
+            }
+            let mut value = self.0 as f64 / 1024.0;
+            let mut unit = 0;
+            while value >= 1024.0 && unit < UNITS.len() - 1 {
+                value /= 1024.0;
+                unit += 1;
+            }
+            write!(f, "{value:.1} {}", UNITS[unit])
+        }
+    }
+
+    Bytes(bytes)
+}
+
+impl fmt::Display for ProfileStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "collections:              {}", self.gc_no)?;
+        writeln!(f, "heap size:                {}", format_bytes(self.heapsize_full))?;
+        writeln!(f, "free:                     {}", format_bytes(self.free_bytes_full))?;
+        writeln!(f, "unmapped:                 {}", format_bytes(self.unmapped_bytes))?;
+        writeln!(f, "allocated since GC:       {}", format_bytes(self.bytes_allocd_since_gc))?;
+        writeln!(f, "allocated before GC:      {}", format_bytes(self.allocd_bytes_before_gc))?;
+        writeln!(f, "not collectable:          {}", format_bytes(self.non_gc_bytes))?;
+        writeln!(f, "reclaimed by last GC:     {}", format_bytes(self.bytes_reclaimed_since_gc))?;
+        writeln!(f, "reclaimed before last GC: {}", format_bytes(self.reclaimed_bytes_before_gc))?;
+        writeln!(f, "freed explicitly:         {}", format_bytes(self.expl_freed_bytes_since_gc))?;
+        write!(f, "extra marker threads:     {}", self.markers_m1)
+    }
+}
+
+impl fmt::Display for HeapUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "heap {}, free {}, unmapped {}, allocated since GC {}, allocated total {}",
+            format_bytes(self.heap_size),
+            format_bytes(self.free_bytes),
+            format_bytes(self.unmapped_bytes),
+            format_bytes(self.bytes_since_gc),
+            format_bytes(self.total_bytes),
+        )
+    }
+}
+
+/// The collector's state at one point in time, e.g. for a crash report.
+#[derive(Debug, Clone, Copy)]
+pub struct GcSnapshot {
+    /// Version of the linked BDWGC library as `(major, minor, micro)`.
+    pub gc_version: (u8, u8, u8),
+    pub stats: ProfileStats,
+    pub usage: HeapUsage,
+}
+
+impl GcSnapshot {
+    /// Read the current statistics. Fails if they do not pass validation.
+    pub fn take() -> Result<Self, ProfileStatsError> {
+        Ok(GcSnapshot {
+            gc_version: crate::gc_version(),
+            stats: crate::get_prof_stats()?,
+            usage: crate::heap_usage(),
+        })
+    }
+
+    /// Render the snapshot as a single-line JSON object, e.g. with
+    /// `format!("{}", snapshot.to_json())` or into a [`crate::GcWriter`].
+    ///
+    /// The field set is stable: new fields may be added, but these keep
+    /// their names and meaning. All numbers are unsigned integers; sizes
+    /// are in bytes.
+    ///
+    /// ```text
+    /// {"version":"<bmalloc version>","gc_version":"<major>.<minor>.<micro>",
+    ///  "stats":{"heapsize_full":..,"free_bytes_full":..,"unmapped_bytes":..,
+    ///    "bytes_allocd_since_gc":..,"allocd_bytes_before_gc":..,
+    ///    "non_gc_bytes":..,"gc_no":..,"markers_m1":..,
+    ///    "bytes_reclaimed_since_gc":..,"reclaimed_bytes_before_gc":..,
+    ///    "expl_freed_bytes_since_gc":..},
+    ///  "usage":{"heap_size":..,"free_bytes":..,"unmapped_bytes":..,
+    ///    "bytes_since_gc":..,"total_bytes":..}}
+    /// ```
+    pub fn to_json(&self) -> impl fmt::Display + '_ {
+        struct Json<'a>(&'a GcSnapshot);
+
+        impl fmt::Display for Json<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let GcSnapshot { gc_version: (major, minor, micro), stats: s, usage: u } = self.0;
+                write!(f, "{{\"version\":\"{}\",", env!("CARGO_PKG_VERSION"))?;
+                write!(f, "\"gc_version\":\"{major}.{minor}.{micro}\",")?;
+                write!(
+                    f,
+                    "\"stats\":{{\"heapsize_full\":{},\"free_bytes_full\":{},\"unmapped_bytes\":{},\
+                     \"bytes_allocd_since_gc\":{},\"allocd_bytes_before_gc\":{},\
+                     \"non_gc_bytes\":{},\"gc_no\":{},\"markers_m1\":{},\
+                     \"bytes_reclaimed_since_gc\":{},\"reclaimed_bytes_before_gc\":{},\
+                     \"expl_freed_bytes_since_gc\":{}}},",
+                    s.heapsize_full,
+                    s.free_bytes_full,
+                    s.unmapped_bytes,
+                    s.bytes_allocd_since_gc,
+                    s.allocd_bytes_before_gc,
+                    s.non_gc_bytes,
+                    s.gc_no,
+                    s.markers_m1,
+                    s.bytes_reclaimed_since_gc,
+                    s.reclaimed_bytes_before_gc,
+                    s.expl_freed_bytes_since_gc,
+                )?;
+                write!(
+                    f,
+                    "\"usage\":{{\"heap_size\":{},\"free_bytes\":{},\"unmapped_bytes\":{},\
+                     \"bytes_since_gc\":{},\"total_bytes\":{}}}}}",
+                    u.heap_size, u.free_bytes, u.unmapped_bytes, u.bytes_since_gc, u.total_bytes,
+                )
+            }
+        }
+
+        Json(self)
+    }
+}
+
+/// Where the memory the collector holds goes, set against what the OS
+/// reports for the whole process.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MemoryBreakdown {
+    /// Mapped heap, in use or free (excluding unmapped blocks).
+    pub gc_heap: usize,
+    /// Heap blocks returned to the OS but still reserved by the collector.
+    pub gc_unmapped: usize,
+    /// Memory obtained from the OS for the collector's own data structures
+    /// (block headers, mark stacks, finalization tables, ...) rather than
+    /// for the heap.
+    pub gc_overhead: usize,
+    /// Resident set size of the whole process, or 0 where it cannot be read.
+    pub os_rss: usize,
+}
+
+/// Reconcile the collector's view of its memory with the process's resident
+/// set size (read from `/proc/self/statm` on Linux and `task_info` on
+/// macOS). RSS may be below `gc_heap` when parts of the heap were never
+/// touched, and above it by whatever else the process maps.
+///
+/// The collector's figures and RSS are read one after the other, so they
+/// are not an atomic snapshot. `gc_overhead` is 0 with BDWGC before 8.2,
+/// which does not report what it obtained from the OS.
+pub fn memory_breakdown() -> MemoryBreakdown {
+    let usage = crate::heap_usage();
+    let gc_overhead = if crate::capabilities().obtained_from_os_bytes {
+        let obtained = unsafe { crate::GC_get_obtained_from_os_bytes() };
+        obtained.saturating_sub(usage.heap_size + usage.unmapped_bytes)
+    } else {
+        0
+    };
+    MemoryBreakdown {
+        gc_heap: usage.heap_size,
+        gc_unmapped: usage.unmapped_bytes,
+        gc_overhead,
+        os_rss: process_rss_bytes().unwrap_or(0),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn process_rss_bytes() -> Option<usize> {
+    let pages = crate::resident::process_rss_pages()?;
+    Some(pages * unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize)
+}
+
+#[cfg(target_os = "macos")]
+#[allow(deprecated)]
+fn process_rss_bytes() -> Option<usize> {
+    let mut info: libc::mach_task_basic_info = unsafe { core::mem::zeroed() };
+    let mut count = libc::MACH_TASK_BASIC_INFO_COUNT;
+    let ret = unsafe {
+        libc::task_info(
+            libc::mach_task_self_,
+            libc::MACH_TASK_BASIC_INFO,
+            &mut info as *mut _ as libc::task_info_t,
+            &mut count,
+        )
+    };
+    (ret == libc::KERN_SUCCESS).then_some(info.resident_size as usize)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn process_rss_bytes() -> Option<usize> {
+    None
+}
@@ -0,0 +1,255 @@
+//! Helpers for tests whose outcome depends on what the collector reclaims.
+//!
+//! Because BDWGC scans stacks and registers conservatively, a stale copy of a
+//! pointer in a dead stack slot or callee-saved register can keep an object
+//! alive long after the last real reference is gone. The assertions here
+//! scrub the inactive part of the stack (see [`crate::clear_stack`]), and
+//! well below it, before every collection they force, which removes copies
+//! left behind by code that has already returned. [`assert_collected`]
+//! starts right below the caller's frame, before its own frames are laid
+//! over those copies. A copy still held in a live frame of the caller is not
+//! something we can remove: drop the last reference in a helper function
+//! rather than in the asserting frame.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+use crate::now;
+
+/// Observes whether a GC object has been reclaimed, without keeping it alive.
+///
+/// Backed by a disappearing link stored in uncollectable, pointer-free
+/// memory, which the collector clears when the object becomes unreachable.
+pub struct CollectionProbe {
+    link: *mut *mut u8,
+}
+
+impl CollectionProbe {
+    /// Start observing the object at `obj`. Must be created while the caller
+    /// still holds a reference, i.e. before the last one is dropped.
+    ///
+    /// # Safety
+    ///
+    /// `obj` must be the base address of a live object allocated by the
+    /// collector.
+    pub unsafe fn for_ptr(obj: *const u8) -> Self {
+        unsafe {
+            let link = crate::GC_malloc_atomic_uncollectable(core::mem::size_of::<*mut u8>())
+                as *mut *mut u8;
+            assert!(!link.is_null(), "out of memory allocating a collection probe");
+            link.write(obj as *mut u8);
+            let ret = crate::GC_general_register_disappearing_link(link, obj);
+            assert!(ret == 0, "failed to register a disappearing link ({ret})");
+            CollectionProbe { link }
+        }
+    }
+
+    /// Returns whether the observed object has been found unreachable.
+    pub fn is_collected(&self) -> bool {
+        unsafe extern "C" fn read(link: *mut u8) -> *mut u8 {
+            unsafe { *(link as *mut *mut u8) }
+        }
+        // The collector clears the link with the allocator lock held.
+        unsafe { crate::GC_call_with_alloc_lock(read, self.link as *mut u8).is_null() }
+    }
+}
+
+impl Drop for CollectionProbe {
+    fn drop(&mut self) {
+        unsafe {
+            crate::GC_unregister_disappearing_link(self.link);
+            crate::GC_free(self.link as *mut u8);
+        }
+    }
+}
+
+/// Repeatedly collect (and run pending finalizers) until the probed object is
+/// reclaimed, panicking if that has not happened within `deadline`.
+#[track_caller]
+#[inline(always)]
+pub fn assert_collected(probe: CollectionProbe, deadline: Duration) {
+    // Scrub from the caller's frame, before the frames below are laid over
+    // the copies that its returned callees left there.
+    crate::clear_stack();
+    scrub_deep();
+    collected_within(probe, deadline)
+}
+
+#[track_caller]
+#[inline(never)]
+fn collected_within(probe: CollectionProbe, deadline: Duration) {
+    let end = now() + deadline;
+    loop {
+        collect_scrubbed();
+        if probe.is_collected() {
+            return;
+        }
+        if now() >= end {
+            panic!("object was still reachable after {deadline:?}");
+        }
+    }
+}
+
+/// Repeatedly collect (and run pending finalizers) until `deadline` has
+/// passed, panicking if the probed object is reclaimed in the meantime.
+#[track_caller]
+pub fn assert_retained(probe: &CollectionProbe, deadline: Duration) {
+    let end = now() + deadline;
+    loop {
+        collect_scrubbed();
+        if probe.is_collected() {
+            panic!("object was reclaimed while expected to be reachable");
+        }
+        if now() >= end {
+            return;
+        }
+    }
+}
+
+/// Default slack allowed by [`HeapCheckpoint::new`]. Live bytes are measured
+/// from the collector's free-block accounting, which moves in units of heap
+/// blocks rather than objects.
+pub const CHECKPOINT_TOLERANCE: usize = 64 * 1024;
+
+/// Asserts that a block of test code leaves no net live allocation behind,
+/// which catches objects accidentally rooted for good (in a static, a
+/// leaked uncollectable cell, a registered root...).
+///
+/// Live bytes are measured after a scrubbed, forced collection when the
+/// checkpoint is created and again when it is checked. The check runs in
+/// [`assert_balanced`](HeapCheckpoint::assert_balanced) or, failing that,
+/// when the checkpoint is dropped; a failing check during unwinding from
+/// another panic aborts the process.
+///
+/// Other threads allocating concurrently make the result meaningless, so
+/// use this in single-threaded tests, ideally with automatic collection
+/// disabled (see `deterministic-test`).
+pub struct HeapCheckpoint {
+    baseline: usize,
+    tolerance: usize,
+    checked: bool,
+}
+
+impl HeapCheckpoint {
+    /// Record the current live bytes, allowing [`CHECKPOINT_TOLERANCE`] of
+    /// growth.
+    pub fn new() -> Self {
+        Self::with_tolerance(CHECKPOINT_TOLERANCE)
+    }
+
+    /// Record the current live bytes, allowing `tolerance` bytes of growth.
+    pub fn with_tolerance(tolerance: usize) -> Self {
+        HeapCheckpoint { baseline: live_bytes(), tolerance, checked: false }
+    }
+
+    /// Live bytes recorded when the checkpoint was created.
+    pub fn baseline(&self) -> usize {
+        self.baseline
+    }
+
+    /// Collect and panic if live bytes grew by more than the tolerance since
+    /// the checkpoint was created.
+    #[track_caller]
+    pub fn assert_balanced(mut self) {
+        self.checked = true;
+        self.check();
+    }
+
+    #[track_caller]
+    fn check(&self) {
+        let live = live_bytes();
+        let grown = live.saturating_sub(self.baseline);
+        if grown > self.tolerance {
+            panic!(
+                "heap not balanced: {grown} bytes still live since the checkpoint \
+                 (baseline {}, now {live}, tolerance {})",
+                self.baseline, self.tolerance
+            );
+        }
+    }
+}
+
+impl Default for HeapCheckpoint {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for HeapCheckpoint {
+    fn drop(&mut self) {
+        if !self.checked {
+            self.check();
+        }
+    }
+}
+
+/// Run `op` and report whether at least one collection completed while it
+/// ran, alongside its result. Collections triggered by other threads count
+/// too.
+pub fn run_observing_gc<R>(op: impl FnOnce() -> R) -> (R, bool) {
+    let before = crate::gc_count();
+    let result = op();
+    (result, crate::gc_count() != before)
+}
+
+// Finalizations already accounted for by `assert_all_finalizers_ran`.
+static FINALIZATIONS_CHECKED: AtomicUsize = AtomicUsize::new(0);
+
+/// Collect and run finalizers until no more objects are queued for
+/// finalization, then panic unless exactly `expected` finalizers ran since
+/// the previous call (or since [`crate::init`], for the first one).
+///
+/// Queued finalizers are run on the calling thread after each collection
+/// until none are left, whatever `GC_set_interrupt_finalizers` limits a
+/// single call to, so the count is that of completed finalizers. Fewer
+/// means an object meant to be finalized is still reachable, or held back
+/// by a finalization cycle; more means something was finalized that should
+/// not have been. Every finalizer registered with the collector counts, not
+/// just the crate's, and so do those queued by other threads: use it in
+/// single-threaded tests.
+#[track_caller]
+pub fn assert_all_finalizers_ran(expected: usize) {
+    // Finalizers may drop the last reference to other finalizable objects,
+    // which the next collection then queues.
+    const MAX_ROUNDS: usize = 8;
+
+    let mut total = crate::finalize::enqueued_total();
+    for _ in 0..MAX_ROUNDS {
+        collect_scrubbed();
+        while crate::finalize::invoke_finalizers() != 0 {}
+        let now = crate::finalize::enqueued_total();
+        if now == total {
+            break;
+        }
+        total = now;
+    }
+    let ran = total - FINALIZATIONS_CHECKED.swap(total, Ordering::Relaxed);
+    if ran != expected {
+        panic!("expected {expected} finalizers to run, but {ran} ran");
+    }
+}
+
+#[inline(never)]
+fn live_bytes() -> usize {
+    collect_scrubbed();
+    let usage = crate::heap_usage();
+    usage.heap_size - usage.free_bytes
+}
+
+// Zero the stack further down than `clear_stack` reaches. The allocator
+// leaves copies of what it returned that deep, and a collection's own frames
+// reach that deep too, so they would be scanned.
+#[inline(never)]
+fn scrub_deep() {
+    let mut region = core::mem::MaybeUninit::<[u8; 128 * 1024]>::uninit();
+    unsafe { core::ptr::write_bytes(region.as_mut_ptr(), 0, 1) };
+    core::hint::black_box(&mut region);
+}
+
+#[inline(never)]
+fn collect_scrubbed() {
+    crate::clear_stack();
+    scrub_deep();
+    crate::collect();
+    crate::invoke_finalizers();
+}
@@ -0,0 +1,275 @@
+//! Registration of threads not created through the collector.
+
+use core::ptr;
+
+use crate::StackBase;
+
+#[thread_local]
+static mut REGISTERED: bool = false;
+// Whether `ensure_thread_registered` registered this thread itself.
+#[thread_local]
+static mut REGISTERED_HERE: bool = false;
+
+static mut KEY_ONCE: libc::pthread_once_t = libc::PTHREAD_ONCE_INIT;
+static mut KEY: libc::pthread_key_t = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Registration {
+    /// This call registered the thread.
+    Registered,
+    /// The thread was registered already, by the collector's thread
+    /// creation wrapper or an earlier registration.
+    AlreadyRegistered,
+    /// The thread could not be registered.
+    Failed,
+}
+
+pub(crate) fn register_current_thread() -> Registration {
+    unsafe {
+        if crate::GC_thread_is_registered() != 0 {
+            return Registration::AlreadyRegistered;
+        }
+        let mut sb = StackBase { mem_base: ptr::null_mut() };
+        if crate::GC_get_stack_base(&mut sb) != 0 {
+            return Registration::Failed;
+        }
+        match crate::GC_register_my_thread(&sb) {
+            // GC_SUCCESS
+            0 => Registration::Registered,
+            // GC_DUPLICATE
+            1 => Registration::AlreadyRegistered,
+            _ => Registration::Failed,
+        }
+    }
+}
+
+/// Make sure the calling thread is registered with the collector, so that
+/// its stack is scanned and it may use the GC heap.
+///
+/// Meant for thread pools whose workers are not started through the
+/// collector's `pthread_create` wrapper: call it at the top of every task.
+/// After the first call on a thread the answer is cached in a thread-local
+/// flag, so further calls cost a single load. A thread registered here is
+/// unregistered automatically when it exits, after the thread-local
+/// destructors that might still allocate; one that was already registered
+/// is left to whoever registered it.
+///
+/// Requires registration from other threads to be allowed (see
+/// [`crate::allow_register_threads`]). Returns whether the thread is
+/// registered.
+pub fn ensure_thread_registered() -> bool {
+    if unsafe { REGISTERED } {
+        return true;
+    }
+    let registered = match register_current_thread() {
+        Registration::Registered => {
+            unsafe { REGISTERED_HERE = true };
+            unregister_at_exit();
+            true
+        }
+        Registration::AlreadyRegistered => true,
+        Registration::Failed => false,
+    };
+    unsafe { REGISTERED = registered };
+    registered
+}
+
+/// Register the calling thread with the collector for as long as it runs.
+/// The same as [`ensure_thread_registered`], which already ties the
+/// registration to the thread's lifetime.
+#[inline]
+pub fn auto_register_current_thread() -> bool {
+    ensure_thread_registered()
+}
+
+// glibc's PTHREAD_DESTRUCTOR_ITERATIONS: the number of rounds in which
+// thread-specific destructors are run at thread exit.
+const DESTRUCTOR_ROUNDS: usize = 4;
+
+fn unregister_at_exit() {
+    extern "C" fn create_key() {
+        unsafe { libc::pthread_key_create(&raw mut KEY, Some(unregister)) };
+    }
+
+    // The value is the number of destructor rounds left before the thread is
+    // unregistered. Other thread-specific destructors may still allocate
+    // (`#[thread_local]` and `thread_local!` destructors all run before any
+    // of these rounds), so re-arm until the last round to let theirs run
+    // first.
+    unsafe extern "C" fn unregister(rounds: *mut libc::c_void) {
+        let rounds = rounds as usize;
+        if rounds > 1 {
+            unsafe { libc::pthread_setspecific(KEY, (rounds - 1) as *const libc::c_void) };
+            return;
+        }
+        unsafe {
+            // Unless a failed `flush_thread_cache` unregistered it already.
+            if REGISTERED_HERE {
+                crate::GC_unregister_my_thread();
+            }
+            REGISTERED = false;
+            REGISTERED_HERE = false;
+        }
+    }
+
+    unsafe {
+        crate::pthread_once(&raw mut KEY_ONCE, create_key);
+        libc::pthread_setspecific(KEY, DESTRUCTOR_ROUNDS as *const libc::c_void);
+    }
+}
+
+/// Whether the calling thread is registered with the collector.
+pub fn thread_is_registered() -> bool {
+    unsafe { crate::GC_thread_is_registered() != 0 }
+}
+
+/// Return the objects cached in the calling thread's free lists to the
+/// collector's global free lists, e.g. before a worker parks, so that other
+/// threads can allocate them. Returns whether the cache was flushed.
+///
+/// BDWGC gives each registered thread its own small-object free lists and
+/// exposes no call to empty them: it only does so when the thread is
+/// unregistered. This therefore unregisters the thread and registers it
+/// again at once, with collection disabled in between so that no
+/// collection can run while the thread's stack is not being scanned. Other
+/// threads can keep allocating meanwhile.
+///
+/// Only threads registered by [`ensure_thread_registered`] itself can be
+/// flushed. The collector does not allow unregistering the main thread or
+/// threads started through its `pthread_create` wrapper, and registering
+/// again would lose what it recorded about them, so for those, and for
+/// unregistered threads, this does nothing and returns `false`. It also
+/// returns `false`, leaving the thread unregistered, in the unlikely case
+/// that its stack base cannot be determined again.
+pub fn flush_thread_cache() -> bool {
+    if !unsafe { REGISTERED_HERE } {
+        return false;
+    }
+    unsafe {
+        crate::GC_disable();
+        crate::GC_unregister_my_thread();
+    }
+    let registered = register_current_thread() == Registration::Registered;
+    unsafe { crate::GC_enable() };
+    if !registered {
+        unsafe {
+            REGISTERED = false;
+            REGISTERED_HERE = false;
+        }
+    }
+    registered
+}
+
+/// Run `f`, a long call that neither allocates from the GC heap nor touches
+/// GC pointers (typically a blocking FFI call or syscall), with the calling
+/// thread marked inactive.
+///
+/// A collection does not wait to stop an inactive thread, and does not scan
+/// the frames `f` pushes; only the stack up to this call is scanned. Every
+/// GC object `f` may use must therefore be reachable from elsewhere, and `f`
+/// must not create new GC pointers, except inside a nested
+/// [`call_with_gc_active`]. The calling thread must be registered, and `f`
+/// must not panic: unwinding out of it aborts.
+pub fn do_blocking<F: FnOnce() -> R, R>(f: F) -> R {
+    let mut call = Call::new(f);
+    unsafe { crate::GC_do_blocking(Call::<F, R>::trampoline, &mut call as *mut _ as *mut u8) };
+    call.take()
+}
+
+/// `read(2)` on `fd` inside [`do_blocking`], so that a collection started
+/// while it waits (e.g. on a socket) does not have to wait for it. Returns
+/// the number of bytes read, or the `errno` value on failure.
+///
+/// `buf` may be GC memory: it is kept alive by the caller's frame, which is
+/// still scanned.
+pub fn blocking_read(fd: libc::c_int, buf: &mut [u8]) -> Result<usize, i32> {
+    let n =
+        do_blocking(|| unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) });
+    if n < 0 {
+        Err(errno())
+    } else {
+        Ok(n as usize)
+    }
+}
+
+/// `write(2)` on `fd` inside [`do_blocking`]; see [`blocking_read`].
+pub fn blocking_write(fd: libc::c_int, buf: &[u8]) -> Result<usize, i32> {
+    let n =
+        do_blocking(|| unsafe { libc::write(fd, buf.as_ptr() as *const libc::c_void, buf.len()) });
+    if n < 0 {
+        Err(errno())
+    } else {
+        Ok(n as usize)
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn errno() -> i32 {
+    unsafe { *libc::__errno_location() }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+fn errno() -> i32 {
+    unsafe { *libc::__error() }
+}
+
+#[cfg(any(target_os = "openbsd", target_os = "netbsd"))]
+fn errno() -> i32 {
+    unsafe { *libc::__errno() }
+}
+
+/// Run `f` with the calling thread active for the collector, so that it may
+/// allocate and handle GC pointers.
+///
+/// Inside [`do_blocking`] this switches back to the active state for the
+/// duration of `f`. On a thread the collector does not know about, such as
+/// one calling back into Rust from C, the thread is registered for the
+/// duration of `f` and unregistered afterwards, even if `f` unwinds; GC
+/// pointers must then not outlive `f` on that thread. Otherwise, unwinding
+/// out of `f` aborts.
+pub fn call_with_gc_active<F: FnOnce() -> R, R>(f: F) -> R {
+    struct Unregister;
+    impl Drop for Unregister {
+        fn drop(&mut self) {
+            unsafe { crate::GC_unregister_my_thread() };
+        }
+    }
+
+    if unsafe { crate::GC_thread_is_registered() } == 0 {
+        let _unregister =
+            (register_current_thread() == Registration::Registered).then_some(Unregister);
+        return f();
+    }
+    let mut call = Call::new(f);
+    unsafe {
+        crate::GC_call_with_gc_active(Call::<F, R>::trampoline, &mut call as *mut _ as *mut u8)
+    };
+    call.take()
+}
+
+// A closure and its result, passed through the collector's `void *` calls.
+struct Call<F, R> {
+    f: Option<F>,
+    result: Option<R>,
+}
+
+impl<F: FnOnce() -> R, R> Call<F, R> {
+    fn new(f: F) -> Self {
+        Call { f: Some(f), result: None }
+    }
+
+    unsafe extern "C" fn trampoline(data: *mut u8) -> *mut u8 {
+        let call = unsafe { &mut *(data as *mut Self) };
+        if let Some(f) = call.f.take() {
+            call.result = Some(f());
+        }
+        ptr::null_mut()
+    }
+
+    fn take(self) -> R {
+        match self.result {
+            Some(result) => result,
+            None => unreachable!("GC callback did not run"),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Named combinations of the collector's tuning knobs.
+
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use crate::TIME_UNLIMITED;
+
+/// A coherent set of collector settings for a common workload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GcPreset {
+    /// Fewest, largest collections: the heap may grow to roughly half again
+    /// the live data between collections. Stop-the-world, no pause target.
+    Throughput,
+    /// BDWGC's defaults.
+    Balanced,
+    /// Small, frequent, incremental collections aiming at pauses of a few
+    /// milliseconds, with a tighter heap and more frequent full collections
+    /// to bound the garbage left by partial ones.
+    LowLatency,
+}
+
+impl GcPreset {
+    pub fn free_space_divisor(self) -> usize {
+        match self {
+            GcPreset::Throughput => 2,
+            GcPreset::Balanced => 3,
+            GcPreset::LowLatency => 4,
+        }
+    }
+
+    pub fn full_freq(self) -> i32 {
+        match self {
+            GcPreset::Throughput | GcPreset::Balanced => 19,
+            GcPreset::LowLatency => 10,
+        }
+    }
+
+    pub fn incremental(self) -> bool {
+        self == GcPreset::LowLatency
+    }
+
+    /// Pause-time target in milliseconds.
+    pub fn time_limit(self) -> u64 {
+        match self {
+            GcPreset::Throughput | GcPreset::Balanced => TIME_UNLIMITED,
+            GcPreset::LowLatency => 5,
+        }
+    }
+}
+
+/// Configure the collector according to `preset`.
+///
+/// The divisor, full-collection frequency and pause target are changed
+/// together under the allocator lock. Incremental mode cannot be turned off
+/// once enabled, so applying `Throughput` or `Balanced` after `LowLatency`
+/// leaves collection generational, though without a pause target.
+pub fn apply_preset(preset: GcPreset) {
+    unsafe extern "C" fn apply_locked(preset: *mut u8) -> *mut u8 {
+        let preset = unsafe { *(preset as *const GcPreset) };
+        unsafe {
+            crate::GC_set_free_space_divisor(preset.free_space_divisor());
+            crate::GC_set_full_freq(preset.full_freq());
+            crate::GC_set_time_limit(preset.time_limit() as libc::c_ulong);
+        }
+        core::ptr::null_mut()
+    }
+
+    let mut preset = preset;
+    unsafe { crate::GC_call_with_alloc_lock(apply_locked, &mut preset as *mut _ as *mut u8) };
+    if preset.incremental() {
+        crate::enable_incremental();
+    }
+}
+
+// Largest divisor the auto-tuner will set; beyond this the heap barely grows
+// and collections run back to back.
+const MAX_AUTO_DIVISOR: usize = 32;
+// Relative distance from the target within which the auto-tuner leaves the
+// divisor alone.
+const AUTO_TUNE_DEADBAND: f32 = 0.1;
+
+// Target heap-to-live ratio and smoothed observed ratio, as `f32` bits; a
+// zero target means auto-tuning is off.
+static AUTO_TARGET: AtomicU32 = AtomicU32::new(0);
+static AUTO_SMOOTHED: AtomicU32 = AtomicU32::new(0);
+// Heap size at the previous adjustment.
+static AUTO_LAST_HEAP: AtomicUsize = AtomicUsize::new(0);
+
+/// Adjust [`crate::free_space_divisor`] after every collection so that the
+/// heap stays around `target_ratio` times the live data, e.g. `1.5`. A
+/// ratio of `0.0` (or below `1.0`) turns auto-tuning off again.
+///
+/// To avoid oscillation the observed ratio is averaged exponentially over
+/// collections, nothing changes while it is within 10% of the target, and
+/// the divisor moves by one step per collection at most, within `1..=32`.
+/// BDWGC rarely shrinks the heap it has mapped, so an overshoot is only
+/// recovered as the live data grows into it; meanwhile the divisor is only
+/// raised while the heap keeps growing, since a larger one would just make
+/// collections more frequent. Adjustments are made on the first allocation
+/// after a collection, or on return from [`crate::collect`] or
+/// [`crate::run_pending_gc_hooks`] if that comes first, and do not displace
+/// the post-collection hook.
+pub fn auto_tune_overhead(target_ratio: f32) {
+    let target = if target_ratio >= 1.0 { target_ratio } else { 0.0 };
+    AUTO_SMOOTHED.store(0, Ordering::Relaxed);
+    AUTO_LAST_HEAP.store(crate::heap_usage().heap_size, Ordering::Relaxed);
+    AUTO_TARGET.store(target.to_bits(), Ordering::Release);
+    if target != 0.0 {
+        crate::events::install();
+    }
+}
+
+#[inline]
+pub(crate) fn auto_tune_active() -> bool {
+    AUTO_TARGET.load(Ordering::Relaxed) != 0
+}
+
+pub(crate) fn auto_tune_step() {
+    let target = f32::from_bits(AUTO_TARGET.load(Ordering::Acquire));
+    if target == 0.0 {
+        return;
+    }
+    let usage = crate::heap_usage();
+    let live = usage.heap_size.saturating_sub(usage.free_bytes);
+    if live == 0 {
+        return;
+    }
+    let ratio = usage.heap_size as f32 / live as f32;
+    let smoothed = match f32::from_bits(AUTO_SMOOTHED.load(Ordering::Relaxed)) {
+        0.0 => ratio,
+        prev => prev * 0.5 + ratio * 0.5,
+    };
+    AUTO_SMOOTHED.store(smoothed.to_bits(), Ordering::Relaxed);
+
+    let grew = usage.heap_size > AUTO_LAST_HEAP.swap(usage.heap_size, Ordering::Relaxed);
+    let divisor = crate::free_space_divisor();
+    let error = (smoothed - target) / target;
+    if error > AUTO_TUNE_DEADBAND && grew && divisor < MAX_AUTO_DIVISOR {
+        crate::set_free_space_divisor(divisor + 1);
+    } else if error < -AUTO_TUNE_DEADBAND && divisor > 1 {
+        crate::set_free_space_divisor(divisor - 1);
+    }
+}
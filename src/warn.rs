@@ -0,0 +1,292 @@
+//! Routing of BDWGC warnings to Rust handlers.
+
+use core::{
+    ffi::{c_char, CStr},
+    fmt::{self, Write},
+    mem,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+// Longest formatted warning passed to a handler; longer ones are truncated.
+const MAX_WARNING_LEN: usize = 512;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+// The warn proc that was installed before ours (the collector's default
+// printer, unless C code set one), as a function address.
+static PREVIOUS: AtomicUsize = AtomicUsize::new(0);
+// The Rust handler currently receiving warnings, as a `fn(&str)` address.
+static HANDLER: AtomicUsize = AtomicUsize::new(0);
+// The handler installed by `set_classified_warn_handler`, as a
+// `fn(GcWarning, &str)` address.
+static CLASSIFIED_HANDLER: AtomicUsize = AtomicUsize::new(0);
+
+/// Most warnings [`set_warning_buffer_capacity`] can be asked to keep.
+pub const MAX_BUFFERED_WARNINGS: usize = 64;
+// Longest warning kept in the buffer; longer ones are truncated.
+const BUFFERED_WARNING_LEN: usize = 256;
+
+// The warning buffer, guarded by `BUFFER_LOCKED`. `BUFFER_CAPACITY` mirrors
+// its capacity so the trampoline can skip it without taking the lock.
+static BUFFER_LOCKED: AtomicBool = AtomicBool::new(false);
+static BUFFER_CAPACITY: AtomicUsize = AtomicUsize::new(0);
+static mut WARNINGS: Warnings = Warnings {
+    slots: [([0; BUFFERED_WARNING_LEN], 0); MAX_BUFFERED_WARNINGS],
+    capacity: 0,
+    recorded: 0,
+};
+
+struct Warnings {
+    slots: [([u8; BUFFERED_WARNING_LEN], usize); MAX_BUFFERED_WARNINGS],
+    capacity: usize,
+    // Total number of warnings recorded; the newest is in slot
+    // `(recorded - 1) % capacity`.
+    recorded: usize,
+}
+
+/// The kind of a collector warning, recognised from BDWGC's message text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GcWarning<'a> {
+    /// "Repeated allocation of very large block": large objects are being
+    /// allocated over and over, which may leak or fragment the heap.
+    RepeatedLargeBlock,
+    /// "Out of Memory!" or "Failed to expand heap": the heap could not grow
+    /// to satisfy a request.
+    OutOfMemory,
+    /// Any other warning, with its text minus the "GC Warning: " prefix.
+    Other(&'a str),
+}
+
+impl<'a> GcWarning<'a> {
+    /// Classify a formatted warning, as passed to a [`with_warn_handler`]
+    /// handler.
+    pub fn classify(msg: &'a str) -> Self {
+        let msg = msg.strip_prefix("GC Warning: ").unwrap_or(msg);
+        if msg.starts_with("Repeated allocation of very large block") {
+            GcWarning::RepeatedLargeBlock
+        } else if msg.starts_with("Out of Memory!") || msg.starts_with("Failed to expand heap") {
+            GcWarning::OutOfMemory
+        } else {
+            GcWarning::Other(msg)
+        }
+    }
+}
+
+/// Send every collector warning, classified, to `f` for the rest of the
+/// process, e.g. to count [`GcWarning::RepeatedLargeBlock`]s in a metric.
+///
+/// `f` also receives the full formatted message. The same restrictions as
+/// for [`with_warn_handler`] apply: it may be called with the allocator lock
+/// held, so it must not allocate from the GC heap. Within a
+/// `with_warn_handler` scope both handlers are called.
+pub fn set_classified_warn_handler(f: fn(GcWarning<'_>, &str)) {
+    CLASSIFIED_HANDLER.store(f as usize, Ordering::Release);
+    install();
+}
+
+/// Route collector warnings to `f` while `op` runs, then restore whatever
+/// handled them before (the collector's default printer, a previously
+/// installed C warn proc, or an enclosing `with_warn_handler`).
+///
+/// `f` receives the formatted message without its trailing newline. It may be
+/// called with the allocator lock held, so it must not allocate from the GC
+/// heap. Handlers are process-wide: warnings raised by other threads during
+/// `op` are routed to `f` too.
+pub fn with_warn_handler<R>(f: fn(&str), op: impl FnOnce() -> R) -> R {
+    struct Restore(usize);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            HANDLER.store(self.0, Ordering::Release);
+        }
+    }
+
+    install();
+    let _restore = Restore(HANDLER.swap(f as usize, Ordering::AcqRel));
+    op()
+}
+
+/// Keep the `n` most recent collector warnings (at most
+/// [`MAX_BUFFERED_WARNINGS`]) for [`recent_warnings`], e.g. for a status
+/// page in a program without a logger. Zero, the default, stops keeping
+/// them. Changing the capacity discards the warnings kept so far.
+///
+/// Buffering works alongside the handlers of [`with_warn_handler`] and
+/// [`set_classified_warn_handler`]. While it is enabled, the collector's
+/// default printer is not called; messages longer than 256 bytes are kept
+/// truncated.
+pub fn set_warning_buffer_capacity(n: usize) {
+    let n = n.min(MAX_BUFFERED_WARNINGS);
+    lock_buffer();
+    let warnings = &raw mut WARNINGS;
+    unsafe {
+        (*warnings).capacity = n;
+        (*warnings).recorded = 0;
+    }
+    BUFFER_CAPACITY.store(n, Ordering::Relaxed);
+    unlock_buffer();
+    if n != 0 {
+        install();
+    }
+}
+
+/// Call `f` with each buffered warning, oldest first (see
+/// [`set_warning_buffer_capacity`]). Returns the number of warnings passed.
+///
+/// Each warning is copied out under a lock and `f` is called without it, so
+/// `f` may allocate; a warning recorded meanwhile may push out one not yet
+/// visited, which is then skipped.
+pub fn recent_warnings(mut f: impl FnMut(&str)) -> usize {
+    let mut buf = Buffer { bytes: [0; MAX_WARNING_LEN], len: 0 };
+    let mut next = None;
+    let mut passed = 0;
+    loop {
+        lock_buffer();
+        let warnings = &raw const WARNINGS;
+        let warnings = unsafe { &*warnings };
+        let oldest = warnings.recorded - warnings.recorded.min(warnings.capacity);
+        let i = next.map_or(oldest, |i: usize| i.max(oldest));
+        if i >= warnings.recorded {
+            unlock_buffer();
+            return passed;
+        }
+        let (bytes, len) = &warnings.slots[i % warnings.capacity];
+        buf.len = 0;
+        buf.push_bytes(&bytes[..*len]);
+        unlock_buffer();
+
+        f(buf.as_str());
+        passed += 1;
+        next = Some(i + 1);
+    }
+}
+
+fn record_warning(msg: &str) {
+    lock_buffer();
+    let warnings = &raw mut WARNINGS;
+    let warnings = unsafe { &mut *warnings };
+    if warnings.capacity != 0 {
+        let (bytes, len) = &mut warnings.slots[warnings.recorded % warnings.capacity];
+        *len = msg.len().min(BUFFERED_WARNING_LEN);
+        bytes[..*len].copy_from_slice(&msg.as_bytes()[..*len]);
+        warnings.recorded += 1;
+    }
+    unlock_buffer();
+}
+
+// Route the collector's warnings through `warn_trampoline` if that is not done
+// yet. Warnings no Rust handler or buffer takes go on to the warn proc that
+// was installed before.
+fn install() {
+    if INSTALLED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    unsafe {
+        PREVIOUS.store(crate::GC_get_warn_proc() as usize, Ordering::Release);
+        crate::GC_set_warn_proc(warn_trampoline);
+    }
+}
+
+fn lock_buffer() {
+    while BUFFER_LOCKED
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+}
+
+fn unlock_buffer() {
+    BUFFER_LOCKED.store(false, Ordering::Release);
+}
+
+unsafe extern "C" fn warn_trampoline(msg: *const c_char, arg: usize) {
+    let handler = HANDLER.load(Ordering::Acquire);
+    let classified = CLASSIFIED_HANDLER.load(Ordering::Acquire);
+    let buffered = BUFFER_CAPACITY.load(Ordering::Relaxed) != 0;
+    if handler == 0 && classified == 0 && !buffered {
+        let previous = PREVIOUS.load(Ordering::Acquire);
+        if previous != 0 {
+            let previous: unsafe extern "C" fn(*const c_char, usize) =
+                unsafe { mem::transmute(previous) };
+            unsafe { previous(msg, arg) };
+        }
+        return;
+    }
+    let mut buf = Buffer { bytes: [0; MAX_WARNING_LEN], len: 0 };
+    unsafe { format_warning(&mut buf, CStr::from_ptr(msg).to_bytes(), arg) };
+    let msg = buf.as_str().trim_end_matches('\n');
+    if buffered {
+        record_warning(msg);
+    }
+    if handler != 0 {
+        let handler: fn(&str) = unsafe { mem::transmute(handler) };
+        handler(msg);
+    }
+    if classified != 0 {
+        let classified: fn(GcWarning<'_>, &str) = unsafe { mem::transmute(classified) };
+        classified(GcWarning::classify(msg), msg);
+    }
+}
+
+/// Expand the single `printf` conversion BDWGC warnings may contain: one of
+/// `%s`, `%p`, `%ld` or `%lu` (see `WARN` in gc_priv.h).
+unsafe fn format_warning(out: &mut Buffer, msg: &[u8], arg: usize) {
+    let mut i = 0;
+    while i < msg.len() {
+        if msg[i] != b'%' {
+            let end = msg[i..].iter().position(|&b| b == b'%').map_or(msg.len(), |p| i + p);
+            out.push_bytes(&msg[i..end]);
+            i = end;
+            continue;
+        }
+        let mut j = i + 1;
+        while j < msg.len() && msg[j] == b'l' {
+            j += 1;
+        }
+        let _ = match msg.get(j) {
+            Some(b'%') => out.write_char('%'),
+            Some(b'd') | Some(b'i') => write!(out, "{}", arg as isize),
+            Some(b'u') => write!(out, "{arg}"),
+            Some(b'x') => write!(out, "{arg:x}"),
+            Some(b'p') => write!(out, "{:p}", arg as *const u8),
+            Some(b's') if arg != 0 => {
+                out.push_bytes(unsafe { CStr::from_ptr(arg as *const c_char) }.to_bytes());
+                Ok(())
+            }
+            Some(b's') => out.write_str("(null)"),
+            _ => {
+                out.push_bytes(&msg[i..(j + 1).min(msg.len())]);
+                Ok(())
+            }
+        };
+        i = j + 1;
+    }
+}
+
+struct Buffer {
+    bytes: [u8; MAX_WARNING_LEN],
+    len: usize,
+}
+
+impl Buffer {
+    fn push_bytes(&mut self, bytes: &[u8]) {
+        let n = bytes.len().min(self.bytes.len() - self.len);
+        self.bytes[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+        self.len += n;
+    }
+
+    fn as_str(&self) -> &str {
+        // Truncation or a non-UTF-8 `%s` argument may leave invalid bytes;
+        // keep the valid prefix.
+        match core::str::from_utf8(&self.bytes[..self.len]) {
+            Ok(s) => s,
+            Err(e) => unsafe { core::str::from_utf8_unchecked(&self.bytes[..e.valid_up_to()]) },
+        }
+    }
+}
+
+impl Write for Buffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_bytes(s.as_bytes());
+        Ok(())
+    }
+}
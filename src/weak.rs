@@ -0,0 +1,166 @@
+//! Weak references to GC objects, and construction of self-referential ones.
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::finalize::DropValue;
+
+/// A reference to a GC object that does not keep it alive.
+///
+/// The handle points to a small pointer-free cell holding a disappearing
+/// link to the object, which the collector clears when the object becomes
+/// unreachable. Copies of the handle share the cell, and so may be stored
+/// anywhere, including inside the object itself, without keeping it alive.
+///
+/// For an object made by [`gc_new_cyclic`], the cell also records a
+/// generation number that is stored in the object after the value, and
+/// [`upgrade`](Self::upgrade) checks that the two still agree. The link is
+/// cleared before the collector can reuse the object's memory, so this only
+/// guards against a stale link ever handing out whatever was allocated at
+/// the same address later.
+pub struct GcWeak<T> {
+    cell: NonNull<Cell>,
+    _target: PhantomData<*const T>,
+}
+
+#[repr(C)]
+struct Cell {
+    // The disappearing link.
+    link: *mut u8,
+    // The generation the object was tagged with, or 0 for none.
+    generation: u64,
+}
+
+// Source of generation numbers for objects made by `gc_new_cyclic`.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+impl<T> Clone for GcWeak<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for GcWeak<T> {}
+
+impl<T> GcWeak<T> {
+    /// Create a weak reference to `obj`. Returns `None` if the allocation,
+    /// or registering the reference with the collector, fails.
+    ///
+    /// # Safety
+    ///
+    /// `obj` must be the base address of a live object allocated by the
+    /// collector.
+    pub unsafe fn new(obj: NonNull<T>) -> Option<Self> {
+        let weak = Self::empty(0)?;
+        let obj = obj.as_ptr() as *mut u8;
+        if !unsafe { weak.register(obj) } {
+            return None;
+        }
+        unsafe { weak.set(obj) };
+        Some(weak)
+    }
+
+    fn empty(generation: u64) -> Option<Self> {
+        let cell = crate::gc_malloc_atomic(mem::size_of::<Cell>())?.cast::<Cell>();
+        unsafe { cell.as_ptr().write(Cell { link: ptr::null_mut(), generation }) };
+        Some(GcWeak { cell, _target: PhantomData })
+    }
+
+    // Have the collector clear the link once `obj` is unreachable. Returns
+    // false if it is out of memory for the registration.
+    unsafe fn register(&self, obj: *mut u8) -> bool {
+        unsafe {
+            let link = &raw mut (*self.cell.as_ptr()).link;
+            // GC_SUCCESS; the cell is new, so never GC_DUPLICATE.
+            crate::GC_general_register_disappearing_link(link, obj) == 0
+        }
+    }
+
+    unsafe fn set(&self, obj: *mut u8) {
+        unsafe { (&raw mut (*self.cell.as_ptr()).link).write(obj) };
+    }
+
+    /// A pointer to the object if it has not been found unreachable (or, for
+    /// one made by [`gc_new_cyclic`], once it is initialised and as long as
+    /// its generation matches). Storing the result where the collector scans
+    /// it keeps the object alive again.
+    pub fn upgrade(&self) -> Option<NonNull<T>> {
+        struct Read {
+            cell: *const Cell,
+            tag_offset: usize,
+        }
+
+        unsafe extern "C" fn read(data: *mut u8) -> *mut u8 {
+            let read = unsafe { &*(data as *const Read) };
+            let cell = unsafe { &*read.cell };
+            if cell.link.is_null() || cell.generation == 0 {
+                return cell.link;
+            }
+            let tag = unsafe { cell.link.add(read.tag_offset).cast::<u64>().read() };
+            if tag == cell.generation {
+                cell.link
+            } else {
+                ptr::null_mut()
+            }
+        }
+
+        let mut read_args = Read { cell: self.cell.as_ptr(), tag_offset: tag_offset::<T>() };
+        // The collector clears the link with the allocator lock held; reading
+        // it under the lock means the object cannot be reclaimed in between.
+        let obj =
+            unsafe { crate::GC_call_with_alloc_lock(read, &mut read_args as *mut Read as *mut u8) };
+        NonNull::new(obj as *mut T)
+    }
+}
+
+// The layout of an object made by `gc_new_cyclic`: the value followed by its
+// generation.
+fn tagged_layout<T>() -> Option<(Layout, usize)> {
+    Layout::new::<T>().extend(Layout::new::<u64>()).ok()
+}
+
+fn tag_offset<T>() -> usize {
+    tagged_layout::<T>().map_or(0, |(_, offset)| offset)
+}
+
+/// Allocate a GC object holding the value returned by `f`, which is given a
+/// weak reference to the object itself, e.g. to store a back-pointer.
+///
+/// The object is zeroed while `f` runs, so the collector never scans
+/// garbage in it, and the weak reference only upgrades once the value has
+/// been written: calling [`GcWeak::upgrade`] from within `f` returns
+/// `None`. BDWGC never moves objects, so the reference stays valid after
+/// construction. The object carries a generation number after the value,
+/// which the weak reference checks (see [`GcWeak`]). If `T` needs dropping,
+/// a finalizer drops it once the object is unreachable. Returns `None`
+/// without calling `f` if an allocation, or registering the weak
+/// reference with the collector, fails.
+pub fn gc_new_cyclic<T>(f: impl FnOnce(GcWeak<T>) -> T) -> Option<NonNull<T>> {
+    // The generation also gives every object an address of its own for the
+    // link to refer to.
+    let (layout, tag_offset) = tagged_layout::<T>()?;
+    let generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+    let weak = GcWeak::empty(generation)?;
+    let obj = NonNull::new(unsafe { crate::gc_malloc(layout) })?;
+    unsafe {
+        ptr::write_bytes(obj.as_ptr(), 0, layout.size());
+        obj.as_ptr().add(tag_offset).cast::<u64>().write(generation);
+        // Registered before `f` runs, so that failing leaves nothing to
+        // drop; the link itself stays null until the value is written.
+        if !weak.register(obj.as_ptr()) {
+            return None;
+        }
+    }
+    let value = f(weak);
+    unsafe {
+        obj.cast::<T>().as_ptr().write(value);
+        if mem::needs_drop::<T>() {
+            crate::finalize::drop_when_unreachable::<DropValue<T>>(obj.as_ptr(), ptr::null_mut());
+        }
+        weak.set(obj.as_ptr());
+    }
+    Some(obj.cast())
+}
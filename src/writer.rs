@@ -0,0 +1,98 @@
+//! Growable byte buffers in pointer-free GC memory.
+
+use core::alloc::AllocError;
+use core::fmt;
+use core::ptr::{self, NonNull};
+
+/// An append-only byte buffer backed by pointer-free ("atomic") GC memory,
+/// for building serialized output directly in collectable memory.
+///
+/// The buffer grows with `GC_realloc`, which keeps it pointer-free, so the
+/// collector never scans its contents. It is kept alive by the `GcWriter`
+/// itself, which must therefore be stored somewhere the collector scans (the
+/// stack, or GC memory). Text can be written through [`fmt::Write`]; `std`
+/// users can forward `io::Write` to [`write_all`](GcWriter::write_all).
+pub struct GcWriter {
+    ptr: *mut u8,
+    len: usize,
+    capacity: usize,
+}
+
+impl GcWriter {
+    pub const fn new() -> Self {
+        GcWriter { ptr: ptr::null_mut(), len: 0, capacity: 0 }
+    }
+
+    /// Create a writer with room for `capacity` bytes. Returns `None` if the
+    /// allocation fails.
+    pub fn with_capacity(capacity: usize) -> Option<Self> {
+        let mut writer = Self::new();
+        writer.reserve(capacity).ok()?;
+        Some(writer)
+    }
+
+    /// Append `bytes`, growing the buffer as needed. On failure nothing is
+    /// appended.
+    pub fn write_all(&mut self, bytes: &[u8]) -> Result<(), AllocError> {
+        self.reserve(bytes.len())?;
+        unsafe { self.ptr.add(self.len).copy_from_nonoverlapping(bytes.as_ptr(), bytes.len()) };
+        self.len += bytes.len();
+        Ok(())
+    }
+
+    /// Make room for at least `additional` more bytes.
+    pub fn reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        let needed = self.len.checked_add(additional).ok_or(AllocError)?;
+        if needed <= self.capacity {
+            return Ok(());
+        }
+        let capacity = needed.max(self.capacity.saturating_mul(2)).max(64);
+        let new = unsafe {
+            if self.ptr.is_null() {
+                crate::GC_malloc_atomic(capacity)
+            } else {
+                crate::GC_realloc(self.ptr, capacity)
+            }
+        };
+        if new.is_null() {
+            return Err(AllocError);
+        }
+        self.ptr = new;
+        self.capacity = capacity;
+        Ok(())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        if self.ptr.is_null() {
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Give up the writer and return the bytes written as a GC object. The
+    /// object is reclaimed once no pointer into it remains.
+    pub fn into_gc_bytes(self) -> NonNull<[u8]> {
+        let ptr = NonNull::new(self.ptr).unwrap_or(NonNull::dangling());
+        NonNull::slice_from_raw_parts(ptr, self.len)
+    }
+}
+
+impl Default for GcWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Write for GcWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
@@ -0,0 +1,68 @@
+#![cfg(all(target_os = "linux", feature = "parallel-mark"))]
+
+// Not `mod common`, as in markers.rs.
+#[used]
+#[link_section = ".init_array"]
+static INIT: extern "C" fn() = {
+    extern "C" fn init() {
+        // Two markers besides the thread that starts a collection, however
+        // many cores there are.
+        std::env::set_var("GC_MARKERS", "3");
+        unsafe { bmalloc::GC_init() };
+    }
+    init
+};
+
+// Not a CPU of any machine these tests run on.
+const ABSENT_CPU: usize = 1023;
+
+fn affinity(tid: libc::pid_t) -> Vec<usize> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    assert_eq!(unsafe { libc::sched_getaffinity(tid, std::mem::size_of_val(&set), &mut set) }, 0);
+    (0..libc::CPU_SETSIZE as usize).filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) }).collect()
+}
+
+// The CPUs each marker may run on, by marker number.
+fn marker_affinities() -> Vec<Vec<usize>> {
+    let mut markers = Vec::new();
+    for entry in std::fs::read_dir("/proc/self/task").unwrap() {
+        let path = entry.unwrap().path();
+        let comm = std::fs::read_to_string(path.join("comm")).unwrap();
+        if let Some(n) = comm.trim_end().strip_prefix("GC-marker-") {
+            let tid = path.file_name().unwrap().to_str().unwrap().parse().unwrap();
+            markers.push((n.parse::<usize>().unwrap(), affinity(tid)));
+        }
+    }
+    markers.sort();
+    markers.into_iter().map(|(_, cpus)| cpus).collect()
+}
+
+#[test]
+fn markers_are_pinned_round_robin() {
+    let allowed = affinity(0);
+    assert!(!allowed.contains(&ABSENT_CPU));
+    assert_eq!(bmalloc::set_marker_affinity(&[]), 0);
+
+    // Starts the markers first.
+    let cpu = allowed[0];
+    assert_eq!(bmalloc::set_marker_affinity(&[cpu]), 2);
+    assert_eq!(bmalloc::marker_thread_count(), 2);
+    assert_eq!(marker_affinities(), [[cpu], [cpu]]);
+
+    // Marker 1 gets the second CPU, which the kernel refuses; marker 0 is
+    // pinned again.
+    assert_eq!(bmalloc::set_marker_affinity(&[cpu, ABSENT_CPU]), 1);
+    assert_eq!(marker_affinities(), [[cpu], [cpu]]);
+    // Beyond what `cpu_set_t` holds: skipped.
+    assert_eq!(bmalloc::set_marker_affinity(&[libc::CPU_SETSIZE as usize]), 0);
+
+    if let [_, second, ..] = allowed[..] {
+        assert_eq!(bmalloc::set_marker_affinity(&[cpu, second]), 2);
+        assert_eq!(marker_affinities(), [[cpu], [second]]);
+    }
+    // Collections still complete with the markers pinned.
+    assert!(bmalloc::ensure_thread_registered());
+    let before = bmalloc::gc_count();
+    bmalloc::collect();
+    assert_eq!(bmalloc::gc_count(), before + 1);
+}
@@ -0,0 +1,70 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::sync::Mutex;
+
+use bmalloc::{Alert, AlertMetric, AlertMonitor};
+
+extern "C" {
+    fn GC_gcollect();
+}
+
+const CHUNKS: usize = 256;
+const CHUNK: usize = 256 << 10;
+const THRESHOLD: f64 = 20.0;
+
+static ALERTS: Mutex<Vec<Alert>> = Mutex::new(Vec::new());
+
+fn record(alert: Alert) {
+    ALERTS.lock().unwrap_or_else(|e| e.into_inner()).push(alert);
+}
+
+fn take_alerts() -> Vec<Alert> {
+    std::mem::take(&mut *ALERTS.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+fn free_percent() -> f64 {
+    let stats = bmalloc::get_prof_stats().unwrap();
+    stats.free_bytes_full as f64 * 100.0 / stats.heapsize_full as f64
+}
+
+#[test]
+fn free_ratio_alert_is_raised_and_cleared() {
+    common::setup();
+    // Collections only happen where the test asks for them.
+    bmalloc::set_automatic_collection(false);
+    bmalloc::collect();
+    AlertMonitor::new(record).rule(AlertMetric::FreePercent, THRESHOLD).install();
+    bmalloc::collect();
+    assert_eq!(take_alerts(), [], "free: {:.1}%", free_percent());
+
+    // 64 MiB of live data: the heap grows by little more than that.
+    let holder = bmalloc::gc_slice_filled(0usize, CHUNKS).unwrap();
+    for slot in unsafe { &mut *holder.as_ptr() } {
+        *slot = bmalloc::gc_malloc_atomic(CHUNK).unwrap().as_ptr() as usize;
+    }
+    // An allocation evaluates the rules, but only queues the alert.
+    unsafe { GC_gcollect() };
+    std::hint::black_box(bmalloc::gc_try_new(0u64).unwrap());
+    assert_eq!(take_alerts(), []);
+    bmalloc::run_pending_gc_hooks();
+    let alerts = take_alerts();
+    assert_eq!(alerts.len(), 1, "free: {:.1}%", free_percent());
+    assert_eq!((alerts[0].metric, alerts[0].threshold), (AlertMetric::FreePercent, THRESHOLD));
+    assert!(alerts[0].raised && alerts[0].value < THRESHOLD, "{alerts:?}");
+    // Still low: raised once only.
+    bmalloc::collect();
+    assert_eq!(take_alerts(), []);
+
+    // Released, the data leaves the heap mostly free.
+    unsafe { std::ptr::write_bytes(holder.as_ptr() as *mut usize, 0, CHUNKS) };
+    bmalloc::clear_stack();
+    bmalloc::collect();
+    let alerts = take_alerts();
+    assert_eq!(alerts.len(), 1, "free: {:.1}%", free_percent());
+    assert!(!alerts[0].raised && alerts[0].value > THRESHOLD * 1.1, "{alerts:?}");
+
+    AlertMonitor::uninstall();
+    std::hint::black_box(holder);
+}
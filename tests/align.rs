@@ -0,0 +1,40 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::alloc::{GlobalAlloc, Layout};
+
+use bmalloc::GcAllocator;
+
+#[test]
+fn every_threshold_keeps_allocations_aligned() {
+    common::setup();
+    for threshold in [1, 2, 4, 8, 16] {
+        bmalloc::set_min_align_threshold(threshold);
+        assert_eq!(bmalloc::min_align_threshold(), threshold);
+        for align in [1, 2, 4, 8, 16, 32, 64, 4096] {
+            for size in [1, 7, 8, 24, 100, 4000, 70000] {
+                let layout = Layout::from_size_align(size, align).unwrap();
+                let ptr = unsafe { GcAllocator.alloc(layout) };
+                assert!(!ptr.is_null());
+                assert_eq!(ptr as usize % align, 0, "{size} bytes at {align} under {threshold}");
+
+                let ptr = unsafe { GcAllocator.realloc(ptr, layout, size * 2) };
+                assert_eq!(ptr as usize % align, 0, "{size} bytes grown at {align}");
+            }
+        }
+    }
+    bmalloc::set_min_align_threshold(bmalloc::MIN_ALIGN);
+}
+
+#[test]
+#[should_panic(expected = "power of two")]
+fn threshold_must_be_a_power_of_two() {
+    bmalloc::set_min_align_threshold(12);
+}
+
+#[test]
+#[should_panic(expected = "only guarantees")]
+fn threshold_cannot_exceed_the_gc_malloc_guarantee() {
+    bmalloc::set_min_align_threshold(2 * bmalloc::GC_MALLOC_ALIGN);
+}
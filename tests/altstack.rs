@@ -0,0 +1,65 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::time::Duration;
+
+use bmalloc::testing::{assert_retained, CollectionProbe};
+
+const ALTSTACK_SIZE: usize = 256 * 1024;
+
+static PROBE: AtomicPtr<CollectionProbe> = AtomicPtr::new(ptr::null_mut());
+static IN_HANDLER: AtomicBool = AtomicBool::new(false);
+static RELEASE: AtomicBool = AtomicBool::new(false);
+
+// Runs on the alternate stack. The object it allocates is referenced only
+// from this frame until the handler returns.
+extern "C" fn handler(_: libc::c_int) {
+    let obj = bmalloc::gc_try_new([0x5a5a_5a5a_usize; 8]).unwrap();
+    let probe = Box::new(unsafe { CollectionProbe::for_ptr(obj.as_ptr() as *const u8) });
+    PROBE.store(Box::into_raw(probe), Ordering::Release);
+    IN_HANDLER.store(true, Ordering::Release);
+    while !RELEASE.load(Ordering::Acquire) {
+        std::thread::yield_now();
+    }
+    assert_eq!(unsafe { *black_box(obj).as_ptr() }, [0x5a5a_5a5a; 8]);
+}
+
+#[test]
+fn object_held_by_a_handler_on_the_altstack_survives_collection() {
+    common::setup();
+    std::thread::scope(|s| {
+        let worker = s.spawn(|| unsafe {
+            common::setup();
+            let mem = libc::mmap(
+                ptr::null_mut(),
+                ALTSTACK_SIZE,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            assert_ne!(mem, libc::MAP_FAILED);
+            let alt = libc::stack_t { ss_sp: mem, ss_flags: 0, ss_size: ALTSTACK_SIZE };
+            assert_eq!(libc::sigaltstack(&alt, ptr::null_mut()), 0);
+            assert!(bmalloc::register_altstack());
+
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handler as usize;
+            action.sa_flags = libc::SA_ONSTACK;
+            assert_eq!(libc::sigaction(libc::SIGUSR1, &action, ptr::null_mut()), 0);
+            libc::raise(libc::SIGUSR1);
+        });
+
+        while !IN_HANDLER.load(Ordering::Acquire) {
+            std::thread::yield_now();
+        }
+        let probe = unsafe { Box::from_raw(PROBE.load(Ordering::Acquire)) };
+        assert_retained(&probe, Duration::from_millis(50));
+        RELEASE.store(true, Ordering::Release);
+        worker.join().unwrap();
+    });
+}
@@ -0,0 +1,52 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use bmalloc::GcAny;
+
+#[derive(Debug, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[test]
+fn downcasts_match_the_stored_type_across_collections() {
+    common::setup();
+    let objects = [
+        GcAny::new(42u64).unwrap(),
+        GcAny::new(String::from("forty-two")).unwrap(),
+        GcAny::new(Point { x: 4, y: 2 }).unwrap(),
+        GcAny::new([1u8, 2, 3]).unwrap(),
+    ];
+    for _ in 0..3 {
+        bmalloc::collect();
+    }
+
+    assert_eq!(unsafe { *objects[0].downcast::<u64>().unwrap().as_ref() }, 42);
+    assert_eq!(unsafe { objects[1].downcast::<String>().unwrap().as_ref() }, "forty-two");
+    assert_eq!(unsafe { objects[2].downcast::<Point>().unwrap().as_ref() }, &Point { x: 4, y: 2 });
+    assert_eq!(unsafe { objects[3].downcast::<[u8; 3]>().unwrap().as_ref() }, &[1, 2, 3]);
+
+    // Wrong types, including ones with the same layout.
+    assert!(objects[0].downcast::<i64>().is_none());
+    assert!(objects[1].downcast::<&str>().is_none());
+    assert!(objects[2].downcast::<(i32, i32)>().is_none());
+    assert!(objects[3].downcast::<[u8; 4]>().is_none());
+    for object in objects {
+        assert_eq!(object.is::<u64>(), object == objects[0]);
+    }
+}
+
+#[test]
+fn from_ptr_only_accepts_object_bases() {
+    common::setup();
+    let object = GcAny::new(7u32).unwrap();
+    let same = unsafe { GcAny::from_ptr(object.as_ptr()) }.unwrap();
+    assert_eq!(same, object);
+    assert_eq!(unsafe { *same.downcast::<u32>().unwrap().as_ref() }, 7);
+
+    assert!(unsafe { GcAny::from_ptr(object.as_ptr().add(1)) }.is_none());
+    let not_gc = 0u64;
+    assert!(unsafe { GcAny::from_ptr(&not_gc as *const u64 as *const u8) }.is_none());
+}
@@ -0,0 +1,48 @@
+#![cfg(all(target_os = "linux", feature = "allocator-api2"))]
+
+mod common;
+
+use std::hint::black_box;
+use std::time::Duration;
+
+use allocator_api2::vec::Vec;
+use bmalloc::testing::{assert_retained, CollectionProbe};
+use bmalloc::{AtomicGcAllocator, GcAllocator};
+
+fn grows_and_survives<A: allocator_api2::alloc::Allocator>(alloc: A) {
+    let mut v = Vec::new_in(alloc);
+    let mut buffers = 0;
+    let mut last = v.as_ptr();
+    for i in 0..100_000u64 {
+        v.push(i);
+        if v.as_ptr() != last {
+            buffers += 1;
+            last = v.as_ptr();
+        }
+    }
+    assert!(buffers > 1, "never grew");
+    assert!(bmalloc::is_gc_heap_ptr(v.as_ptr()));
+    let probe = unsafe { CollectionProbe::for_ptr(v.as_ptr() as *const u8) };
+    assert_retained(&probe, Duration::from_millis(50));
+    for _ in 0..64 {
+        black_box(bmalloc::gc_try_new([u64::MAX; 64]).unwrap());
+    }
+    assert!(v.iter().copied().eq(0..100_000));
+
+    v.truncate(10);
+    v.shrink_to_fit();
+    bmalloc::collect();
+    assert_eq!(v, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+}
+
+#[test]
+fn gc_allocator_vec_grows_and_survives_collection() {
+    common::setup();
+    grows_and_survives(GcAllocator);
+}
+
+#[test]
+fn atomic_allocator_vec_grows_and_survives_collection() {
+    common::setup();
+    grows_and_survives(AtomicGcAllocator);
+}
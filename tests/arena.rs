@@ -0,0 +1,61 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+const CAPACITY: usize = 16;
+const ELEMENTS: usize = 12;
+
+static DROPS: [AtomicUsize; CAPACITY] = [const { AtomicUsize::new(0) }; CAPACITY];
+
+fn total_drops() -> usize {
+    DROPS.iter().map(|d| d.load(Ordering::Relaxed)).sum()
+}
+
+struct Tracked(usize);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        DROPS[self.0].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn every_element_is_dropped_once_when_the_arena_is_collected() {
+    common::setup();
+    common::isolated(|| {
+        let mut arena = bmalloc::GcArena::with_capacity(CAPACITY).unwrap();
+        for i in 0..ELEMENTS {
+            assert_eq!(unsafe { arena.alloc(Tracked(i)).ok().unwrap().as_ref().0 }, i);
+        }
+        assert_eq!(arena.len(), ELEMENTS);
+        bmalloc::collect();
+        assert_eq!(total_drops(), 0);
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while total_drops() < ELEMENTS {
+        assert!(Instant::now() < deadline, "{} dropped", total_drops());
+        bmalloc::clear_stack();
+        bmalloc::collect();
+        bmalloc::invoke_finalizers();
+    }
+    for _ in 0..3 {
+        bmalloc::collect();
+        bmalloc::invoke_finalizers();
+    }
+    for (i, drops) in DROPS.iter().enumerate() {
+        assert_eq!(drops.load(Ordering::Relaxed), (i < ELEMENTS) as usize, "element {i}");
+    }
+}
+
+#[test]
+fn full_arena_hands_the_value_back() {
+    common::setup();
+    let mut arena = bmalloc::GcArena::with_capacity(1).unwrap();
+    assert!(arena.alloc(1u32).is_ok());
+    assert_eq!(arena.alloc(2u32), Err(2));
+    assert_eq!(arena.len(), arena.capacity());
+}
@@ -0,0 +1,89 @@
+#![cfg(target_os = "linux")]
+
+// Not `mod common`: the collector's own fork handling has to be turned off
+// before it is initialised, so that only the handlers installed by
+// `install_atfork_handlers` prepare it for a fork.
+#[used]
+#[link_section = ".init_array"]
+static INIT: extern "C" fn() = {
+    extern "C" fn init() {
+        bmalloc::set_handle_fork(false);
+        if cfg!(feature = "parallel-mark") {
+            std::env::set_var("GC_MARKERS", "3");
+        }
+        bmalloc::init();
+    }
+    init
+};
+
+use std::sync::Barrier;
+use std::time::{Duration, Instant};
+
+// Runs in the child: only plain checks, no panicking or printing, as the
+// other threads of the test harness are gone. Returns the exit status.
+fn child() -> i32 {
+    if !bmalloc::after_fork_child() || !bmalloc::thread_is_registered() {
+        return 1;
+    }
+    let Ok(obj) = bmalloc::gc_try_new([7u64; 16]) else {
+        return 2;
+    };
+    let before = bmalloc::gc_count();
+    bmalloc::collect();
+    if bmalloc::gc_count() == before || unsafe { *obj.as_ptr() } != [7; 16] {
+        return 3;
+    }
+    0
+}
+
+// The child's exit status. A child that hangs, as one whose collector still
+// waits for the parent's other threads does, is killed.
+fn wait_for(pid: libc::pid_t) -> i32 {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut status = 0;
+    while unsafe { libc::waitpid(pid, &mut status, libc::WNOHANG) } == 0 {
+        if Instant::now() > deadline {
+            unsafe { libc::kill(pid, libc::SIGKILL) };
+            unsafe { libc::waitpid(pid, &mut status, 0) };
+            panic!("the child hung");
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    status
+}
+
+#[test]
+fn raw_fork_with_the_handlers_installed() {
+    assert!(bmalloc::ensure_thread_registered());
+    assert!(bmalloc::install_atfork_handlers());
+    // Installing again is a no-op.
+    assert!(bmalloc::install_atfork_handlers());
+    let obj = bmalloc::gc_try_new(1u64).unwrap();
+
+    // Another registered thread, alive at the fork but not in the child.
+    let parked = Barrier::new(2);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            assert!(bmalloc::ensure_thread_registered());
+            parked.wait();
+            parked.wait();
+        });
+        parked.wait();
+        match unsafe { libc::fork() } {
+            -1 => panic!("fork failed"),
+            0 => unsafe { libc::_exit(child()) },
+            pid => {
+                let status = wait_for(pid);
+                assert!(libc::WIFEXITED(status), "child status {status:#x}");
+                assert_eq!(libc::WEXITSTATUS(status), 0);
+            }
+        }
+        // The parent carries on with both threads.
+        let before = bmalloc::gc_count();
+        bmalloc::collect();
+        assert_eq!(bmalloc::gc_count(), before + 1);
+        parked.wait();
+    });
+    assert_eq!(unsafe { *obj.as_ptr() }, 1);
+    assert_eq!(unsafe { *bmalloc::gc_try_new(2u64).unwrap().as_ptr() }, 2);
+}
@@ -0,0 +1,45 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+
+const LIVE_CHUNKS: usize = 256;
+const CHUNK: usize = 32 << 10;
+const TARGET: f32 = 2.0;
+const COLLECTIONS: u64 = 40;
+
+fn overhead_ratio() -> f32 {
+    bmalloc::clear_stack();
+    bmalloc::collect();
+    let usage = bmalloc::heap_usage();
+    usage.heap_size as f32 / (usage.heap_size - usage.free_bytes) as f32
+}
+
+#[test]
+fn heap_overhead_converges_on_the_target() {
+    common::setup();
+    // A steady live set, kept in scanned GC memory.
+    let live = bmalloc::gc_slice_filled(0usize, LIVE_CHUNKS).unwrap();
+    for slot in unsafe { &mut *live.as_ptr() } {
+        *slot = bmalloc::gc_malloc_atomic(CHUNK).unwrap().as_ptr() as usize;
+    }
+    let start_divisor = bmalloc::free_space_divisor();
+    let start_ratio = overhead_ratio();
+
+    // Tuning steers the collections allocation triggers, which the
+    // deterministic-test feature turns off.
+    bmalloc::set_automatic_collection(true);
+    bmalloc::auto_tune_overhead(TARGET);
+    let end = bmalloc::gc_count() + COLLECTIONS;
+    while bmalloc::gc_count() < end {
+        black_box(bmalloc::gc_try_new([0u64; 512]).unwrap());
+    }
+    bmalloc::auto_tune_overhead(0.0);
+    let ratio = overhead_ratio();
+    assert!((ratio - TARGET).abs() / TARGET < 0.25, "ratio {ratio} (from {start_ratio})");
+    // Once the heap has overshot, a higher divisor cannot shrink it.
+    let divisor = bmalloc::free_space_divisor();
+    assert!(divisor <= start_divisor, "divisor ran up from {start_divisor} to {divisor}");
+    black_box(live);
+}
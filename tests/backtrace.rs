@@ -0,0 +1,57 @@
+#![cfg(all(target_os = "linux", feature = "backtrace"))]
+#![feature(allocator_api)]
+
+mod common;
+
+use std::alloc::{Allocator, GlobalAlloc, Layout};
+use std::hint::black_box;
+
+use bmalloc::GcAllocator;
+
+// Small enough that a return address within this many bytes of its start
+// lies inside it.
+const MAX_FN_LEN: usize = 512;
+
+#[inline(never)]
+fn allocate_here() -> *mut u8 {
+    GcAllocator.allocate(Layout::new::<[usize; 4]>()).unwrap().as_ptr() as *mut u8
+}
+
+#[test]
+fn backtrace_contains_the_allocating_function() {
+    common::setup();
+    let obj = allocate_here();
+    let trace = bmalloc::allocation_backtrace(obj).expect("no backtrace recorded");
+    let start = allocate_here as usize;
+    assert!(
+        trace.frames().iter().any(|&ip| ip > start && ip - start < MAX_FN_LEN),
+        "{trace:?} does not include allocate_here at {start:#x}"
+    );
+    // Interior pointers find the same trace.
+    let inner = bmalloc::allocation_backtrace(unsafe { obj.add(8) }).unwrap();
+    assert_eq!(inner.frames(), trace.frames());
+    black_box(obj);
+}
+
+#[test]
+fn foreign_pointers_have_no_backtrace() {
+    common::setup();
+    let on_stack = 0usize;
+    assert!(bmalloc::allocation_backtrace(&on_stack).is_none());
+}
+
+#[test]
+fn collection_after_freeing_large_objects() {
+    common::setup();
+    // Over-aligned, so each reallocation allocates anew and frees the old
+    // object, whose block the collector may merge with its free neighbours.
+    let mut layout = Layout::from_size_align(70_000, 4096).unwrap();
+    let mut ptr = unsafe { GcAllocator.alloc(layout) };
+    for _ in 0..8 {
+        ptr = unsafe { GcAllocator.realloc(ptr, layout, layout.size() * 2) };
+        layout = Layout::from_size_align(layout.size() * 2, 4096).unwrap();
+        bmalloc::collect();
+    }
+    assert!(bmalloc::allocation_backtrace(ptr).is_some());
+    black_box(ptr);
+}
@@ -0,0 +1,82 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use bmalloc::testing::CollectionProbe;
+
+extern "C" {
+    fn GC_set_manual_vdb_allowed(value: i32);
+}
+
+struct Node {
+    value: u64,
+}
+
+// Larger than `Node`, so that allocating young nodes never touches the old
+// objects' pages.
+struct Old {
+    young: *mut Node,
+    _padding: [u64; 31],
+}
+
+// Roots the old objects; statics are scanned by every collection.
+static OLD: [AtomicPtr<Old>; 2] =
+    [AtomicPtr::new(ptr::null_mut()), AtomicPtr::new(ptr::null_mut())];
+
+// Run partial collections only: marks left by the previous collection are
+// kept, and only roots and pages recorded as dirty are scanned again.
+fn partial_collections(rounds: usize) {
+    for _ in 0..rounds {
+        bmalloc::clear_stack();
+        unsafe {
+            bmalloc::GC_start_incremental_collection();
+            while bmalloc::GC_collect_a_little() != 0 {}
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Store(fn(&mut *mut Node, *mut Node));
+
+fn young_after_partial_collections(slot: usize, store: Store) -> bool {
+    common::isolated(move || {
+        let old = bmalloc::gc_try_new(Old { young: ptr::null_mut(), _padding: [0; 31] })
+            .unwrap()
+            .as_ptr();
+        OLD[slot].store(old, Ordering::Relaxed);
+    });
+    // A full collection marks the old object and clears the dirty bits.
+    common::isolated(bmalloc::collect);
+
+    let probe = common::isolated(move || {
+        let young = bmalloc::gc_try_new(Node { value: 2 }).unwrap().as_ptr();
+        let old = OLD[slot].load(Ordering::Relaxed);
+        (store.0)(unsafe { &mut (*old).young }, young);
+        SendProbe(unsafe { CollectionProbe::for_ptr(young as *const u8) })
+    });
+    common::isolated(|| partial_collections(4));
+    !probe.0.is_collected()
+}
+
+struct SendProbe(CollectionProbe);
+
+unsafe impl Send for SendProbe {}
+
+#[test]
+fn young_object_stored_through_the_barrier_survives_partial_collections() {
+    unsafe { GC_set_manual_vdb_allowed(1) };
+    common::setup();
+    bmalloc::enable_incremental();
+    assert!(bmalloc::is_incremental());
+    unsafe { bmalloc::GC_set_full_freq(1000) };
+
+    assert!(young_after_partial_collections(0, Store(bmalloc::gc_store)));
+    let old = OLD[0].load(Ordering::Relaxed);
+    assert_eq!(unsafe { (*(*old).young).value }, 2);
+
+    // A plain store goes unnoticed by the partial collections.
+    assert!(!young_after_partial_collections(1, Store(|field, value| *field = value)));
+}
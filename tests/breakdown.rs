@@ -0,0 +1,44 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+const CHUNKS: usize = 64;
+const CHUNK: usize = 1 << 20;
+
+#[test]
+fn breakdown_accounts_for_a_touched_heap() {
+    common::setup();
+    bmalloc::collect();
+    let before = bmalloc::memory_breakdown();
+    assert!(before.os_rss > 0);
+    assert!(bmalloc::memory_use() <= before.gc_heap);
+    if bmalloc::capabilities().obtained_from_os_bytes {
+        // Block headers at least.
+        assert!(before.gc_overhead > 0);
+    }
+
+    // 64 MiB of live data, written to so that it is resident.
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    let holder = bmalloc::gc_slice_filled(0usize, CHUNKS).unwrap();
+    for slot in unsafe { &mut *holder.as_ptr() } {
+        let chunk = bmalloc::gc_malloc_atomic(CHUNK).unwrap().as_ptr();
+        for offset in (0..CHUNK).step_by(page) {
+            unsafe { chunk.add(offset).write_volatile(1) };
+        }
+        *slot = chunk as usize;
+    }
+
+    let after = bmalloc::memory_breakdown();
+    let usage = bmalloc::heap_usage();
+    assert_eq!((after.gc_heap, after.gc_unmapped), (usage.heap_size, usage.unmapped_bytes));
+    assert!(bmalloc::memory_use() <= after.gc_heap);
+    assert!(after.gc_heap >= before.gc_heap + CHUNKS * CHUNK);
+    assert!(
+        after.os_rss >= before.os_rss + CHUNKS * CHUNK,
+        "RSS {} -> {} after touching {} MiB",
+        before.os_rss,
+        after.os_rss,
+        (CHUNKS * CHUNK) >> 20
+    );
+    std::hint::black_box(holder);
+}
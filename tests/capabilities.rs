@@ -0,0 +1,29 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use bmalloc::GcCapabilities;
+
+#[test]
+fn linked_library_has_the_apis_the_crate_calls() {
+    common::setup();
+    let caps = bmalloc::capabilities();
+    assert_eq!(caps.version, bmalloc::gc_version());
+    assert!(caps.version >= (8, 3, 0), "vendored BDWGC is {:?}", caps.version);
+    assert!(caps.start_incremental_collection);
+    assert!(caps.interrupt_finalizers);
+    assert!(caps.foreach_heap_section);
+}
+
+#[test]
+fn flags_follow_the_release_that_added_them() {
+    let v82 = GcCapabilities::for_version((8, 2, 8));
+    assert!(v82.obtained_from_os_bytes && v82.start_incremental_collection);
+    assert!(!v82.interrupt_finalizers && !v82.foreach_heap_section);
+
+    let v80 = GcCapabilities::for_version((8, 0, 4));
+    assert!(v80.time_limit_tv && !v80.start_incremental_collection);
+
+    let v9 = GcCapabilities::for_version((9, 0, 0));
+    assert!(v9.interrupt_finalizers && v9.foreach_heap_section);
+}
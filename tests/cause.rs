@@ -0,0 +1,68 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use bmalloc::CollectionCause;
+
+static SERIAL: Mutex<()> = Mutex::new(());
+static EXPLICIT: AtomicUsize = AtomicUsize::new(0);
+static RETRY: AtomicUsize = AtomicUsize::new(0);
+static HEAP_FULL: AtomicUsize = AtomicUsize::new(0);
+
+fn count(cause: CollectionCause) {
+    let counter = match cause {
+        CollectionCause::Explicit => &EXPLICIT,
+        CollectionCause::AllocationRetry => &RETRY,
+        CollectionCause::HeapFull => &HEAP_FULL,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+fn counts() -> [usize; 3] {
+    [&EXPLICIT, &RETRY, &HEAP_FULL].map(|c| c.load(Ordering::Relaxed))
+}
+
+fn setup() -> std::sync::MutexGuard<'static, ()> {
+    common::setup();
+    let serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    bmalloc::set_collection_cause_callback(count);
+    serial
+}
+
+#[test]
+fn collect_is_reported_as_explicit() {
+    let _serial = setup();
+    let [explicit, retry, heap_full] = counts();
+    let before = bmalloc::gc_count();
+    bmalloc::collect();
+    let collections = (bmalloc::gc_count() - before) as usize;
+    assert!(collections >= 1);
+    assert_eq!(counts(), [explicit + collections, retry, heap_full]);
+}
+
+#[test]
+fn allocating_past_the_threshold_is_reported_as_heap_full() {
+    let _serial = setup();
+    // Off from the start under the deterministic-test feature.
+    bmalloc::set_automatic_collection(true);
+    let [explicit, retry, heap_full] = counts();
+    let before = bmalloc::gc_count();
+    while bmalloc::gc_count() < before + 3 {
+        black_box(bmalloc::gc_malloc_atomic(64 << 10).unwrap());
+    }
+    let collections = (bmalloc::gc_count() - before) as usize;
+    assert_eq!(counts(), [explicit, retry, heap_full + collections]);
+}
+
+#[test]
+fn cleared_callback_is_not_called() {
+    let _serial = setup();
+    bmalloc::clear_collection_cause_callback();
+    let before = counts();
+    bmalloc::collect();
+    assert_eq!(counts(), before);
+}
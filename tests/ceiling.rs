@@ -0,0 +1,132 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bmalloc::CeilingAction;
+
+extern "C" {
+    fn GC_gcollect();
+}
+
+const CHILD_ENV: &str = "BMALLOC_CEILING_CHILD";
+const CHUNKS: usize = 64;
+const CHUNK: usize = 256 << 10;
+
+// Holds `CHUNKS * CHUNK` bytes of live data, in scanned GC memory.
+struct Holder(usize);
+
+impl Holder {
+    fn new() -> Holder {
+        Holder(common::isolated(|| {
+            let holder = bmalloc::gc_slice_filled(0usize, CHUNKS).unwrap();
+            for slot in unsafe { &mut *holder.as_ptr() } {
+                *slot = bmalloc::gc_malloc_atomic(CHUNK).unwrap().as_ptr() as usize;
+            }
+            holder.as_ptr() as *mut usize as usize
+        }))
+    }
+
+    fn release(&self) {
+        unsafe { std::ptr::write_bytes(self.0 as *mut usize, 0, CHUNKS) };
+        bmalloc::clear_stack();
+    }
+}
+
+/// Enforce a ceiling half-way through the held data, then collect without
+/// running the checks, so that they see the figures of a collection that
+/// found the data live. The data is released (or not) before they run.
+fn exceed_at_collection(on_exceed: CeilingAction, release: bool) -> Holder {
+    bmalloc::collect();
+    let ceiling = bmalloc::memory_use() + CHUNKS * CHUNK / 2;
+    let holder = Holder::new();
+    bmalloc::enforce_memory_ceiling(ceiling, on_exceed);
+    unsafe { GC_gcollect() };
+    assert!(bmalloc::memory_use() > ceiling);
+    if release {
+        holder.release();
+    }
+    bmalloc::run_pending_gc_hooks();
+    holder
+}
+
+// Runs in the child processes spawned below.
+#[test]
+fn ceiling_child() {
+    let Some(case) = std::env::var_os(CHILD_ENV) else {
+        return;
+    };
+    common::setup();
+    let holder = match case.to_str().unwrap() {
+        "immediate" => exceed_at_collection(CeilingAction::AbortImmediately, true),
+        "collect-released" => exceed_at_collection(CeilingAction::CollectThenAbort, true),
+        "collect-live" => exceed_at_collection(CeilingAction::CollectThenAbort, false),
+        case => panic!("unknown case {case}"),
+    };
+    std::hint::black_box(holder);
+    println!("survived");
+}
+
+fn run_child(case: &str) -> (bool, String) {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["ceiling_child", "--exact", "--nocapture", "--test-threads=1"])
+        .env(CHILD_ENV, case)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let survived = stdout.contains("survived");
+    assert_eq!(survived, output.status.success(), "{stdout}\n{stderr}");
+    (survived, stderr.into_owned())
+}
+
+#[test]
+fn abort_immediately_goes_by_the_last_collection() {
+    let (survived, stderr) = run_child("immediate");
+    assert!(!survived, "{stderr}");
+    assert!(stderr.contains("bmalloc: memory ceiling exceeded: "), "{stderr}");
+}
+
+#[test]
+fn collect_then_abort_rechecks_after_collecting() {
+    let (survived, stderr) = run_child("collect-released");
+    assert!(survived, "{stderr}");
+    assert!(!stderr.contains("memory ceiling exceeded"), "{stderr}");
+}
+
+#[test]
+fn collect_then_abort_aborts_when_still_exceeded() {
+    let (survived, stderr) = run_child("collect-live");
+    assert!(!survived, "{stderr}");
+    assert!(stderr.contains("bmalloc: memory ceiling exceeded: "), "{stderr}");
+}
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+fn count() {
+    CALLS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[test]
+fn callback_fires_after_each_collection_above_the_ceiling() {
+    common::setup();
+    let holder = exceed_at_collection(CeilingAction::Callback(count), false);
+    assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+    bmalloc::collect();
+    assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+    // Allocating after a collection checks the ceiling but leaves the call
+    // to the hooks.
+    unsafe { GC_gcollect() };
+    std::hint::black_box(bmalloc::gc_try_new(0u64).unwrap());
+    assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+    bmalloc::run_pending_gc_hooks();
+    assert_eq!(CALLS.load(Ordering::Relaxed), 3);
+
+    holder.release();
+    bmalloc::collect();
+    bmalloc::collect();
+    assert_eq!(CALLS.load(Ordering::Relaxed), 3);
+    bmalloc::enforce_memory_ceiling(0, CeilingAction::Callback(count));
+}
@@ -0,0 +1,74 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Mutex;
+
+use bmalloc::testing::HeapCheckpoint;
+
+// A checkpoint measures the whole heap.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+const LEAK: usize = 1 << 20;
+
+// Statics are roots: anything stored here stays live for good. Stores go
+// through `black_box`, as the compiler drops stores to a static never read.
+static ROOT: AtomicPtr<u8> = AtomicPtr::new(std::ptr::null_mut());
+
+fn serial() -> std::sync::MutexGuard<'static, ()> {
+    let serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    black_box(&ROOT).store(std::ptr::null_mut(), Ordering::Relaxed);
+    serial
+}
+
+fn garbage() {
+    common::isolated(|| {
+        for _ in 0..64 {
+            black_box(bmalloc::gc_malloc_atomic(LEAK / 64).unwrap());
+        }
+    });
+}
+
+fn leak_a_root() {
+    let obj = bmalloc::gc_malloc_atomic(LEAK).unwrap().as_ptr();
+    black_box(&ROOT).store(obj, Ordering::Relaxed);
+}
+
+#[test]
+fn balanced_block_passes() {
+    let _serial = serial();
+    let checkpoint = HeapCheckpoint::new();
+    garbage();
+    checkpoint.assert_balanced();
+    let _dropped = HeapCheckpoint::new();
+    garbage();
+}
+
+#[test]
+#[should_panic(expected = "heap not balanced")]
+fn leaked_root_fails_the_assertion() {
+    let _serial = serial();
+    let checkpoint = HeapCheckpoint::new();
+    garbage();
+    leak_a_root();
+    checkpoint.assert_balanced();
+}
+
+#[test]
+#[should_panic(expected = "heap not balanced")]
+fn leaked_root_fails_on_drop() {
+    let _serial = serial();
+    let _checkpoint = HeapCheckpoint::new();
+    leak_a_root();
+}
+
+#[test]
+fn tolerance_covers_small_growth() {
+    let _serial = serial();
+    let checkpoint = HeapCheckpoint::with_tolerance(2 * LEAK);
+    leak_a_root();
+    checkpoint.assert_balanced();
+}
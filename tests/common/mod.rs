@@ -0,0 +1,37 @@
+//! Setup shared by the integration tests.
+//!
+//! The collector must be initialised on the main thread, but the test
+//! harness runs every test on a thread of its own. Initialise it from an ELF
+//! constructor instead, before `main`, and have each test register the
+//! thread it runs on.
+
+#![allow(dead_code)]
+
+#[used]
+#[link_section = ".init_array"]
+static INIT: extern "C" fn() = {
+    extern "C" fn init() {
+        bmalloc::init();
+    }
+    init
+};
+
+/// Register the calling test thread with the collector. Call at the top of
+/// every test.
+pub fn setup() {
+    assert!(bmalloc::ensure_thread_registered());
+}
+
+/// Run `f` on a thread of its own, scrubbing what it leaves on the stack,
+/// so that the objects it allocates are unreachable once it returns.
+#[inline(never)]
+pub fn isolated<R: Send>(f: impl FnOnce() -> R + Send) -> R {
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            setup();
+            bmalloc::with_stack_cleared(f)
+        })
+        .join()
+        .unwrap()
+    })
+}
@@ -0,0 +1,44 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use bmalloc::GcCow;
+
+#[test]
+fn promoted_slice_outlives_the_borrow() {
+    common::setup();
+    let owned = {
+        let source: Vec<String> = (0..16).map(|i| format!("item {i}")).collect();
+        let borrowed = GcCow::Borrowed(&source[..]);
+        assert!(!borrowed.is_owned());
+        assert_eq!(borrowed.len(), 16);
+        let owned = borrowed.to_gc_owned().unwrap();
+        assert_ne!(owned.as_ptr(), source.as_ptr());
+        owned
+    };
+    for _ in 0..3 {
+        bmalloc::collect();
+    }
+    assert!(owned.is_owned());
+    for (i, item) in owned.iter().enumerate() {
+        assert_eq!(*item, format!("item {i}"));
+    }
+
+    // Promoting an owned value keeps pointing at the same object.
+    let (GcCow::Owned(a), GcCow::Owned(b)) = (owned, owned.to_gc_owned().unwrap()) else {
+        panic!("not owned");
+    };
+    assert_eq!(a, b);
+}
+
+#[test]
+fn promoted_value_is_a_copy() {
+    common::setup();
+    let value = 41u64;
+    let owned = GcCow::Borrowed(&value).to_gc_owned().unwrap();
+    let GcCow::Owned(ptr) = owned else { panic!("not owned") };
+    assert!(bmalloc::base_of(ptr.as_ptr()).is_some());
+    unsafe { *ptr.as_ptr() += 1 };
+    bmalloc::collect();
+    assert_eq!((*owned, value), (42, 41));
+}
@@ -0,0 +1,81 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use bmalloc::testing::{assert_collected, CollectionProbe};
+
+const OBJECTS: usize = 8;
+
+type Value = [u64; 4];
+
+fn value(i: usize) -> Value {
+    [i as u64, !(i as u64), 0xdead_beef, i as u64 * 3]
+}
+
+struct Moved([NonNull<Value>; OBJECTS], Vec<CollectionProbe>);
+
+unsafe impl Send for Moved {}
+
+#[test]
+fn defragmented_objects_keep_their_values() {
+    common::setup();
+    let moved = common::isolated(|| {
+        let old: [NonNull<Value>; OBJECTS] =
+            std::array::from_fn(|i| bmalloc::gc_try_new(value(i)).unwrap());
+        let probes = old.iter().map(|p| unsafe { CollectionProbe::for_ptr(p.as_ptr().cast()) });
+        let probes = probes.collect();
+        let new = old.map(|p| unsafe { bmalloc::defragment(p) }.unwrap());
+        for (old, new) in old.iter().zip(&new) {
+            assert_ne!(old, new);
+        }
+        Moved(new, probes)
+    });
+
+    for probe in moved.1 {
+        assert_collected(probe, Duration::from_secs(5));
+    }
+    for (i, new) in moved.0.iter().enumerate() {
+        assert_eq!(bmalloc::base_of(new.as_ptr()), Some(new.cast()));
+        assert_eq!(unsafe { new.as_ptr().read() }, value(i));
+    }
+    black_box(moved.0);
+}
+
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+struct Tracked(u64);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn objects_with_a_finalizer_are_left_in_place() {
+    common::setup();
+    common::isolated(|| {
+        let obj = bmalloc::gc_new_cyclic(|_| Tracked(7)).unwrap();
+        assert!(unsafe { bmalloc::defragment(obj) }.is_none());
+        assert_eq!(unsafe { obj.as_ref().0 }, 7);
+    });
+
+    // The finalizer was put back, and drops the value exactly once.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while DROPS.load(Ordering::Relaxed) == 0 {
+        assert!(Instant::now() < deadline, "the value was never dropped");
+        bmalloc::clear_stack();
+        bmalloc::collect();
+        bmalloc::invoke_finalizers();
+    }
+    for _ in 0..3 {
+        bmalloc::collect();
+        bmalloc::invoke_finalizers();
+    }
+    assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+}
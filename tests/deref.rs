@@ -0,0 +1,63 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bmalloc::testing::{assert_collected, CollectionProbe};
+
+type Large = [u64; 8192];
+
+// A large object allocated meanwhile could take the collected one's block.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+struct Hidden(usize, CollectionProbe);
+
+unsafe impl Send for Hidden {}
+
+#[test]
+fn live_object_derefs() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    let obj = bmalloc::gc_try_new([3u64; 4]).unwrap();
+    let found = unsafe { bmalloc::deref_if_live(obj.as_ptr() as *const [u64; 4]) }.unwrap();
+    assert_eq!(found as *const _, obj.as_ptr() as *const _);
+    assert_eq!(*found, [3; 4]);
+
+    let large = bmalloc::gc_try_new([5u64; 8192]).unwrap();
+    let found = unsafe { bmalloc::deref_if_live(large.as_ptr() as *const Large) }.unwrap();
+    assert_eq!(found[8191], 5);
+}
+
+#[test]
+fn non_objects_are_rejected() {
+    common::setup();
+    let obj = bmalloc::gc_try_new([3u64; 4]).unwrap();
+    unsafe {
+        assert!(bmalloc::deref_if_live(std::ptr::null::<u64>()).is_none());
+        assert!(bmalloc::deref_if_live((obj.as_ptr() as *const u64).add(1)).is_none());
+        assert!(bmalloc::deref_if_live((obj.as_ptr() as *const u8).add(1) as *const u32).is_none());
+        // Larger than the object.
+        assert!(bmalloc::deref_if_live(obj.as_ptr() as *const [u64; 64]).is_none());
+        let boxed = Box::new(7u64);
+        assert!(bmalloc::deref_if_live(&*boxed as *const u64).is_none());
+    }
+}
+
+#[test]
+fn collected_large_object_is_rejected() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    // The address is kept complemented, where the collector does not see it.
+    let Hidden(hidden, probe) = common::isolated(|| {
+        let obj = bmalloc::gc_try_new([5u64; 8192]).unwrap();
+        let probe = unsafe { CollectionProbe::for_ptr(obj.as_ptr() as *const u8) };
+        Hidden(!(obj.as_ptr() as usize), probe)
+    });
+    assert_collected(probe, Duration::from_secs(5));
+    // With poison-on-reclaim, it is only reclaimed by the next collection.
+    bmalloc::clear_stack();
+    bmalloc::collect();
+    assert!(unsafe { bmalloc::deref_if_live(!hidden as *const Large) }.is_none());
+}
@@ -0,0 +1,54 @@
+#![cfg(all(target_os = "linux", feature = "deterministic-test"))]
+
+mod common;
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bmalloc::testing::CollectionProbe;
+
+// The automatic collection setting is process-wide.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+struct Probes(Vec<CollectionProbe>);
+
+unsafe impl Send for Probes {}
+
+#[test]
+fn liveness_changes_only_at_explicit_collections() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    assert!(!bmalloc::automatic_collection());
+
+    let probes = common::isolated(|| {
+        Probes(
+            (0..64)
+                .map(|_| {
+                    let obj = bmalloc::gc_malloc_atomic(1024).unwrap();
+                    unsafe { CollectionProbe::for_ptr(obj.as_ptr()) }
+                })
+                .collect(),
+        )
+    });
+    let gc_no = bmalloc::gc_count();
+    // Far more than would normally trigger a collection.
+    for _ in 0..64 * 1024 {
+        bmalloc::gc_malloc_atomic(1024).unwrap();
+    }
+    assert_eq!(bmalloc::gc_count(), gc_no);
+    assert!(probes.0.iter().all(|probe| !probe.is_collected()));
+
+    for probe in probes.0 {
+        bmalloc::testing::assert_collected(probe, Duration::from_secs(5));
+    }
+}
+
+#[test]
+fn with_manual_gc_restores_the_setting() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    bmalloc::set_automatic_collection(true);
+    bmalloc::with_manual_gc(|| assert!(!bmalloc::automatic_collection()));
+    assert!(bmalloc::automatic_collection());
+    bmalloc::set_automatic_collection(false);
+}
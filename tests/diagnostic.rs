@@ -0,0 +1,116 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::sync::Mutex;
+
+#[cfg(not(feature = "interpose-threads"))]
+const CHILD_ENV: &str = "BMALLOC_DIAGNOSTIC_CHILD";
+
+#[test]
+fn hint_only_for_misuse_symptoms() {
+    for message in [
+        "Collecting from unknown thread",
+        "GC_unregister_my_thread: thread not registered",
+        "Smashed object at 0x1234",
+        "Bad free list",
+    ] {
+        assert!(bmalloc::registration_hint(message).unwrap().contains("ensure_thread_registered"));
+    }
+    assert_eq!(bmalloc::registration_hint("index out of bounds"), None);
+    assert_eq!(bmalloc::registration_hint(""), None);
+}
+
+static REPORTED: Mutex<String> = Mutex::new(String::new());
+
+// A `std` panic hook adding the hint, as the crate docs suggest.
+#[test]
+fn panic_hook_can_add_the_hint() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("");
+        let mut reported = REPORTED.lock().unwrap_or_else(|e| e.into_inner());
+        *reported = message.to_owned();
+        if let Some(hint) = bmalloc::registration_hint(message) {
+            reported.push_str("\nhint: ");
+            reported.push_str(hint);
+        }
+    }));
+    let result = std::panic::catch_unwind(|| panic!("GC reported a corrupt mark stack"));
+    std::panic::set_hook(previous);
+    assert!(result.is_err());
+
+    let reported = REPORTED.lock().unwrap_or_else(|e| e.into_inner());
+    assert!(reported.starts_with("GC reported a corrupt mark stack\nhint: "), "{reported}");
+    assert!(reported.contains("not registered with the collector"), "{reported}");
+}
+
+// Runs in the child processes spawned below: collect from a thread the
+// collector does not know about, which aborts, with the abort handler named
+// by the variable installed. Under interpose-threads there is no such
+// thread.
+#[test]
+#[cfg(not(feature = "interpose-threads"))]
+fn unregistered_collect_child() {
+    let Some(handler) = std::env::var_os(CHILD_ENV) else {
+        return;
+    };
+    common::setup();
+    match handler.to_str().unwrap() {
+        "hint" => bmalloc::install_diagnostic_abort_hook(),
+        "postmortem" => {
+            // Something for the backtrace feature to list.
+            std::hint::black_box(bmalloc::gc_try_new(0u64).unwrap());
+            bmalloc::enable_corruption_postmortem(false);
+        }
+        handler => panic!("unknown handler {handler}"),
+    }
+    std::thread::spawn(|| {
+        assert!(!bmalloc::thread_is_registered());
+        bmalloc::collect();
+    })
+    .join()
+    .unwrap();
+    unreachable!("collected from an unregistered thread");
+}
+
+#[cfg(not(feature = "interpose-threads"))]
+fn abort_child(handler: &str) -> String {
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["unregistered_collect_child", "--exact", "--nocapture", "--test-threads=1"])
+        .env(CHILD_ENV, handler)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(!output.status.success(), "the child did not abort: {stderr}");
+    stderr
+}
+
+#[test]
+#[cfg(not(feature = "interpose-threads"))]
+fn abort_hook_adds_the_hint() {
+    let stderr = abort_child("hint");
+    assert!(stderr.contains("bmalloc: GC abort ("), "{stderr}");
+    assert!(stderr.contains("ensure_thread_registered"), "{stderr}");
+}
+
+#[test]
+#[cfg(not(feature = "interpose-threads"))]
+fn postmortem_block_precedes_the_abort() {
+    let stderr = abort_child("postmortem");
+    let block = stderr
+        .split_once("bmalloc: GC abort postmortem: ")
+        .unwrap_or_else(|| panic!("no postmortem in {stderr}"))
+        .1;
+    assert!(block.starts_with("Collecting from unknown thread"), "{block}");
+    assert!(block.contains("\ncollections:"), "{block}");
+    assert!(block.contains("\nheap size:"), "{block}");
+    assert!(block.contains("\naborting thread: 0x"), "{block}");
+    #[cfg(feature = "backtrace")]
+    assert!(block.contains("\nrecent allocation at "), "{block}");
+}
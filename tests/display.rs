@@ -0,0 +1,62 @@
+#![cfg(target_os = "linux")]
+
+use bmalloc::{format_bytes, HeapUsage, ProfileStats};
+
+#[test]
+fn bytes_use_binary_units() {
+    assert_eq!(format_bytes(0).to_string(), "0 B");
+    assert_eq!(format_bytes(1023).to_string(), "1023 B");
+    assert_eq!(format_bytes(1024).to_string(), "1.0 KiB");
+    assert_eq!(format_bytes(1536).to_string(), "1.5 KiB");
+    assert_eq!(format_bytes(3 << 20).to_string(), "3.0 MiB");
+    assert_eq!(format_bytes(5 << 30).to_string(), "5.0 GiB");
+    assert_eq!(format_bytes(2048 << 30).to_string(), "2048.0 GiB");
+}
+
+#[test]
+fn profile_stats_are_labelled_with_units() {
+    let stats = ProfileStats {
+        heapsize_full: 64 << 20,
+        free_bytes_full: 3 << 19,
+        unmapped_bytes: 0,
+        bytes_allocd_since_gc: 512,
+        gc_no: 7,
+        markers_m1: 3,
+        bytes_reclaimed_since_gc: 2 << 30,
+        ..Default::default()
+    };
+    let text = stats.to_string();
+    for line in [
+        "collections:              7",
+        "heap size:                64.0 MiB",
+        "free:                     1.5 MiB",
+        "unmapped:                 0 B",
+        "allocated since GC:       512 B",
+        "reclaimed by last GC:     2.0 GiB",
+        "extra marker threads:     3",
+    ] {
+        assert!(text.lines().any(|l| l == line), "{line:?} missing from\n{text}");
+    }
+    assert_eq!(text.lines().count(), 11);
+
+    // Debug stays machine-friendly: raw numbers under the field names.
+    let debug = format!("{stats:?}");
+    assert!(debug.contains("heapsize_full: 67108864"), "{debug}");
+    assert!(debug.contains("gc_no: 7"), "{debug}");
+}
+
+#[test]
+fn heap_usage_summary() {
+    let usage = HeapUsage {
+        heap_size: 8 << 20,
+        free_bytes: 2 << 20,
+        unmapped_bytes: 1 << 10,
+        bytes_since_gc: 100,
+        total_bytes: 10 << 30,
+    };
+    assert_eq!(
+        usage.to_string(),
+        "heap 8.0 MiB, free 2.0 MiB, unmapped 1.0 KiB, allocated since GC 100 B, \
+         allocated total 10.0 GiB"
+    );
+}
@@ -0,0 +1,43 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::process::Command;
+
+const CHILD_ENV: &str = "BMALLOC_DUMP_CHILD";
+
+// Runs in the child process spawned below, whose standard output, where
+// the collector logs, is captured.
+#[test]
+fn dump_child() {
+    if std::env::var_os(CHILD_ENV).is_none() {
+        return;
+    }
+    common::setup();
+    bmalloc::gc_try_new(1u64).unwrap();
+    bmalloc::dump_named("phase-a");
+    bmalloc::dump_named("phase-b");
+    bmalloc::dump_named("cut\0here");
+    bmalloc::dump_named(&"x".repeat(300));
+}
+
+#[test]
+fn dumps_are_headed_by_their_labels() {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["dump_child", "--exact", "--nocapture", "--test-threads=1"])
+        .env(CHILD_ENV, "1")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let at = |label: &str| {
+        let header = format!("***GC Dump {label}\n");
+        stdout.find(&header).unwrap_or_else(|| panic!("no {header:?} in\n{stdout}"))
+    };
+    assert!(at("phase-a") < at("phase-b"));
+    assert!(at("phase-b") < at("cut"));
+    assert!(at("cut") < at(&"x".repeat(127)));
+    assert!(!stdout.contains("here"));
+    assert!(stdout.contains("***Heap sections:"));
+}
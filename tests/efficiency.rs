@@ -0,0 +1,38 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+
+const WORKLOAD_BYTES: usize = 64 << 20;
+const CHUNK: usize = 4096;
+
+#[test]
+fn efficiency_tracks_a_known_workload() {
+    common::setup();
+    let before = bmalloc::get_prof_stats().unwrap();
+    assert_eq!(bmalloc::collection_efficiency().unwrap().is_infinite(), before.gc_no == 0);
+
+    // Off from the start under the deterministic-test feature.
+    bmalloc::set_automatic_collection(true);
+    // Short-lived garbage only: the heap stays small, so automatic
+    // collections run every few MiB rather than once for the whole workload.
+    for _ in 0..WORKLOAD_BYTES / CHUNK {
+        black_box(bmalloc::gc_malloc_atomic(CHUNK).unwrap());
+    }
+    bmalloc::collect();
+
+    let efficiency = bmalloc::collection_efficiency().unwrap();
+    let after = bmalloc::get_prof_stats().unwrap();
+    assert!(after.gc_no > before.gc_no + 1, "the workload triggered no collections");
+    assert!(efficiency.is_finite());
+
+    let allocated = efficiency * after.gc_no as f64;
+    assert!(
+        allocated >= WORKLOAD_BYTES as f64,
+        "{allocated} bytes over {} collections",
+        after.gc_no
+    );
+    assert!(efficiency >= CHUNK as f64);
+    assert!(efficiency < WORKLOAD_BYTES as f64);
+}
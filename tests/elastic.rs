@@ -0,0 +1,42 @@
+#![cfg(target_os = "linux")]
+#![feature(allocator_api)]
+
+mod common;
+
+use std::alloc::{Allocator, Layout};
+
+use bmalloc::AtomicGcAllocator;
+
+const MIB: usize = 1 << 20;
+const CHUNK: usize = 64 << 10;
+
+#[test]
+fn limit_stretches_up_to_the_hard_ceiling() {
+    common::setup();
+    let base = bmalloc::heap_usage().heap_size;
+    let (soft, hard) = (base + MIB, base + 4 * MIB);
+
+    // Scanned GC memory holding every chunk, so that all of them stay live.
+    let live = bmalloc::gc_slice_filled(0usize, 2 * hard / CHUNK).unwrap();
+    let live = unsafe { &mut *live.as_ptr() };
+    bmalloc::set_elastic_heap_limit(soft, hard, 1.5);
+    assert_eq!(bmalloc::max_heap_size(), Some(soft));
+
+    let layout = Layout::from_size_align(CHUNK, 8).unwrap();
+    let mut allocated = 0;
+    let mut raised = false;
+    while let Ok(chunk) = AtomicGcAllocator.allocate(layout) {
+        live[allocated] = chunk.as_ptr() as *mut u8 as usize;
+        allocated += 1;
+        raised |= bmalloc::max_heap_size() > Some(soft);
+    }
+    assert!(raised, "the limit never moved past {soft}");
+    assert!(allocated * CHUNK > soft - base, "only {allocated} chunks fit");
+    assert_eq!(bmalloc::max_heap_size(), Some(hard));
+    assert!(bmalloc::heap_usage().heap_size <= hard);
+
+    // At the ceiling, a collection frees nothing and the limit stays put.
+    assert!(AtomicGcAllocator.allocate(layout).is_err());
+    assert_eq!(bmalloc::max_heap_size(), Some(hard));
+    assert!(live[..allocated].iter().all(|&chunk| bmalloc::base_of(chunk as *const u8).is_some()));
+}
@@ -0,0 +1,38 @@
+#![cfg(all(target_os = "linux", feature = "explicit-free"))]
+#![feature(allocator_api)]
+
+mod common;
+
+use std::alloc::{Allocator, Layout};
+
+use bmalloc::AtomicGcAllocator;
+
+const BUFFER: usize = 8 << 20;
+
+fn freed_since_gc() -> usize {
+    bmalloc::get_prof_stats().unwrap().expl_freed_bytes_since_gc
+}
+
+#[test]
+fn freeing_an_atomic_buffer_is_counted_and_reclaims_it() {
+    common::setup();
+    // No collection may reset the counter in between.
+    bmalloc::set_automatic_collection(false);
+    let layout = Layout::from_size_align(BUFFER, 8).unwrap();
+    let buffer = AtomicGcAllocator.allocate(layout).unwrap().cast::<u8>();
+    unsafe { buffer.as_ptr().write_bytes(0xa5, BUFFER) };
+
+    let freed = freed_since_gc();
+    let free = bmalloc::heap_usage().free_bytes;
+    unsafe { AtomicGcAllocator.deallocate(buffer, layout) };
+    assert!(freed_since_gc() >= freed + BUFFER);
+    // Back in the heap's free space at once, without a collection.
+    assert!(bmalloc::heap_usage().free_bytes >= free + BUFFER);
+
+    // Through a box, as a released frame buffer would be.
+    let frame: Box<[u8], _> =
+        unsafe { Box::new_zeroed_slice_in(BUFFER, AtomicGcAllocator).assume_init() };
+    let freed = freed_since_gc();
+    drop(frame);
+    assert!(freed_since_gc() >= freed + BUFFER);
+}
@@ -0,0 +1,84 @@
+#![cfg(all(target_os = "linux", feature = "test-fault-injection"))]
+#![feature(allocator_api)]
+
+mod common;
+
+use std::alloc::Layout;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use bmalloc::GcAllocator;
+
+// The injector is process-wide.
+static SERIAL: Mutex<()> = Mutex::new(());
+static FIRED: AtomicUsize = AtomicUsize::new(0);
+const LIMIT: usize = 4096;
+
+fn above_limit(layout: Layout) -> bool {
+    let fire = layout.size() > LIMIT;
+    if fire {
+        FIRED.fetch_add(1, Ordering::Relaxed);
+    }
+    fire
+}
+
+fn setup() -> std::sync::MutexGuard<'static, ()> {
+    common::setup();
+    let serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    bmalloc::set_fault_injector(above_limit);
+    serial
+}
+
+#[test]
+fn try_new_fails_exactly_when_the_injector_fires() {
+    let _serial = setup();
+    let fired = FIRED.load(Ordering::Relaxed);
+    assert!(bmalloc::gc_try_new([0u8; LIMIT]).is_ok());
+    assert_eq!(FIRED.load(Ordering::Relaxed), fired);
+    assert!(bmalloc::gc_try_new([0u8; LIMIT + 1]).is_err());
+    assert_eq!(FIRED.load(Ordering::Relaxed), fired + 1);
+    assert!(bmalloc::gc_try_new_slice(&[0u64; LIMIT]).is_err());
+    assert_eq!(FIRED.load(Ordering::Relaxed), fired + 2);
+
+    bmalloc::clear_fault_injector();
+    assert!(bmalloc::gc_try_new([0u8; LIMIT + 1]).is_ok());
+    assert_eq!(FIRED.load(Ordering::Relaxed), fired + 2);
+}
+
+#[test]
+fn try_reserve_fails_exactly_when_the_injector_fires() {
+    let _serial = setup();
+    for size in [1, 100, LIMIT, LIMIT + 1, 1 << 20] {
+        let mut v: Vec<u8, GcAllocator> = Vec::new_in(GcAllocator);
+        let fired = FIRED.load(Ordering::Relaxed);
+        let result = v.try_reserve_exact(size);
+        let now_fired = FIRED.load(Ordering::Relaxed) > fired;
+        assert_eq!(result.is_err(), now_fired, "{size} bytes");
+        assert_eq!(now_fired, size > LIMIT, "{size} bytes");
+        if result.is_ok() {
+            v.resize(size, 7);
+            assert!(v.iter().all(|&b| b == 7));
+        }
+    }
+
+    // Growing past the limit fails too, leaving the vector intact.
+    let mut v: Vec<u8, GcAllocator> = Vec::new_in(GcAllocator);
+    v.resize(LIMIT, 7);
+    assert!(v.try_reserve(1).is_err());
+    assert_eq!(v.len(), LIMIT);
+    assert!(v.iter().all(|&b| b == 7));
+    bmalloc::clear_fault_injector();
+}
+
+#[test]
+fn pointer_free_allocations_fail_when_the_injector_fires() {
+    let _serial = setup();
+    let fired = FIRED.load(Ordering::Relaxed);
+    assert!(bmalloc::gc_malloc_atomic(LIMIT).is_some());
+    assert!(bmalloc::gc_bytes(&[7; LIMIT]).is_some());
+    assert_eq!(FIRED.load(Ordering::Relaxed), fired);
+    assert!(bmalloc::gc_malloc_atomic(LIMIT + 1).is_none());
+    assert!(bmalloc::gc_bytes(&[7; LIMIT + 1]).is_none());
+    assert_eq!(FIRED.load(Ordering::Relaxed), fired + 2);
+    bmalloc::clear_fault_injector();
+}
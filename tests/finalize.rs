@@ -0,0 +1,223 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// The metered thread drains the finalization queue that the other tests
+// count.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+#[test]
+fn metered_thread_rejects_a_zero_interval() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    assert!(bmalloc::start_metered_finalizer_thread(Duration::ZERO).is_none());
+
+    let handle = bmalloc::start_metered_finalizer_thread(Duration::from_millis(1)).unwrap();
+    while handle.metrics().ticks < 2 {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    handle.stop();
+}
+
+const FINALIZABLE: usize = 8;
+
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+struct Counted;
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn pending_count_drains_to_zero() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    unsafe { bmalloc::GC_set_finalize_on_demand(1) };
+    bmalloc::invoke_finalizers();
+    assert_eq!(bmalloc::pending_finalizers(), 0);
+
+    common::isolated(|| {
+        for _ in 0..FINALIZABLE {
+            bmalloc::GcAny::new(Counted).unwrap();
+        }
+    });
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while bmalloc::pending_finalizers() < FINALIZABLE {
+        assert!(Instant::now() < deadline, "{} queued", bmalloc::pending_finalizers());
+        bmalloc::clear_stack();
+        bmalloc::collect();
+    }
+    assert_eq!(bmalloc::pending_finalizers(), FINALIZABLE);
+    assert_eq!(DROPPED.load(Ordering::Relaxed), 0);
+
+    assert_eq!(bmalloc::invoke_finalizers(), FINALIZABLE);
+    assert_eq!(bmalloc::pending_finalizers(), 0);
+    assert_eq!(DROPPED.load(Ordering::Relaxed), FINALIZABLE);
+    unsafe { bmalloc::GC_set_finalize_on_demand(0) };
+}
+
+static KEPT_DROPPED: AtomicUsize = AtomicUsize::new(0);
+static CANCELLED_DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+struct Kept;
+
+impl Drop for Kept {
+    fn drop(&mut self) {
+        KEPT_DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+struct Cancelled;
+
+impl Drop for Cancelled {
+    fn drop(&mut self) {
+        CANCELLED_DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn unregistered_finalizer_does_not_run() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    unsafe { bmalloc::GC_set_finalize_on_demand(1) };
+    bmalloc::invoke_finalizers();
+
+    common::isolated(|| {
+        for _ in 0..FINALIZABLE {
+            // The finalized objects show when a collection has found the
+            // cancelled ones unreachable too.
+            bmalloc::GcAny::new(Kept).unwrap();
+            let cancelled = bmalloc::GcAny::new(Cancelled).unwrap();
+            assert!(unsafe { bmalloc::unregister_finalizer(cancelled.as_ptr()) });
+            assert!(!unsafe { bmalloc::unregister_finalizer(cancelled.as_ptr()) });
+        }
+    });
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while KEPT_DROPPED.load(Ordering::Relaxed) < FINALIZABLE {
+        assert!(Instant::now() < deadline, "{} finalized", KEPT_DROPPED.load(Ordering::Relaxed));
+        bmalloc::clear_stack();
+        bmalloc::collect();
+        bmalloc::invoke_finalizers();
+    }
+    assert_eq!(KEPT_DROPPED.load(Ordering::Relaxed), FINALIZABLE);
+    assert_eq!(CANCELLED_DROPPED.load(Ordering::Relaxed), 0);
+    unsafe { bmalloc::GC_set_finalize_on_demand(0) };
+}
+
+const SLOW: usize = 50;
+const SLOW_DROP: Duration = Duration::from_millis(2);
+
+static SLOW_DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+struct Slow;
+
+impl Drop for Slow {
+    fn drop(&mut self) {
+        std::thread::sleep(SLOW_DROP);
+        SLOW_DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn budgeted_draining_leaves_the_rest_queued() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    unsafe { bmalloc::GC_set_finalize_on_demand(1) };
+    bmalloc::invoke_finalizers();
+
+    common::isolated(|| {
+        for _ in 0..SLOW {
+            bmalloc::GcAny::new(Slow).unwrap();
+        }
+    });
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while bmalloc::pending_finalizers() < SLOW {
+        assert!(Instant::now() < deadline, "{} queued", bmalloc::pending_finalizers());
+        bmalloc::clear_stack();
+        bmalloc::collect();
+    }
+
+    let ran = bmalloc::invoke_finalizers_within(SLOW_DROP * 5);
+    assert_eq!(SLOW_DROPPED.load(Ordering::Relaxed), ran);
+    if bmalloc::capabilities().interrupt_finalizers {
+        // The clock is checked between finalizers: at most one overruns.
+        assert!((1..=6).contains(&ran), "{ran} ran");
+        assert_eq!(bmalloc::pending_finalizers(), SLOW - ran);
+        let mut total = ran;
+        while total < SLOW {
+            let ran = bmalloc::invoke_finalizers_within(SLOW_DROP * 5);
+            assert!((1..=6).contains(&ran), "{ran} ran");
+            total += ran;
+        }
+        assert_eq!(total, SLOW);
+    } else {
+        // Without a per-call limit the whole queue runs at once.
+        assert_eq!(ran, SLOW);
+    }
+    assert_eq!(bmalloc::pending_finalizers(), 0);
+    assert_eq!(SLOW_DROPPED.load(Ordering::Relaxed), SLOW);
+    unsafe { bmalloc::GC_set_finalize_on_demand(0) };
+}
+
+const METERED: usize = 16;
+
+static METERED_DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+struct Metered;
+
+impl Drop for Metered {
+    fn drop(&mut self) {
+        METERED_DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn metered_thread_drains_the_queue_and_counts_it() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    unsafe { bmalloc::GC_set_finalize_on_demand(1) };
+    bmalloc::invoke_finalizers();
+
+    common::isolated(|| {
+        for _ in 0..METERED {
+            bmalloc::GcAny::new(Metered).unwrap();
+        }
+    });
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while bmalloc::pending_finalizers() < METERED {
+        assert!(Instant::now() < deadline, "{} queued", bmalloc::pending_finalizers());
+        bmalloc::clear_stack();
+        bmalloc::collect();
+    }
+    assert_eq!(METERED_DROPPED.load(Ordering::Relaxed), 0);
+
+    let handle = bmalloc::start_metered_finalizer_thread(Duration::from_millis(5)).unwrap();
+    // Only one runs at a time.
+    assert!(bmalloc::start_metered_finalizer_thread(Duration::from_millis(5)).is_none());
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while handle.metrics().finalizers_run < METERED as u64 {
+        assert!(Instant::now() < deadline, "{:?}", handle.metrics());
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(METERED_DROPPED.load(Ordering::Relaxed), METERED);
+    assert_eq!(bmalloc::pending_finalizers(), 0);
+
+    // Nothing more is queued: later ticks run nothing.
+    let ticks = handle.metrics().ticks;
+    while handle.metrics().ticks < ticks + 2 {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    let metrics = handle.metrics();
+    assert_eq!(metrics.finalizers_run, METERED as u64);
+    assert_eq!(metrics.last_tick_run, 0);
+    assert!(metrics.max_tick > Duration::ZERO && metrics.max_tick <= metrics.busy, "{metrics:?}");
+    handle.stop();
+    unsafe { bmalloc::GC_set_finalize_on_demand(0) };
+}
@@ -0,0 +1,63 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use bmalloc::{require_gc_ptr, ForeignPointerError};
+
+#[test]
+fn gc_objects_are_accepted() {
+    common::setup();
+    let obj = bmalloc::gc_try_new([7u64; 4]).unwrap();
+    assert_eq!(require_gc_ptr(obj.as_ptr() as *const [u64; 4]), Ok(obj));
+    // A smaller type at the same address is fine too.
+    let ptr = obj.as_ptr() as *const u64;
+    assert_eq!(require_gc_ptr(ptr).map(|p| p.as_ptr() as *const u64), Ok(ptr));
+}
+
+#[test]
+fn foreign_pointers_are_rejected_with_the_reason() {
+    common::setup();
+    assert_eq!(require_gc_ptr(std::ptr::null::<u64>()), Err(ForeignPointerError::Null));
+
+    let boxed = Box::into_raw(Box::new([0u64; 4]));
+    assert_eq!(require_gc_ptr(boxed), Err(ForeignPointerError::NotGcHeap { addr: boxed as usize }));
+    drop(unsafe { Box::from_raw(boxed) });
+
+    let malloced = unsafe { libc::malloc(64) } as *const u64;
+    assert_eq!(
+        require_gc_ptr(malloced),
+        Err(ForeignPointerError::NotGcHeap { addr: malloced as usize })
+    );
+    unsafe { libc::free(malloced as *mut libc::c_void) };
+
+    let local = 0u64;
+    assert_eq!(
+        require_gc_ptr(&local as *const u64),
+        Err(ForeignPointerError::NotGcHeap { addr: &local as *const u64 as usize })
+    );
+}
+
+#[test]
+fn bad_pointers_into_gc_objects_are_rejected_with_the_reason() {
+    common::setup();
+    let obj = bmalloc::gc_try_new([0u64; 4]).unwrap().as_ptr() as *const u64;
+    let base = obj as usize;
+
+    let interior = unsafe { obj.add(1) };
+    assert_eq!(
+        require_gc_ptr(interior),
+        Err(ForeignPointerError::Interior { addr: base + 8, offset: 8 })
+    );
+
+    let misaligned = (base + 1) as *const u64;
+    assert_eq!(require_gc_ptr(misaligned), Err(ForeignPointerError::Misaligned { addr: base + 1 }));
+
+    let size = unsafe { bmalloc::GC_size(obj as *const u8) };
+    let too_small = require_gc_ptr(obj as *const [u64; 64]);
+    assert_eq!(too_small, Err(ForeignPointerError::TooSmall { addr: base, size }));
+    assert_eq!(
+        too_small.unwrap_err().to_string(),
+        format!("pointer {base:#x} is to a GC object of only {size} bytes")
+    );
+    std::hint::black_box(obj);
+}
@@ -0,0 +1,63 @@
+#![cfg(target_os = "linux")]
+
+// Not `mod common`: parallel marking needs more than one marker, which has to
+// be requested before the collector is initialised.
+#[used]
+#[link_section = ".init_array"]
+static INIT: extern "C" fn() = {
+    extern "C" fn init() {
+        if cfg!(feature = "parallel-mark") {
+            std::env::set_var("GC_MARKERS", "3");
+        }
+        bmalloc::init();
+    }
+    init
+};
+
+// Runs in the child: only plain checks, no panicking or printing, as the
+// other threads of the test harness are gone. Returns the exit status.
+fn child() -> i32 {
+    if !bmalloc::after_fork_child() || !bmalloc::after_fork_child() {
+        return 1;
+    }
+    if !bmalloc::thread_is_registered() {
+        return 2;
+    }
+    let Ok(obj) = bmalloc::gc_try_new([7u64; 16]) else {
+        return 3;
+    };
+    let before = bmalloc::gc_count();
+    bmalloc::collect();
+    if bmalloc::gc_count() == before || unsafe { *obj.as_ptr() } != [7; 16] {
+        return 4;
+    }
+    if cfg!(feature = "parallel-mark") && bmalloc::marker_thread_count() == 0 {
+        return 5;
+    }
+    0
+}
+
+#[test]
+fn child_allocates_and_collects_after_fork() {
+    assert!(bmalloc::ensure_thread_registered());
+    assert!(bmalloc::install_atfork_handlers());
+    if cfg!(feature = "parallel-mark") {
+        bmalloc::start_mark_threads();
+        assert!(bmalloc::marker_thread_count() > 0);
+    }
+    // Something for the child to inherit.
+    let obj = bmalloc::gc_try_new(1u64).unwrap();
+
+    match unsafe { libc::fork() } {
+        -1 => panic!("fork failed"),
+        0 => unsafe { libc::_exit(child()) },
+        pid => {
+            let mut status = 0;
+            assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+            assert!(libc::WIFEXITED(status), "child status {status:#x}");
+            assert_eq!(libc::WEXITSTATUS(status), 0);
+        }
+    }
+    bmalloc::collect();
+    assert_eq!(unsafe { *obj.as_ptr() }, 1);
+}
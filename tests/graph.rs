@@ -0,0 +1,142 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use bmalloc::testing::CollectionProbe;
+
+// Allocation by one test could reuse the memory of another's garbage.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn node(children: &[*const u8]) -> *const u8 {
+    bmalloc::gc_try_new_slice(children).unwrap().as_ptr() as *const u8
+}
+
+#[test]
+fn snapshot_records_a_known_graph() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    // a -> b, a -> c (twice), b -> c; c holds no pointers.
+    let c = node(&[ptr::null(); 2]);
+    let b = node(&[c, ptr::null()]);
+    let a = node(&[b, c, c]);
+
+    let graph = bmalloc::snapshot_graph().unwrap();
+    let find = |base| graph.nodes().iter().find(|n| n.base == base).unwrap();
+    let edges = |base| {
+        let mut edges = graph.edges_of(find(base)).to_vec();
+        edges.sort();
+        edges
+    };
+    let mut a_edges = vec![b, c];
+    a_edges.sort();
+    assert_eq!(edges(a), a_edges);
+    assert_eq!(edges(b), [c]);
+    assert!(edges(c).is_empty());
+    assert!(find(a).size >= 3 * std::mem::size_of::<usize>());
+
+    let total: usize = graph.nodes().iter().map(|n| graph.edges_of(n).len()).sum();
+    assert_eq!(total, graph.edges().len());
+    black_box((a, b, c));
+}
+
+struct Garbage(Vec<usize>, CollectionProbe);
+
+unsafe impl Send for Garbage {}
+
+#[test]
+fn only_reachable_objects_are_visited() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    // y <- x <- root (on this stack), and an unreachable cycle a -> b -> c -> a.
+    let y = node(&[ptr::null(); 2]);
+    let x = node(&[y, ptr::null()]);
+    let root = node(&[x, ptr::null()]);
+    let garbage = common::isolated(|| {
+        let a =
+            bmalloc::gc_try_new_slice(&[ptr::null::<u8>(); 3]).unwrap().as_ptr() as *mut *const u8;
+        let b = node(&[a as *const u8, ptr::null()]);
+        let c = node(&[b, ptr::null()]);
+        unsafe { a.write(c) };
+        let hidden = [a as *const u8, b, c].map(|n| !(n as usize)).to_vec();
+        Garbage(hidden, unsafe { CollectionProbe::for_ptr(a as *const u8) })
+    });
+
+    // Collect until the cycle is gone, allocating nothing meanwhile so that
+    // none of its memory is reused.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !garbage.1.is_collected() {
+        assert!(Instant::now() < deadline, "the unreachable cycle was retained");
+        bmalloc::clear_stack();
+        bmalloc::collect();
+    }
+
+    // `f` runs with the world stopped, so it must not reallocate.
+    let mut visited = Vec::with_capacity(1 << 16);
+    bmalloc::for_each_reachable(|obj, size| {
+        if visited.len() < visited.capacity() {
+            visited.push((obj.as_ptr() as usize, size));
+        }
+    });
+    assert!(visited.len() < visited.capacity(), "too many objects to check");
+
+    for n in [root, x, y] {
+        let &(_, size) = visited.iter().find(|&&(base, _)| base == n as usize).unwrap();
+        assert!(size >= 2 * std::mem::size_of::<usize>());
+    }
+    for hidden in &garbage.0 {
+        assert!(!visited.iter().any(|&(base, _)| base == !hidden), "garbage visited");
+    }
+    black_box(root);
+}
+
+#[test]
+fn frozen_heap_holds_still_while_another_thread_allocates() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    let obj = node(&[ptr::null(); 4]);
+    let allocated = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            common::setup();
+            // A growing list, so that what is reachable keeps changing.
+            let mut head = ptr::null();
+            while !stop.load(Ordering::Relaxed) {
+                head = node(&[head, ptr::null()]);
+                allocated.fetch_add(1, Ordering::Relaxed);
+            }
+            black_box(head);
+        });
+        while allocated.load(Ordering::Relaxed) < 1000 {
+            std::thread::yield_now();
+        }
+
+        let (counts, during) = bmalloc::with_heap_frozen(|heap| {
+            let before = allocated.load(Ordering::Relaxed);
+            let first = heap.object_count();
+            std::thread::sleep(Duration::from_millis(50));
+            let second = heap.object_count();
+            let (base, size) = heap.resolve(unsafe { obj.add(1) }).unwrap();
+            assert_eq!(base.as_ptr() as *const u8, obj);
+            assert_eq!(heap.bytes(obj).unwrap().len(), size);
+            ((first, second), allocated.load(Ordering::Relaxed) - before)
+        });
+        assert_eq!(counts.0, counts.1);
+        assert!(counts.0 > 1000, "{} objects", counts.0);
+        assert_eq!(during, 0, "{during} allocations with the heap frozen");
+
+        // The allocating thread resumes afterwards.
+        let after = allocated.load(Ordering::Relaxed);
+        while allocated.load(Ordering::Relaxed) == after {
+            std::thread::yield_now();
+        }
+        stop.store(true, Ordering::Relaxed);
+    });
+    black_box(obj);
+}
@@ -0,0 +1,53 @@
+#![cfg(all(target_os = "linux", feature = "log"))]
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const DELTA: usize = 1 << 20;
+const CHUNK: usize = 64 << 10;
+
+static GROWTH_LINES: AtomicUsize = AtomicUsize::new(0);
+
+struct Counter;
+
+impl log::Log for Counter {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        if record.args().to_string().starts_with("GC heap grew by more than") {
+            GROWTH_LINES.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn one_line_per_delta_crossed() {
+    common::setup();
+    log::set_logger(&Counter).unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+
+    // Nothing is collected, so every chunk grows the heap.
+    bmalloc::set_automatic_collection(false);
+    let base = unsafe { bmalloc::GC_get_heap_size() };
+    bmalloc::set_growth_log_delta(DELTA);
+    while unsafe { bmalloc::GC_get_heap_size() } < base + 4 * DELTA + DELTA / 2 {
+        bmalloc::gc_try_new([0usize; CHUNK / 8]).unwrap();
+    }
+    // The lines are emitted by the allocation after the resize.
+    bmalloc::gc_try_new(0u8).unwrap();
+    let crossed = (unsafe { bmalloc::GC_get_heap_size() } - base) / DELTA;
+    assert!(crossed >= 4);
+    assert_eq!(GROWTH_LINES.load(Ordering::Relaxed), crossed);
+
+    bmalloc::set_growth_log_delta(0);
+    for _ in 0..64 {
+        bmalloc::gc_try_new([0usize; CHUNK / 8]).unwrap();
+    }
+    assert_eq!(GROWTH_LINES.load(Ordering::Relaxed), crossed);
+    bmalloc::set_automatic_collection(true);
+}
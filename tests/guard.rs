@@ -0,0 +1,53 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+use std::sync::Mutex;
+
+use bmalloc::CollectOnDrop;
+
+static SERIAL: Mutex<()> = Mutex::new(());
+
+// Collections only happen where the test asks for them.
+fn setup() -> std::sync::MutexGuard<'static, ()> {
+    common::setup();
+    let serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    bmalloc::set_automatic_collection(false);
+    serial
+}
+
+fn make_garbage(bytes: usize) {
+    for _ in 0..bytes / (64 << 10) {
+        black_box(bmalloc::gc_malloc_atomic(64 << 10).unwrap());
+    }
+}
+
+#[test]
+fn collects_when_dropped_and_not_before() {
+    let _serial = setup();
+    let before = bmalloc::gc_count();
+    {
+        let _collect = CollectOnDrop::new();
+        make_garbage(16 << 20);
+        assert_eq!(bmalloc::gc_count(), before);
+    }
+    assert_eq!(bmalloc::gc_count(), before + 1);
+}
+
+#[test]
+fn unmapping_guard_returns_the_free_heap() {
+    let _serial = setup();
+    // The collector only unmaps blocks that were already free before the
+    // collection, not those it frees itself.
+    make_garbage(16 << 20);
+    bmalloc::collect();
+    let before = bmalloc::gc_count();
+    let unmapped = bmalloc::heap_usage().unmapped_bytes;
+    {
+        let _collect = CollectOnDrop::with_unmap();
+        assert_eq!(bmalloc::gc_count(), before);
+    }
+    assert_eq!(bmalloc::gc_count(), before + 1);
+    assert!(bmalloc::heap_usage().unmapped_bytes > unmapped);
+}
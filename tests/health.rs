@@ -0,0 +1,93 @@
+#![cfg(target_os = "linux")]
+#![feature(allocator_api)]
+
+mod common;
+
+use std::sync::Mutex;
+
+use bmalloc::{GcAllocator, HealthStatus, HEALTH_CRITICAL_FREE_PERCENT, HEALTH_WARN_FREE_PERCENT};
+
+// The heap limit is process-wide.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+const CHUNK: usize = 4096;
+
+fn severity(status: HealthStatus) -> u8 {
+    match status {
+        HealthStatus::Ok => 0,
+        HealthStatus::Warn => 1,
+        HealthStatus::Critical => 2,
+    }
+}
+
+fn check_consistent(health: &bmalloc::GcHealth) {
+    assert!((0.0..=100.0).contains(&health.free_percent), "{health:?}");
+    // Unmapped bytes are counted as free.
+    assert!(health.unmapped_percent <= health.free_percent, "{health:?}");
+    assert_eq!(health.status, HealthStatus::from_free_percent(health.free_percent));
+}
+
+#[test]
+fn percentages_match_the_raw_stats() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    let health = bmalloc::health().unwrap();
+    let stats = bmalloc::get_prof_stats().unwrap();
+    check_consistent(&health);
+    if health.collections == stats.gc_no && health.heap_size == stats.heapsize_full {
+        let free = health.free_percent * health.heap_size as f64 / 100.0;
+        let unmapped = health.unmapped_percent * health.heap_size as f64 / 100.0;
+        assert!((free - stats.free_bytes_full as f64).abs() < 1.0);
+        assert!((unmapped - stats.unmapped_bytes as f64).abs() < 1.0);
+    }
+}
+
+#[test]
+fn thresholds_classify_free_space() {
+    assert_eq!(HealthStatus::from_free_percent(100.0), HealthStatus::Ok);
+    assert_eq!(HealthStatus::from_free_percent(HEALTH_WARN_FREE_PERCENT), HealthStatus::Ok);
+    assert_eq!(HealthStatus::from_free_percent(HEALTH_WARN_FREE_PERCENT - 0.1), HealthStatus::Warn);
+    assert_eq!(HealthStatus::from_free_percent(HEALTH_CRITICAL_FREE_PERCENT), HealthStatus::Warn);
+    assert_eq!(
+        HealthStatus::from_free_percent(HEALTH_CRITICAL_FREE_PERCENT - 0.1),
+        HealthStatus::Critical
+    );
+    assert_eq!(HealthStatus::from_free_percent(0.0), HealthStatus::Critical);
+}
+
+#[test]
+fn status_worsens_as_a_capped_heap_fills() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    bmalloc::collect();
+    bmalloc::set_max_heap_size(bmalloc::heap_usage().heap_size + (16 << 20));
+
+    // Fill the heap with live objects until the limit is hit, sampling as
+    // it goes.
+    let mut samples = Vec::new();
+    let mut live = Vec::with_capacity_in(8192, GcAllocator);
+    while let Some(obj) = bmalloc::gc_malloc_atomic(CHUNK) {
+        live.push(obj);
+        if live.len() % 64 == 0 {
+            samples.push(bmalloc::health().unwrap());
+        }
+    }
+    let full = bmalloc::health().unwrap();
+    bmalloc::set_max_heap_size(0);
+    drop(live);
+
+    for health in &samples {
+        check_consistent(health);
+    }
+    check_consistent(&full);
+    assert_eq!(full.status, HealthStatus::Critical, "{full:?}");
+    assert!(samples.iter().any(|h| h.status == HealthStatus::Ok));
+
+    // Once the heap stops growing, free space only shrinks.
+    let capped = samples.iter().filter(|h| h.heap_size == full.heap_size).chain([&full]);
+    let capped: Vec<_> = capped.collect();
+    for pair in capped.windows(2) {
+        assert!(pair[1].free_percent <= pair[0].free_percent, "{pair:?}");
+        assert!(severity(pair[1].status) >= severity(pair[0].status), "{pair:?}");
+    }
+}
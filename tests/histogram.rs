@@ -0,0 +1,37 @@
+#![cfg(all(target_os = "linux", feature = "alloc-histogram"))]
+#![feature(allocator_api)]
+
+mod common;
+
+use std::alloc::{Allocator, Layout};
+
+use bmalloc::{alloc_histogram, histogram_bucket, AtomicGcAllocator, GcAllocator, BUCKETS};
+
+#[test]
+fn buckets_are_powers_of_two() {
+    assert_eq!([0, 1, 2, 3, 4, 5].map(histogram_bucket), [0, 0, 1, 2, 2, 3]);
+    assert_eq!([4096, 4097].map(histogram_bucket), [12, 13]);
+    assert_eq!(histogram_bucket(usize::MAX), BUCKETS - 1);
+}
+
+#[test]
+fn allocations_land_in_their_buckets() {
+    common::setup();
+    bmalloc::reset_alloc_histogram();
+    assert_eq!(alloc_histogram(), [0; BUCKETS]);
+
+    let sizes = [1, 2, 3, 4, 5, 1000, 1024, 4096, 4097];
+    for size in sizes {
+        GcAllocator.allocate(Layout::from_size_align(size, 1).unwrap()).unwrap();
+    }
+    AtomicGcAllocator.allocate(Layout::from_size_align(4096, 4096).unwrap()).unwrap();
+
+    let mut expected = [0; BUCKETS];
+    for size in sizes.into_iter().chain([4096]) {
+        expected[histogram_bucket(size)] += 1;
+    }
+    assert_eq!(alloc_histogram(), expected);
+
+    bmalloc::reset_alloc_histogram();
+    assert_eq!(alloc_histogram(), [0; BUCKETS]);
+}
@@ -0,0 +1,72 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+use std::time::Duration;
+
+use bmalloc::testing::{assert_collected, assert_retained, CollectionProbe};
+use bmalloc::ImmortalArena;
+
+#[repr(align(64))]
+struct Line([u64; 8]);
+
+struct SendProbe(CollectionProbe);
+
+unsafe impl Send for SendProbe {}
+
+#[test]
+fn immortal_objects_outlive_collections() {
+    common::setup();
+    // The arena itself goes away; what it handed out does not.
+    let (words, lines, big) = {
+        let arena = ImmortalArena::new(4096);
+        let words: Vec<&'static [u64; 4]> =
+            (0..10_000).map(|i| arena.alloc([i; 4]).unwrap()).collect();
+        let lines: Vec<&'static Line> =
+            (0..100).map(|i| arena.alloc(Line([i; 8])).unwrap()).collect();
+        // Larger than a slab: gets one of its own.
+        (words, lines, arena.alloc([7u8; 10_000]).unwrap())
+    };
+
+    for _ in 0..4 {
+        bmalloc::collect();
+        // Anything reclaimed would be handed out again and overwritten.
+        for _ in 0..1000 {
+            black_box(bmalloc::gc_try_new([u64::MAX; 8]).unwrap());
+        }
+    }
+    for (i, w) in words.iter().enumerate() {
+        assert_eq!(**w, [i as u64; 4]);
+    }
+    for (i, l) in lines.iter().enumerate() {
+        assert_eq!(*l as *const Line as usize % 64, 0);
+        assert_eq!(l.0, [i as u64; 8]);
+    }
+    assert!(big.iter().all(|&b| b == 7));
+}
+
+fn probe_referenced_from(arena: fn() -> ImmortalArena) -> CollectionProbe {
+    common::isolated(|| {
+        let arena = arena();
+        let target = bmalloc::gc_try_new([0u64; 8]).unwrap();
+        let probe = unsafe { CollectionProbe::for_ptr(target.as_ptr() as *const u8) };
+        arena.alloc(target.as_ptr() as usize).unwrap();
+        SendProbe(probe)
+    })
+    .0
+}
+
+#[test]
+fn scanned_arena_keeps_its_targets_alive() {
+    common::setup();
+    let probe = probe_referenced_from(|| ImmortalArena::new(4096));
+    assert_retained(&probe, Duration::from_millis(50));
+}
+
+#[test]
+fn atomic_arena_is_not_scanned() {
+    common::setup();
+    let probe = probe_referenced_from(|| unsafe { ImmortalArena::new_atomic(4096) });
+    assert_collected(probe, Duration::from_secs(5));
+}
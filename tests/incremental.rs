@@ -0,0 +1,96 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bmalloc::CollectionCause;
+
+// The time limit is process-wide.
+static SERIAL: Mutex<()> = Mutex::new(());
+static HOOK_RUNS: AtomicUsize = AtomicUsize::new(0);
+static OTHER_CAUSES: AtomicUsize = AtomicUsize::new(0);
+
+// Switch to incremental mode with only explicit collections, counting
+// collections attributed to anything else and post-collection hook runs.
+fn setup() {
+    common::setup();
+    bmalloc::enable_incremental();
+    assert!(bmalloc::is_incremental());
+    bmalloc::set_automatic_collection(false);
+    bmalloc::set_post_collection_hook(|| {
+        HOOK_RUNS.fetch_add(1, Ordering::Relaxed);
+    });
+    bmalloc::set_collection_cause_callback(|cause| {
+        if cause != CollectionCause::Explicit {
+            OTHER_CAUSES.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+}
+
+#[test]
+fn sliced_collection_is_explicit_and_runs_the_hook() {
+    setup();
+    bmalloc::collect_incrementally(Duration::from_micros(1), || {});
+    assert!(HOOK_RUNS.load(Ordering::Relaxed) >= 1);
+    assert_eq!(OTHER_CAUSES.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn progress_is_monotonic_and_ends_at_one() {
+    setup();
+    let mut reported = Vec::new();
+    bmalloc::collect_incremental_with_progress(|f| reported.push(f));
+    assert_eq!(reported.last(), Some(&1.0));
+    assert!(reported.windows(2).all(|w| w[0] <= w[1]));
+    assert_eq!(OTHER_CAUSES.load(Ordering::Relaxed), 0);
+}
+
+// 32 MiB of scanned, live objects: marking them takes many slices.
+fn large_live_heap() -> NonNull<[NonNull<[usize]>]> {
+    let chunks: Vec<_> =
+        (0..512).map(|_| bmalloc::gc_slice_filled(0usize, 8192).unwrap()).collect();
+    bmalloc::gc_try_new_slice(&chunks).unwrap()
+}
+
+#[test]
+fn large_heap_collection_yields_and_completes() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    setup();
+    let live = large_live_heap();
+
+    // Without a pause target the collector would otherwise mark everything
+    // in its first step.
+    bmalloc::set_time_limit(bmalloc::TIME_UNLIMITED);
+    let before = bmalloc::gc_count();
+    let mut yields = 0;
+    bmalloc::collect_incrementally(Duration::from_micros(1), || yields += 1);
+    assert!(yields > 1, "{yields} yields");
+    assert!(bmalloc::gc_count() > before);
+    assert_eq!(bmalloc::time_limit(), bmalloc::TIME_UNLIMITED);
+    assert!(unsafe { &*live.as_ptr() }.iter().all(|c| unsafe { &*c.as_ptr() }[8191] == 0));
+}
+
+#[test]
+fn time_limit_is_restored_when_yield_fn_panics() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    setup();
+    let live = large_live_heap();
+
+    bmalloc::set_time_limit(bmalloc::TIME_UNLIMITED);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        bmalloc::collect_incrementally(Duration::from_micros(1), || panic!("yield"));
+    }));
+    assert!(result.is_err());
+    assert_eq!(bmalloc::time_limit(), bmalloc::TIME_UNLIMITED);
+
+    // The collection left behind still finishes.
+    let before = bmalloc::gc_count();
+    bmalloc::collect();
+    assert!(bmalloc::gc_count() > before);
+    assert!(unsafe { &*live.as_ptr() }.iter().all(|c| unsafe { &*c.as_ptr() }[8191] == 0));
+}
@@ -0,0 +1,94 @@
+#![cfg(target_os = "linux")]
+#![feature(allocator_api)]
+
+mod common;
+
+use std::alloc::{Allocator, Layout};
+use std::hint::black_box;
+use std::sync::Mutex;
+
+use bmalloc::{AtomicGcAllocator, LARGE_ALLOC_THRESHOLD};
+
+// The limit and automatic collection are process-wide.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+const LIMIT: usize = 8 * LARGE_ALLOC_THRESHOLD;
+
+fn alloc(size: usize) {
+    black_box(AtomicGcAllocator.allocate(Layout::from_size_align(size, 8).unwrap()).unwrap());
+}
+
+// Runs `f` with the limit set and only the collections it starts, returning
+// how many there were.
+fn collections_during(f: impl FnOnce()) -> u64 {
+    bmalloc::set_automatic_collection(false);
+    bmalloc::set_large_alloc_concurrency_limit(LIMIT);
+    bmalloc::collect();
+    assert_eq!(bmalloc::large_alloc_inflight_bytes(), 0);
+    let before = bmalloc::gc_count();
+    f();
+    let collections = bmalloc::gc_count() - before;
+    bmalloc::set_large_alloc_concurrency_limit(0);
+    bmalloc::set_automatic_collection(true);
+    collections
+}
+
+#[test]
+fn crossing_the_limit_collects_first() {
+    common::setup();
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    let collections = collections_during(|| {
+        for _ in 0..8 {
+            alloc(LARGE_ALLOC_THRESHOLD);
+        }
+        assert_eq!(bmalloc::large_alloc_inflight_bytes(), LIMIT);
+        // The ninth would take the count past the limit.
+        let before = bmalloc::gc_count();
+        alloc(LARGE_ALLOC_THRESHOLD);
+        assert_eq!(bmalloc::gc_count(), before + 1);
+        assert_eq!(bmalloc::large_alloc_inflight_bytes(), LARGE_ALLOC_THRESHOLD);
+        // Small allocations are not counted.
+        for _ in 0..1000 {
+            alloc(LARGE_ALLOC_THRESHOLD / 2);
+        }
+        assert_eq!(bmalloc::large_alloc_inflight_bytes(), LARGE_ALLOC_THRESHOLD);
+        // Any collection resets the count.
+        bmalloc::collect();
+        assert_eq!(bmalloc::large_alloc_inflight_bytes(), 0);
+    });
+    assert_eq!(collections, 2);
+}
+
+#[test]
+fn concurrent_burst_is_collected_along_the_way() {
+    common::setup();
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    let collections = collections_during(|| {
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    common::setup();
+                    for _ in 0..16 {
+                        alloc(LARGE_ALLOC_THRESHOLD);
+                    }
+                });
+            }
+        });
+    });
+    // 64 MiB against an 8 MiB limit. Threads that cross the limit while
+    // another collects do not wait, so fewer than 7 collections may run.
+    assert!((1..=7).contains(&collections), "{collections} collections");
+}
+
+#[test]
+fn no_limit_no_collections() {
+    common::setup();
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    bmalloc::set_automatic_collection(false);
+    let before = bmalloc::gc_count();
+    for _ in 0..16 {
+        alloc(LARGE_ALLOC_THRESHOLD);
+    }
+    assert_eq!(bmalloc::gc_count(), before);
+    bmalloc::set_automatic_collection(true);
+}
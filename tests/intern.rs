@@ -0,0 +1,85 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use bmalloc::{ByteInterner, GcInterner};
+
+struct Shared(GcInterner<String>);
+
+unsafe impl Send for Shared {}
+
+impl Shared {
+    fn get(&mut self) -> &mut GcInterner<String> {
+        &mut self.0
+    }
+}
+
+#[test]
+fn equal_values_share_an_object() {
+    common::setup();
+    let mut interner = GcInterner::new();
+    let a = interner.intern(String::from("node")).unwrap();
+    let b = interner.intern(String::from("node")).unwrap();
+    let c = interner.intern(String::from("other")).unwrap();
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+    assert_eq!(interner.len(), 2);
+}
+
+#[test]
+fn unreachable_values_are_pruned() {
+    const N: usize = 512;
+
+    common::setup();
+    let mut shared = Shared(GcInterner::new());
+    let kept = shared.get().intern(String::from("kept")).unwrap();
+    common::isolated(|| {
+        for i in 0..N {
+            shared.get().intern(i.to_string()).unwrap();
+        }
+        assert_eq!(shared.get().len(), N + 1);
+    });
+    for _ in 0..3 {
+        bmalloc::collect();
+    }
+    // Stale copies on cached stacks may keep a few alive; the bulk must go.
+    let live = shared.get().len();
+    assert!(live < N / 2, "{live} of {} entries survived", N + 1);
+
+    // The value still referenced survives pruning and is still found.
+    assert_eq!(unsafe { kept.as_ref() }, "kept");
+    assert_eq!(shared.get().intern(String::from("kept")), Some(kept));
+}
+
+#[test]
+fn identical_bytes_share_one_object() {
+    common::setup();
+    let mut interner = ByteInterner::new();
+    let a = interner.intern(b"payload").unwrap();
+    let b = interner.intern(&b"payload!"[..7]).unwrap();
+    let c = interner.intern(b"other").unwrap();
+    let empty = interner.intern(b"").unwrap();
+    assert_eq!(a.as_ptr() as *const u8, b.as_ptr() as *const u8);
+    assert_ne!(a.as_ptr() as *const u8, c.as_ptr() as *const u8);
+    assert_eq!(interner.len(), 3);
+
+    for _ in 0..3 {
+        bmalloc::collect();
+    }
+    assert_eq!(unsafe { a.as_ref() }, b"payload");
+    assert_eq!(unsafe { c.as_ref() }, b"other");
+    assert_eq!(unsafe { empty.as_ref() }, b"");
+    assert_eq!(interner.intern(b"payload").unwrap(), a);
+    assert_eq!(interner.len(), 3);
+}
+
+#[test]
+fn gc_bytes_copies_into_a_new_object() {
+    common::setup();
+    let source = [1u8, 2, 3, 4, 5];
+    let bytes = bmalloc::gc_bytes(&source).unwrap();
+    assert_ne!(bytes.as_ptr() as *const u8, source.as_ptr());
+    bmalloc::collect();
+    assert_eq!(unsafe { bytes.as_ref() }, &source);
+    assert!(bmalloc::base_of(bytes.as_ptr() as *const u8).is_some());
+}
@@ -0,0 +1,52 @@
+#![cfg(all(target_os = "linux", feature = "interpose-threads"))]
+
+mod common;
+
+use std::sync::Barrier;
+
+const THREADS: usize = 4;
+const OBJECTS: usize = 256;
+
+#[test]
+fn plain_threads_are_intercepted() {
+    assert!(bmalloc::foreign_threads_intercepted());
+}
+
+#[test]
+fn objects_of_plain_threads_survive_collection() {
+    let allocated = Barrier::new(THREADS + 1);
+    let collected = Barrier::new(THREADS + 1);
+    std::thread::scope(|s| {
+        for t in 0..THREADS {
+            let (allocated, collected) = (&allocated, &collected);
+            s.spawn(move || {
+                // Never registered explicitly: only the interposed
+                // `pthread_create` makes this thread's stack a root.
+                let objs: Vec<_> = (0..OBJECTS)
+                    .map(|i| {
+                        let obj = bmalloc::gc_malloc_atomic(64).unwrap().as_ptr();
+                        unsafe { obj.write_bytes((t * OBJECTS + i) as u8, 64) };
+                        obj
+                    })
+                    .collect();
+                let objs = bmalloc::gc_try_new_slice(&objs).unwrap();
+                allocated.wait();
+                collected.wait();
+                for (i, &obj) in unsafe { objs.as_ref() }.iter().enumerate() {
+                    let bytes = unsafe { std::slice::from_raw_parts(obj, 64) };
+                    assert!(bytes.iter().all(|&b| b == (t * OBJECTS + i) as u8));
+                }
+            });
+        }
+        allocated.wait();
+        for _ in 0..3 {
+            bmalloc::collect();
+            // Fill the freed space, so that anything reclaimed is reused.
+            for _ in 0..THREADS * OBJECTS {
+                let obj = bmalloc::gc_malloc_atomic(64).unwrap().as_ptr();
+                unsafe { obj.write_bytes(0xEE, 64) };
+            }
+        }
+        collected.wait();
+    });
+}
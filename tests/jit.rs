@@ -0,0 +1,83 @@
+#![cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+
+use bmalloc::JitBuffer;
+
+// Not `mod common`: executable pages must be requested before the collector
+// is initialised.
+#[used]
+#[link_section = ".init_array"]
+static INIT: extern "C" fn() = {
+    extern "C" fn init() {
+        bmalloc::set_pages_executable(true);
+        bmalloc::init();
+    }
+    init
+};
+
+// mov eax, 42; ret
+#[cfg(target_arch = "x86_64")]
+const RETURN_42: [u8; 6] = [0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3];
+// lea eax, [rdi + rsi]; ret
+#[cfg(target_arch = "x86_64")]
+const ADD: [u8; 4] = [0x8d, 0x04, 0x37, 0xc3];
+
+// mov w0, #42; ret
+#[cfg(target_arch = "aarch64")]
+const RETURN_42: [u8; 8] = [0x40, 0x05, 0x80, 0x52, 0xc0, 0x03, 0x5f, 0xd6];
+// add w0, w0, w1; ret
+#[cfg(target_arch = "aarch64")]
+const ADD: [u8; 8] = [0x00, 0x00, 0x01, 0x0b, 0xc0, 0x03, 0x5f, 0xd6];
+
+fn setup() {
+    assert!(bmalloc::ensure_thread_registered());
+    assert!(bmalloc::pages_executable());
+}
+
+#[test]
+fn writes_accumulate_until_finalized() {
+    setup();
+    let mut buf = JitBuffer::new(64).unwrap();
+    assert!(buf.is_empty() && !buf.is_finalized());
+    buf.write(&RETURN_42[..3]);
+    buf.write(&RETURN_42[3..]);
+    assert_eq!((buf.len(), buf.capacity()), (RETURN_42.len(), 64));
+
+    let code = buf.finalize();
+    assert!(buf.is_finalized());
+    assert_eq!(buf.finalize(), code);
+    let f: extern "C" fn() -> u32 = unsafe { std::mem::transmute(code) };
+    assert_eq!(f(), 42);
+}
+
+#[test]
+fn each_finalized_buffer_runs_its_own_code() {
+    setup();
+    // Buffers may reuse memory freed by earlier ones, whose old instructions
+    // must not be what runs.
+    for i in 0..64u32 {
+        let mut buf = JitBuffer::new(16).unwrap();
+        let code = if i % 2 == 0 { &RETURN_42[..] } else { &ADD[..] };
+        buf.write(code);
+        let f: extern "C" fn(u32, u32) -> u32 = unsafe { std::mem::transmute(buf.finalize()) };
+        assert_eq!(f(i, 1), if i % 2 == 0 { 42 } else { i + 1 });
+        bmalloc::collect();
+    }
+}
+
+#[test]
+#[should_panic(expected = "after it was finalized")]
+fn write_after_finalize_panics() {
+    setup();
+    let mut buf = JitBuffer::new(64).unwrap();
+    buf.write(&RETURN_42);
+    buf.finalize();
+    buf.write(&[0x90]);
+}
+
+#[test]
+#[should_panic(expected = "JitBuffer overflow")]
+fn write_past_capacity_panics() {
+    setup();
+    let mut buf = JitBuffer::new(4).unwrap();
+    buf.write(&RETURN_42);
+}
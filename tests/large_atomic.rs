@@ -0,0 +1,81 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+use std::ptr::NonNull;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bmalloc::testing::{assert_retained, CollectionProbe};
+
+const HUGE: usize = 2 << 30;
+const STRIDE: usize = 64 << 20;
+const THRESHOLD: usize = 1 << 20;
+
+// The threshold is process-wide.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+#[test]
+fn huge_buffer_through_the_ignore_off_page_path() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    bmalloc::set_atomic_ignore_off_page_threshold(THRESHOLD);
+    let buf = bmalloc::gc_malloc_atomic(HUGE).unwrap();
+    assert!(unsafe { bmalloc::GC_size(buf.as_ptr()) } >= HUGE);
+    // Touch one byte per stride only, so that most pages are never backed.
+    for offset in (0..HUGE).step_by(STRIDE).chain([HUGE - 1]) {
+        unsafe { buf.as_ptr().add(offset).write((offset >> 20) as u8 ^ 0x5a) };
+    }
+
+    let probe = unsafe { CollectionProbe::for_ptr(buf.as_ptr()) };
+    assert_retained(&probe, Duration::from_millis(20));
+    for offset in (0..HUGE).step_by(STRIDE).chain([HUGE - 1]) {
+        assert_eq!(unsafe { buf.as_ptr().add(offset).read() }, (offset >> 20) as u8 ^ 0x5a);
+    }
+    black_box(buf);
+    bmalloc::set_atomic_ignore_off_page_threshold(usize::MAX);
+}
+
+const BUFFERS: usize = 16;
+
+struct Held(NonNull<[usize]>, Vec<CollectionProbe>);
+
+unsafe impl Send for Held {}
+
+// Allocate buffers through the ignore-off-page path, keeping a pointer
+// `offset` bytes into each in scanned GC memory, and report how many are
+// reclaimed.
+fn collected_when_held_at(offset: usize) -> usize {
+    let Held(holder, probes) = common::isolated(|| {
+        let holder = bmalloc::gc_slice_filled(0usize, BUFFERS).unwrap();
+        let probes = (0..BUFFERS)
+            .map(|i| {
+                let buf = bmalloc::gc_malloc_atomic(4 * THRESHOLD).unwrap().as_ptr();
+                unsafe { (*holder.as_ptr())[i] = buf as usize + offset };
+                unsafe { CollectionProbe::for_ptr(buf) }
+            })
+            .collect();
+        Held(holder, probes)
+    });
+    for _ in 0..3 {
+        bmalloc::clear_stack();
+        bmalloc::collect();
+    }
+    black_box(holder);
+    probes.iter().filter(|p| p.is_collected()).count()
+}
+
+#[test]
+fn only_a_pointer_near_the_start_retains_it() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    bmalloc::set_atomic_ignore_off_page_threshold(THRESHOLD);
+    assert_eq!(collected_when_held_at(16), 0);
+    // The collector ignores heap references past the first page of these
+    // buffers (stack references are treated more leniently). Stray
+    // conservative references may keep a few alive; the bulk must go.
+    let collected = collected_when_held_at(2 * THRESHOLD);
+    assert!(collected > BUFFERS / 2, "only {collected} of {BUFFERS} collected");
+    bmalloc::set_atomic_ignore_off_page_threshold(usize::MAX);
+}
@@ -0,0 +1,45 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bmalloc::{LeakTrendMonitor, LEAK_TREND_SAMPLES};
+
+#[test]
+fn rising_live_set_is_a_leak() {
+    let mut monitor = LeakTrendMonitor::new();
+    for i in 0..10 {
+        monitor.record((1 << 20) + i * 4096);
+    }
+    assert_eq!(monitor.slope(), 4096.0);
+    assert!(monitor.is_leaking(1024.0));
+}
+
+#[test]
+fn flat_live_set_is_not_a_leak() {
+    let mut monitor = LeakTrendMonitor::new();
+    for _ in 0..LEAK_TREND_SAMPLES * 2 {
+        monitor.record(1 << 20);
+    }
+    assert_eq!(monitor.len(), LEAK_TREND_SAMPLES);
+    assert!(!monitor.is_leaking(0.0));
+}
+
+static HOOK_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+fn sampling_leaves_the_user_hook_alone() {
+    common::setup();
+    bmalloc::set_automatic_collection(false);
+    bmalloc::set_post_collection_hook(|| {
+        HOOK_RUNS.fetch_add(1, Ordering::Relaxed);
+    });
+    LeakTrendMonitor::start_sampling();
+    let before = LeakTrendMonitor::sampled().len();
+    bmalloc::collect();
+    bmalloc::collect();
+    LeakTrendMonitor::stop_sampling();
+    assert_eq!(HOOK_RUNS.load(Ordering::Relaxed), 2);
+    assert_eq!(LeakTrendMonitor::sampled().len(), before + 2);
+}
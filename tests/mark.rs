@@ -0,0 +1,71 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+use std::time::Duration;
+
+use bmalloc::testing::{assert_collected, assert_retained, CollectionProbe};
+use bmalloc::Marker;
+
+const CHILDREN: usize = 4;
+
+// Children are stored complemented, which the conservative scan cannot
+// recognise as pointers.
+fn tag(ptr: *mut u8) -> usize {
+    !(ptr as usize)
+}
+
+fn mark_tagged(obj: *mut u8, marker: &mut Marker) {
+    let slots = obj as *const usize;
+    for i in 0..CHILDREN {
+        let slot = unsafe { slots.add(i).read() };
+        if slot != 0 {
+            marker.push(!slot as *const u8);
+        }
+    }
+}
+
+struct Parent(*mut u8, Vec<CollectionProbe>);
+
+unsafe impl Send for Parent {}
+
+// Allocate a parent with `alloc` and give it tagged children.
+fn parent_with_children(alloc: impl FnOnce(usize) -> *mut u8 + Send) -> Parent {
+    common::isolated(move || {
+        let parent = alloc(CHILDREN * std::mem::size_of::<usize>());
+        let probes = (0..CHILDREN)
+            .map(|i| {
+                let child = bmalloc::gc_malloc_atomic(64).unwrap().as_ptr();
+                unsafe { (parent as *mut usize).add(i).write(tag(child)) };
+                unsafe { CollectionProbe::for_ptr(child) }
+            })
+            .collect();
+        Parent(parent, probes)
+    })
+}
+
+#[test]
+fn mark_proc_keeps_tagged_children_alive() {
+    common::setup();
+    let kind = bmalloc::register_mark_proc(mark_tagged);
+    let parent = parent_with_children(|size| kind.malloc(size));
+    for probe in &parent.1 {
+        assert_retained(probe, Duration::from_millis(50));
+    }
+    black_box(parent.0);
+}
+
+#[test]
+fn conservative_scan_misses_tagged_children() {
+    common::setup();
+    let parent = parent_with_children(|size| {
+        bmalloc::gc_try_new_slice(&vec![0usize; size / std::mem::size_of::<usize>()])
+            .unwrap()
+            .as_ptr() as *mut u8
+    });
+    for probe in parent.1 {
+        assert_collected(probe, Duration::from_secs(5));
+    }
+    black_box(parent.0);
+}
@@ -0,0 +1,24 @@
+#![cfg(all(target_os = "linux", feature = "parallel-mark"))]
+
+// Not `mod common`: its initialisation allows thread registration, which
+// starts the markers straight away.
+#[used]
+#[link_section = ".init_array"]
+static INIT: extern "C" fn() = {
+    extern "C" fn init() {
+        // One marker per core by default, which on a single-core machine
+        // disables parallel marking altogether.
+        std::env::set_var("GC_MARKERS", "3");
+        unsafe { bmalloc::GC_init() };
+    }
+    init
+};
+
+#[test]
+fn markers_start_when_asked() {
+    assert_eq!(bmalloc::marker_thread_count(), 0);
+    bmalloc::start_mark_threads();
+    assert_eq!(bmalloc::marker_thread_count(), 2);
+    bmalloc::start_mark_threads();
+    assert_eq!(bmalloc::marker_thread_count(), 2);
+}
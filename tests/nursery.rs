@@ -0,0 +1,66 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+use std::ptr::{self, NonNull};
+use std::sync::Mutex;
+
+use bmalloc::Nursery;
+
+// Main-heap allocation counters are process-wide.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+const CAPACITY: usize = 64 * 1024;
+
+#[test]
+fn young_garbage_stays_out_of_the_main_heap() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    let mut nursery = Nursery::new(CAPACITY).unwrap();
+    let before = bmalloc::heap_usage();
+
+    // 64 MiB of short-lived objects through a 64 KiB nursery.
+    let mut resets = 0;
+    for i in 0..(1usize << 20) {
+        let value = [i; 8];
+        if let Err(value) = nursery.alloc_value(value) {
+            unsafe { nursery.reset() };
+            resets += 1;
+            black_box(nursery.alloc_value(value).unwrap());
+        }
+    }
+
+    let after = bmalloc::heap_usage();
+    assert!(resets >= (64 << 20) / CAPACITY - 1, "{resets} resets");
+    assert!(after.total_bytes - before.total_bytes < 64 * 1024, "{before:?} {after:?}");
+    assert_eq!(after.heap_size, before.heap_size);
+}
+
+#[test]
+fn survivors_are_promoted_intact() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    let mut nursery = Nursery::new(CAPACITY).unwrap();
+
+    // Each survivor refers to a main-heap object that only it keeps alive.
+    let mut survivors = [ptr::null_mut::<(u64, *mut u64)>(); 8];
+    for (i, survivor) in survivors.iter_mut().enumerate() {
+        let target = bmalloc::gc_try_new(i as u64 * 10).unwrap().as_ptr();
+        let young = nursery.alloc_value((i as u64, target)).unwrap();
+        black_box(nursery.alloc_value([0u8; 100]).unwrap());
+        *survivor = unsafe { nursery.promote(young) }.unwrap().as_ptr();
+    }
+    unsafe { nursery.reset() };
+    assert_eq!(nursery.used(), 0);
+    bmalloc::collect();
+
+    for (i, &survivor) in survivors.iter().enumerate() {
+        assert!(!nursery.contains(survivor as *const u8));
+        let survivor = NonNull::new(survivor).unwrap();
+        assert_eq!(bmalloc::base_of(survivor.as_ptr()), Some(survivor.cast()));
+        let (value, target) = unsafe { survivor.as_ptr().read() };
+        assert_eq!((value, unsafe { *target }), (i as u64, i as u64 * 10));
+    }
+    black_box(survivors);
+}
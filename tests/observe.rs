@@ -0,0 +1,35 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::sync::Mutex;
+
+use bmalloc::testing::run_observing_gc;
+
+// Both tests depend on what the other does to the collector.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+#[test]
+fn a_forced_collection_is_observed() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    let (value, collected) = run_observing_gc(|| {
+        bmalloc::collect();
+        7
+    });
+    assert_eq!(value, 7);
+    assert!(collected);
+}
+
+#[test]
+fn no_collection_while_automatic_collection_is_off() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    bmalloc::set_automatic_collection(false);
+    let (allocated, collected) = run_observing_gc(|| {
+        (0..1000).filter(|_| bmalloc::gc_malloc_atomic(4096).is_some()).count()
+    });
+    bmalloc::set_automatic_collection(true);
+    assert_eq!(allocated, 1000);
+    assert!(!collected);
+}
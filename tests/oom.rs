@@ -0,0 +1,196 @@
+#![cfg(target_os = "linux")]
+#![feature(alloc_error_hook, allocator_api)]
+
+mod common;
+
+use std::alloc::{Allocator, GlobalAlloc, Layout};
+use std::hint::black_box;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use bmalloc::{AtomicGcAllocator, GcAllocator, OomPolicy};
+
+// The heap limit, retry setting and automatic collection are process-wide.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+const CHILD_ENV: &str = "BMALLOC_OOM_CHILD";
+const REQUEST: usize = 64 << 20;
+
+fn cap_heap() {
+    bmalloc::set_max_heap_size(bmalloc::heap_usage().heap_size + (1 << 20));
+}
+
+// An allocation entry point, requesting `REQUEST` bytes and returning
+// whether the allocation succeeded.
+type Kind = (&'static str, fn() -> bool);
+
+const KINDS: [Kind; 6] = [
+    ("normal", || GcAllocator.allocate(Layout::from_size_align(REQUEST, 8).unwrap()).is_ok()),
+    ("atomic", || AtomicGcAllocator.allocate(Layout::from_size_align(REQUEST, 8).unwrap()).is_ok()),
+    ("aligned", || GcAllocator.allocate(Layout::from_size_align(REQUEST, 4096).unwrap()).is_ok()),
+    ("atomic-aligned", || {
+        AtomicGcAllocator.allocate(Layout::from_size_align(REQUEST, 4096).unwrap()).is_ok()
+    }),
+    ("global", || unsafe {
+        !GcAllocator.alloc(Layout::from_size_align(REQUEST, 8).unwrap()).is_null()
+    }),
+    ("realloc", || unsafe {
+        let layout = Layout::new::<[u64; 4]>();
+        let old = GcAllocator.alloc(layout) as *mut [u64; 4];
+        old.write([1, 2, 3, 4]);
+        let new = GcAllocator.realloc(old as *mut u8, layout, REQUEST);
+        // A failed realloc leaves the old object untouched.
+        assert!(!new.is_null() || *old == [1, 2, 3, 4]);
+        !new.is_null()
+    }),
+];
+
+// Runs in the child processes spawned below: exhaust a capped heap, which
+// ends in an abort. `hook` goes through an infallible allocation and the
+// alloc error hook; any other kind through `OomPolicy::Abort`.
+#[test]
+fn oom_child() {
+    let Some(kind) = std::env::var_os(CHILD_ENV) else {
+        return;
+    };
+    common::setup();
+    cap_heap();
+    if kind == "hook" {
+        std::alloc::set_alloc_error_hook(bmalloc::gc_alloc_error_hook);
+        let v: Vec<u8, GcAllocator> = Vec::with_capacity_in(REQUEST, GcAllocator);
+        unreachable!("allocated {} bytes past the heap limit", v.capacity());
+    }
+    bmalloc::set_oom_policy(OomPolicy::Abort);
+    let (_, allocate) = KINDS.iter().find(|(name, _)| kind == *name).unwrap();
+    allocate();
+    unreachable!("allocated {REQUEST} bytes past the heap limit");
+}
+
+fn run_child(kind: &str) -> String {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["oom_child", "--exact", "--nocapture", "--test-threads=1"])
+        .env(CHILD_ENV, kind)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(!output.status.success(), "the {kind} child did not abort: {stderr}");
+    stderr
+}
+
+#[test]
+fn alloc_error_hook_reports_heap_stats() {
+    let stderr = run_child("hook");
+    assert!(
+        stderr.contains(&format!("memory allocation of {REQUEST} bytes (align 1) failed")),
+        "{stderr}"
+    );
+    assert!(stderr.contains("GC heap: size "), "{stderr}");
+    assert!(stderr.contains("; max heap "), "{stderr}");
+}
+
+#[test]
+fn every_kind_returns_null_on_oom() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    bmalloc::set_automatic_collection(false);
+    cap_heap();
+    for (name, allocate) in KINDS {
+        assert!(!allocate(), "{name} allocation succeeded past the heap limit");
+    }
+    assert!(bmalloc::gc_malloc_atomic(REQUEST).is_none());
+    assert!(bmalloc::gc_vec_raw_parts::<u64>(REQUEST / 8).is_none());
+    bmalloc::set_max_heap_size(0);
+    bmalloc::set_automatic_collection(true);
+
+    // The same requests succeed without the limit.
+    for (name, allocate) in KINDS {
+        assert!(allocate(), "{name} allocation failed without a heap limit");
+    }
+}
+
+#[test]
+fn every_kind_aborts_under_the_abort_policy() {
+    for (name, _) in KINDS {
+        let stderr = run_child(name);
+        assert!(
+            stderr.contains(&format!("memory allocation of {REQUEST} bytes")),
+            "{name}: {stderr}"
+        );
+        assert!(stderr.contains("GC heap: size "), "{name}: {stderr}");
+    }
+}
+
+#[test]
+fn retry_collects_garbage_under_the_limit() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    bmalloc::set_automatic_collection(false);
+    cap_heap();
+    // Fill the capped heap with garbage, without the retry collecting it.
+    bmalloc::set_retry_on_oom(false);
+    common::isolated(|| {
+        while let Some(obj) = bmalloc::gc_malloc_atomic(4096) {
+            black_box(obj);
+        }
+    });
+    let layout = Layout::from_size_align(64 * 1024, 8).unwrap();
+
+    assert!(GcAllocator.allocate(layout).is_err());
+    bmalloc::set_retry_on_oom(true);
+    let before = bmalloc::gc_count();
+    assert!(GcAllocator.allocate(layout).is_ok());
+    assert!(bmalloc::gc_count() > before);
+
+    bmalloc::set_max_heap_size(0);
+    bmalloc::set_automatic_collection(true);
+}
+
+static TRY_DROPS: AtomicUsize = AtomicUsize::new(0);
+static TRY_CLONES: AtomicUsize = AtomicUsize::new(0);
+
+struct Tracked([u64; 512]);
+
+impl Clone for Tracked {
+    fn clone(&self) -> Self {
+        TRY_CLONES.fetch_add(1, Ordering::Relaxed);
+        Tracked(self.0)
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        TRY_DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn failed_try_new_drops_the_value_once() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    bmalloc::set_automatic_collection(false);
+    bmalloc::set_retry_on_oom(false);
+    cap_heap();
+
+    let mut allocated = 0;
+    while bmalloc::gc_try_new(Tracked([7; 512])).is_ok() {
+        allocated += 1;
+    }
+    assert!(allocated > 0);
+    // Only the value that did not fit was dropped; the others now belong to
+    // their (unfinalized) GC objects.
+    assert_eq!(TRY_DROPS.load(Ordering::Relaxed), 1);
+
+    let items = vec![Tracked([1; 512]), Tracked([2; 512])];
+    assert!(bmalloc::gc_try_new_slice(&items).is_err());
+    assert_eq!(TRY_CLONES.load(Ordering::Relaxed), 0);
+    drop(items);
+    assert_eq!(TRY_DROPS.load(Ordering::Relaxed), 3);
+
+    bmalloc::set_max_heap_size(0);
+    bmalloc::set_retry_on_oom(true);
+    bmalloc::set_automatic_collection(true);
+    let slice = bmalloc::gc_try_new_slice(&[Tracked([3; 512])]).unwrap();
+    assert_eq!(TRY_CLONES.load(Ordering::Relaxed), 1);
+    assert_eq!(unsafe { slice.as_ref() }[0].0, [3; 512]);
+}
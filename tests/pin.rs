@@ -0,0 +1,46 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::ptr::NonNull;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use bmalloc::testing::{assert_collected, assert_retained, CollectionProbe};
+
+struct Hidden(usize, CollectionProbe);
+
+unsafe impl Send for Hidden {}
+
+#[test]
+fn pinned_object_survives_collections_until_unpinned() {
+    common::setup();
+    let Hidden(hidden, probe) = common::isolated(|| {
+        let obj = bmalloc::gc_try_new([7u64; 4]).unwrap();
+        let probe = unsafe { CollectionProbe::for_ptr(obj.as_ptr() as *const u8) };
+        Hidden(!(obj.as_ptr() as usize), probe)
+    });
+
+    // The pin is held by a thread the collector does not know about, so
+    // its stack is not scanned: only the pin keeps the object alive. With
+    // automatic collection off, pinning cannot trigger a collection from
+    // that thread.
+    bmalloc::set_automatic_collection(false);
+    let (pinned_tx, pinned_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    let pinner = std::thread::spawn(move || {
+        let obj = NonNull::new(!hidden as *mut [u64; 4]).unwrap();
+        bmalloc::with_pinned(obj, |obj| {
+            pinned_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+            unsafe { *obj }
+        })
+    });
+    pinned_rx.recv().unwrap();
+    bmalloc::set_automatic_collection(true);
+
+    assert_retained(&probe, Duration::from_millis(50));
+    release_tx.send(()).unwrap();
+    assert_eq!(pinner.join().unwrap(), [7; 4]);
+    assert_collected(probe, Duration::from_secs(5));
+}
@@ -0,0 +1,52 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+use std::time::Duration;
+
+use bmalloc::testing::{assert_collected, assert_retained, CollectionProbe};
+
+struct Probes(CollectionProbe, CollectionProbe);
+
+unsafe impl Send for Probes {}
+
+#[test]
+fn atomic_value_survives_collection() {
+    common::setup();
+    let mut bytes = [0u8; 4096];
+    bytes.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+    let obj = bmalloc::gc_new_atomic(bytes).unwrap();
+    let probe = unsafe { CollectionProbe::for_ptr(obj.as_ptr() as *const u8) };
+    assert_retained(&probe, Duration::from_millis(50));
+    for _ in 0..64 {
+        black_box(bmalloc::gc_malloc_atomic(4096).unwrap());
+    }
+    bmalloc::collect();
+    assert_eq!(unsafe { *obj.as_ptr() }, bytes);
+
+    let floats = bmalloc::gc_new_atomic([1.5f64; 512]).unwrap();
+    bmalloc::collect();
+    assert!(unsafe { &*floats.as_ptr() }.iter().all(|&x| x == 1.5));
+}
+
+#[test]
+fn atomic_value_is_not_scanned() {
+    common::setup();
+    // An address stored as an integer in an atomic object, and the object
+    // itself once nothing refers to it.
+    let Probes(target, holder) = common::isolated(|| {
+        let target = bmalloc::gc_try_new(0u64).unwrap().as_ptr();
+        let mut words = [0usize; 512];
+        words[0] = target as usize;
+        let holder = bmalloc::gc_new_atomic(words).unwrap().as_ptr();
+        unsafe {
+            Probes(
+                CollectionProbe::for_ptr(target as *const u8),
+                CollectionProbe::for_ptr(holder as *const u8),
+            )
+        }
+    });
+    assert_collected(target, Duration::from_secs(5));
+    assert_collected(holder, Duration::from_secs(5));
+}
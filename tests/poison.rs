@@ -0,0 +1,64 @@
+#![cfg(all(target_os = "linux", feature = "poison-on-reclaim"))]
+#![feature(allocator_api)]
+
+mod common;
+
+use bmalloc::{GcAllocator, POISON_BYTE};
+use core::alloc::{Allocator, Layout};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+#[test]
+fn unreachable_object_reads_back_poison() {
+    common::setup();
+    // Large objects are swept as soon as the collection ends, small ones
+    // only lazily.
+    const SIZE: usize = 8192;
+    let layout = Layout::from_size_align(SIZE, 8).unwrap();
+    // Hide the address from the conservative scan.
+    let hidden = common::isolated(|| {
+        let ptr = GcAllocator.allocate(layout).unwrap().cast::<u8>();
+        unsafe { ptr.as_ptr().write_bytes(0x11, SIZE) };
+        !(ptr.as_ptr() as usize)
+    });
+    bmalloc::collect();
+    let ptr = !hidden as *const u8;
+    let bytes = unsafe { core::slice::from_raw_parts(ptr, SIZE) };
+    assert!(bytes.iter().all(|&b| b == POISON_BYTE), "{bytes:x?}");
+}
+
+#[test]
+fn reachable_object_is_left_alone() {
+    common::setup();
+    let mut v: Vec<u64, GcAllocator> = Vec::with_capacity_in(16, GcAllocator);
+    v.extend(0..16);
+    bmalloc::collect();
+    bmalloc::collect();
+    assert!(v.iter().copied().eq(0..16));
+}
+
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+struct Probe(u64);
+
+impl Drop for Probe {
+    fn drop(&mut self) {
+        DROPPED.fetch_add(self.0, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn owner_finalizer_sees_intact_buffer() {
+    common::setup();
+    common::isolated(|| {
+        let mut probes = Vec::new_in(GcAllocator);
+        probes.extend((1..=32).map(Probe));
+        bmalloc::GcAny::new(probes).unwrap();
+    });
+    for _ in 0..4 {
+        bmalloc::collect();
+        bmalloc::invoke_finalizers();
+    }
+    // Dropping the vector read its buffer: had the buffer been poisoned
+    // first, these would not be the values written.
+    assert_eq!(DROPPED.load(Ordering::Relaxed), (1..=32).sum::<u64>());
+}
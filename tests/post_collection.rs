@@ -0,0 +1,31 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+static SEEN_GC_COUNT: AtomicU64 = AtomicU64::new(0);
+
+#[test]
+fn hook_runs_once_per_collection() {
+    common::setup();
+    bmalloc::set_automatic_collection(false);
+    bmalloc::set_post_collection_hook(|| {
+        // Allocation is permitted, and the collection has been counted.
+        assert!(bmalloc::gc_try_new(0u64).is_ok());
+        SEEN_GC_COUNT.store(bmalloc::gc_count(), Ordering::Relaxed);
+        RUNS.fetch_add(1, Ordering::Relaxed);
+    });
+    for _ in 0..3 {
+        bmalloc::collect();
+        assert_eq!(SEEN_GC_COUNT.load(Ordering::Relaxed), bmalloc::gc_count());
+    }
+    assert_eq!(RUNS.load(Ordering::Relaxed), 3);
+    bmalloc::run_pending_gc_hooks();
+    assert_eq!(RUNS.load(Ordering::Relaxed), 3);
+
+    bmalloc::clear_post_collection_hook();
+    bmalloc::collect();
+    assert_eq!(RUNS.load(Ordering::Relaxed), 3);
+}
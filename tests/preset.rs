@@ -0,0 +1,40 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use bmalloc::GcPreset;
+
+fn assert_applied(preset: GcPreset) {
+    assert_eq!(bmalloc::free_space_divisor(), preset.free_space_divisor(), "{preset:?}");
+    assert_eq!(bmalloc::full_freq(), preset.full_freq(), "{preset:?}");
+    assert_eq!(bmalloc::time_limit(), preset.time_limit(), "{preset:?}");
+}
+
+// One test, in this order: incremental mode cannot be turned off again.
+#[test]
+fn presets_set_their_documented_values() {
+    common::setup();
+    assert!(!bmalloc::is_incremental());
+
+    bmalloc::apply_preset(GcPreset::Throughput);
+    assert_applied(GcPreset::Throughput);
+    assert_eq!(bmalloc::free_space_divisor(), 2);
+    assert_eq!(bmalloc::time_limit(), bmalloc::TIME_UNLIMITED);
+    assert!(!bmalloc::is_incremental());
+
+    bmalloc::apply_preset(GcPreset::Balanced);
+    assert_applied(GcPreset::Balanced);
+    assert_eq!((bmalloc::free_space_divisor(), bmalloc::full_freq()), (3, 19));
+    assert!(!bmalloc::is_incremental());
+
+    bmalloc::apply_preset(GcPreset::LowLatency);
+    assert_applied(GcPreset::LowLatency);
+    assert_eq!((bmalloc::free_space_divisor(), bmalloc::full_freq()), (4, 10));
+    assert_eq!(bmalloc::time_limit(), 5);
+    assert!(bmalloc::is_incremental());
+
+    // Back to stop-the-world settings, but collection stays incremental.
+    bmalloc::apply_preset(GcPreset::Throughput);
+    assert_applied(GcPreset::Throughput);
+    assert!(bmalloc::is_incremental());
+}
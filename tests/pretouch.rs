@@ -0,0 +1,65 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::process::Command;
+
+const CHILD_ENV: &str = "BMALLOC_PRETOUCH_CHILD";
+const CHUNKS: usize = 128;
+const CHUNK: usize = 256 << 10;
+
+fn minor_faults() -> i64 {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    assert_eq!(unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) }, 0);
+    usage.ru_minflt
+}
+
+// Runs in the child processes spawned below: allocate and write 32 MiB,
+// after pretouching twice that much if asked to, and report the minor page
+// faults taken by the burst.
+#[test]
+fn burst_child() {
+    let Some(pretouch) = std::env::var_os(CHILD_ENV) else {
+        return;
+    };
+    common::setup();
+    bmalloc::collect();
+    if pretouch == "1" {
+        assert!(bmalloc::pretouch_heap(CHUNKS * CHUNK * 2));
+    }
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    // Keeps the chunks alive, so that the burst never reuses one.
+    let holder = bmalloc::gc_slice_filled(0usize, CHUNKS).unwrap();
+    let before = minor_faults();
+    for slot in unsafe { &mut *holder.as_ptr() } {
+        let chunk = bmalloc::gc_malloc_atomic(CHUNK).unwrap().as_ptr();
+        for offset in (0..CHUNK).step_by(page) {
+            unsafe { chunk.add(offset).write_volatile(1) };
+        }
+        *slot = chunk as usize;
+    }
+    println!("faults {}", minor_faults() - before);
+    std::hint::black_box(holder);
+}
+
+fn burst_faults(pretouch: bool) -> i64 {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["burst_child", "--exact", "--nocapture", "--test-threads=1"])
+        .env(CHILD_ENV, if pretouch { "1" } else { "0" })
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}\n{}", String::from_utf8_lossy(&output.stderr));
+    let line =
+        stdout.lines().find_map(|l| Some(l.split_once("faults ")?.1)).expect("no fault count");
+    line.trim().parse().unwrap()
+}
+
+#[test]
+fn pretouched_heap_takes_fewer_page_faults() {
+    let cold = burst_faults(false);
+    let warm = burst_faults(true);
+    // 32 MiB of fresh pages fault thousands of times.
+    assert!(cold > 1000, "a cold burst took only {cold} faults");
+    assert!(warm * 2 < cold, "{warm} faults after pretouching, {cold} without");
+}
@@ -0,0 +1,36 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+const COUNT: usize = 2000;
+
+// Bytes in heap blocks handed out to objects. Allocations served from free
+// lists leave it unchanged; carving up fresh blocks, the slow path that
+// prewarming avoids, increases it.
+fn block_bytes_in_use() -> usize {
+    let usage = bmalloc::heap_usage();
+    usage.heap_size - usage.free_bytes
+}
+
+fn allocate(size: usize) {
+    for _ in 0..COUNT {
+        assert!(!unsafe { bmalloc::GC_malloc(size) }.is_null());
+        assert!(!unsafe { bmalloc::GC_malloc_atomic(size) }.is_null());
+    }
+}
+
+#[test]
+fn prewarmed_size_class_needs_no_fresh_blocks() {
+    common::setup();
+    bmalloc::set_automatic_collection(false);
+
+    bmalloc::prewarm(48, COUNT);
+    let warm = block_bytes_in_use();
+    allocate(48);
+    assert_eq!(block_bytes_in_use(), warm);
+
+    // A size class that was not prewarmed.
+    let cold = block_bytes_in_use();
+    allocate(112);
+    assert!(block_bytes_in_use() > cold);
+}
@@ -0,0 +1,62 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use bmalloc::{ProfileStats, ProfileStatsError};
+
+const WORDS: usize = std::mem::size_of::<ProfileStats>() / std::mem::size_of::<usize>();
+
+fn plausible() -> ProfileStats {
+    ProfileStats {
+        heapsize_full: 4 << 20,
+        free_bytes_full: 1 << 20,
+        unmapped_bytes: 256 << 10,
+        bytes_allocd_since_gc: 3 << 20,
+        allocd_bytes_before_gc: 96 << 20,
+        gc_no: 12,
+        bytes_reclaimed_since_gc: 512 << 10,
+        reclaimed_bytes_before_gc: 90 << 20,
+        ..ProfileStats::default()
+    }
+}
+
+// Read `stats` as a library whose struct has `shift` extra leading words
+// would fill it in.
+fn misread(stats: ProfileStats, shift: usize) -> ProfileStats {
+    let words: [usize; WORDS] = unsafe { std::mem::transmute(stats) };
+    let mut shifted = [0; WORDS];
+    shifted[shift..].copy_from_slice(&words[..WORDS - shift]);
+    unsafe { std::mem::transmute(shifted) }
+}
+
+#[test]
+fn live_snapshot_is_consistent() {
+    common::setup();
+    let stats = bmalloc::get_prof_stats().unwrap();
+    assert_eq!(stats.validate(), Ok(()));
+}
+
+#[test]
+fn corrupted_fields_are_flagged() {
+    assert_eq!(plausible().validate(), Ok(()));
+
+    let free_over_heap = ProfileStats { free_bytes_full: 8 << 20, ..plausible() };
+    assert_eq!(
+        free_over_heap.validate(),
+        Err(ProfileStatsError::Inconsistent("free_bytes_full <= heapsize_full"))
+    );
+    let unmapped_over_free = ProfileStats { unmapped_bytes: 2 << 20, ..plausible() };
+    assert_eq!(
+        unmapped_over_free.validate(),
+        Err(ProfileStatsError::Inconsistent("unmapped_bytes <= free_bytes_full"))
+    );
+    let unmapped_over_heap = ProfileStats { unmapped_bytes: 8 << 20, ..plausible() };
+    assert!(unmapped_over_heap.validate().is_err());
+}
+
+#[test]
+fn shifted_layout_is_flagged() {
+    let err = misread(plausible(), 1).validate().unwrap_err();
+    assert!(matches!(err, ProfileStatsError::Inconsistent(_)));
+    assert!(err.to_string().contains("version mismatch"), "{err}");
+}
@@ -0,0 +1,37 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+use std::time::Duration;
+
+use bmalloc::testing::{assert_retained, CollectionProbe};
+use bmalloc::GcProjected;
+
+#[repr(C)]
+struct Record {
+    id: u64,
+    payload: [u64; 8],
+}
+
+struct Projected(GcProjected<[u64; 8]>, CollectionProbe);
+
+unsafe impl Send for Projected {}
+
+#[test]
+fn projection_keeps_the_whole_object_alive() {
+    common::setup();
+    // The record's own handle only lives on the scrubbed thread.
+    let Projected(payload, probe) = common::isolated(|| {
+        let record = bmalloc::gc_try_new(Record { id: 7, payload: [42; 8] }).unwrap();
+        let probe = unsafe { CollectionProbe::for_ptr(record.as_ptr() as *const u8) };
+        Projected(unsafe { GcProjected::new(record, |r| &r.payload) }, probe)
+    });
+    assert_ne!(payload.as_ptr().cast::<u8>(), payload.base().unwrap());
+
+    assert_retained(&probe, Duration::from_millis(50));
+    black_box(&payload);
+    assert_eq!(*payload, [42; 8]);
+    let record = payload.base().unwrap().cast::<Record>();
+    assert_eq!(unsafe { record.as_ref().id }, 7);
+}
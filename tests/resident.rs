@@ -0,0 +1,43 @@
+#![cfg(target_os = "linux")]
+#![feature(allocator_api)]
+
+mod common;
+
+use bmalloc::GcAllocator;
+
+fn check_consistent(report: &bmalloc::ResidentReport) {
+    assert_eq!(report.gc_resident_bytes + report.gc_non_resident_bytes, report.gc_heap_bytes);
+    assert!(report.gc_resident_bytes <= report.gc_heap_bytes);
+    assert!(report.gc_unmapped_bytes <= report.gc_heap_bytes);
+    // The GC heap is part of the process, give or take a page per section
+    // touched between the two halves of the report.
+    assert!(report.gc_resident_bytes <= report.process_rss_bytes + 16 * report.page_size);
+    assert_eq!(
+        report.other_rss_bytes,
+        report.process_rss_bytes.saturating_sub(report.gc_resident_bytes)
+    );
+}
+
+#[test]
+fn unmapping_shows_up_as_non_resident() {
+    common::setup();
+    common::isolated(|| {
+        // Grow the heap by 32 MiB of touched pages, then drop all of it.
+        let mut live = Vec::new_in(GcAllocator);
+        for _ in 0..512 {
+            let obj = bmalloc::gc_malloc_atomic(64 * 1024).unwrap().as_ptr();
+            unsafe { obj.write_bytes(1, 64 * 1024) };
+            live.push(obj);
+        }
+    });
+    bmalloc::collect();
+    let before = bmalloc::resident_report();
+    check_consistent(&before);
+    assert!(before.gc_heap_bytes >= 32 << 20, "{before:?}");
+
+    bmalloc::collect_and_unmap();
+    let after = bmalloc::resident_report();
+    check_consistent(&after);
+    assert!(after.gc_unmapped_bytes > before.gc_unmapped_bytes, "{before:?} {after:?}");
+    assert!(after.gc_non_resident_bytes > before.gc_non_resident_bytes, "{before:?} {after:?}");
+}
@@ -0,0 +1,58 @@
+#![cfg(all(target_os = "linux", feature = "detect-resurrection"))]
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bmalloc::{gc_new_cyclic, GcAny, GcArena, GcCow, GcInterner, SplitAlloc};
+
+const KINDS: usize = 7;
+
+// Where each `Resurrect` leaves its own address when dropped. Statics are
+// scanned by the collector, so the object is reachable again afterwards.
+static STASH: [AtomicUsize; KINDS] = [const { AtomicUsize::new(0) }; KINDS];
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Resurrect(usize);
+
+impl Drop for Resurrect {
+    fn drop(&mut self) {
+        STASH[self.0].store(self as *const Self as usize, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn every_dropping_finalizer_watches_for_resurrection() {
+    common::setup();
+    common::isolated(|| {
+        GcAny::new(Resurrect(0)).unwrap();
+        let mut arena = GcArena::with_capacity(1).unwrap();
+        assert!(arena.alloc(Resurrect(1)).is_ok());
+        GcCow::Borrowed(&Resurrect(2)).to_gc_owned().unwrap();
+        GcCow::Borrowed(&[Resurrect(3)][..]).to_gc_owned().unwrap();
+        GcInterner::new().intern(Resurrect(4)).unwrap();
+        gc_new_cyclic(|_| Resurrect(5)).unwrap();
+        unsafe { SplitAlloc::new(Resurrect(6), 0u64) }.unwrap();
+        // The borrowed originals above left stack addresses behind.
+        for stash in &STASH {
+            stash.store(0, Ordering::Relaxed);
+        }
+    });
+    // Conservative marking may keep an object alive for a collection or two.
+    let finalized = common::isolated(|| {
+        for _ in 0..8 {
+            bmalloc::collect();
+            bmalloc::invoke_finalizers();
+            if STASH.iter().all(|s| s.load(Ordering::Relaxed) != 0) {
+                return true;
+            }
+        }
+        false
+    });
+    assert!(finalized, "not every finalizer ran");
+
+    // Every object is reachable from `STASH` now, so each is reported once.
+    let before = bmalloc::resurrection_count();
+    common::isolated(bmalloc::collect);
+    assert_eq!(bmalloc::resurrection_count() - before, KINDS);
+}
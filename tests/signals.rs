@@ -0,0 +1,52 @@
+#![cfg(all(target_os = "linux", not(feature = "interpose-threads")))]
+
+// Not `mod common`: the signals can only be chosen before the collector is
+// initialised. Everything runs in one test, in order, for the same reason.
+// Under interpose-threads, the harness starting its threads initialises it.
+
+use bmalloc::SignalConflict;
+
+#[test]
+fn signals_are_checked_before_init() {
+    bmalloc::reserve_signals(&[libc::SIGALRM]);
+
+    assert_eq!(
+        bmalloc::set_gc_signals(libc::SIGALRM, libc::SIGUSR2),
+        Err(SignalConflict::Reserved(libc::SIGALRM))
+    );
+    assert_eq!(
+        bmalloc::set_gc_signals(libc::SIGUSR1, libc::SIGALRM),
+        Err(SignalConflict::Reserved(libc::SIGALRM))
+    );
+    assert_eq!(
+        bmalloc::set_gc_signals(libc::SIGUSR1, libc::SIGUSR1),
+        Err(SignalConflict::SameSignal(libc::SIGUSR1))
+    );
+    assert_eq!(
+        bmalloc::set_gc_signals(libc::SIGKILL, libc::SIGUSR2),
+        Err(SignalConflict::Invalid(libc::SIGKILL))
+    );
+    assert_eq!(bmalloc::set_gc_signals(0, libc::SIGUSR2), Err(SignalConflict::Invalid(0)));
+
+    assert_eq!(bmalloc::set_gc_signals(libc::SIGUSR1, libc::SIGUSR2), Ok(()));
+    bmalloc::init();
+    assert_eq!(bmalloc::suspend_signal(), libc::SIGUSR1);
+    assert_eq!(bmalloc::restart_signal(), libc::SIGUSR2);
+
+    // The world still stops and restarts with the new signals.
+    assert!(bmalloc::ensure_thread_registered());
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            assert!(bmalloc::ensure_thread_registered());
+            let obj = bmalloc::gc_try_new(3u64).unwrap();
+            bmalloc::collect();
+            assert_eq!(unsafe { *obj.as_ptr() }, 3);
+        });
+        bmalloc::collect();
+    });
+
+    assert_eq!(
+        bmalloc::set_gc_signals(libc::SIGUSR1, libc::SIGUSR2),
+        Err(SignalConflict::AlreadyInitialized)
+    );
+}
@@ -0,0 +1,168 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use bmalloc::GcSnapshot;
+
+/// The subset of JSON that `to_json` may produce.
+#[derive(Debug, PartialEq)]
+enum Value {
+    Num(u64),
+    Str(String),
+    Obj(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn get(&self, key: &str) -> &Value {
+        match self {
+            Value::Obj(fields) => {
+                &fields.iter().find(|(k, _)| k == key).unwrap_or_else(|| panic!("no {key:?}")).1
+            }
+            _ => panic!("{self:?} is not an object"),
+        }
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        match self {
+            Value::Obj(fields) => fields.iter().map(|(k, _)| k.as_str()).collect(),
+            _ => panic!("{self:?} is not an object"),
+        }
+    }
+
+    fn num(&self) -> usize {
+        match self {
+            Value::Num(n) => *n as usize,
+            _ => panic!("{self:?} is not a number"),
+        }
+    }
+}
+
+struct Parser<'a>(&'a [u8]);
+
+impl Parser<'_> {
+    fn parse(text: &str) -> Value {
+        let mut p = Parser(text.as_bytes());
+        let value = p.value();
+        assert!(p.0.is_empty(), "trailing input {:?}", String::from_utf8_lossy(p.0));
+        value
+    }
+
+    fn eat(&mut self, byte: u8) {
+        assert_eq!(self.0.first(), Some(&byte), "at {:?}", String::from_utf8_lossy(self.0));
+        self.0 = &self.0[1..];
+    }
+
+    fn value(&mut self) -> Value {
+        match self.0.first() {
+            Some(b'{') => self.object(),
+            Some(b'"') => Value::Str(self.string()),
+            Some(b'0'..=b'9') => self.number(),
+            _ => panic!("unexpected {:?}", String::from_utf8_lossy(self.0)),
+        }
+    }
+
+    fn object(&mut self) -> Value {
+        self.eat(b'{');
+        let mut fields = Vec::new();
+        if self.0.first() == Some(&b'}') {
+            self.eat(b'}');
+            return Value::Obj(fields);
+        }
+        loop {
+            let key = self.string();
+            assert!(fields.iter().all(|(k, _)| *k != key), "duplicate key {key:?}");
+            self.eat(b':');
+            fields.push((key, self.value()));
+            match self.0.first() {
+                Some(b',') => self.eat(b','),
+                _ => break,
+            }
+        }
+        self.eat(b'}');
+        Value::Obj(fields)
+    }
+
+    fn string(&mut self) -> String {
+        self.eat(b'"');
+        let end = self.0.iter().position(|&b| b == b'"').expect("unterminated string");
+        let s = std::str::from_utf8(&self.0[..end]).unwrap().to_owned();
+        assert!(!s.contains('\\') && !s.chars().any(char::is_control), "{s:?} needs escaping");
+        self.0 = &self.0[end..];
+        self.eat(b'"');
+        s
+    }
+
+    fn number(&mut self) -> Value {
+        let end = self.0.iter().position(|b| !b.is_ascii_digit()).unwrap_or(self.0.len());
+        let digits = std::str::from_utf8(&self.0[..end]).unwrap();
+        assert!(digits == "0" || !digits.starts_with('0'), "leading zero in {digits}");
+        self.0 = &self.0[end..];
+        Value::Num(digits.parse().unwrap())
+    }
+}
+
+const STATS_KEYS: [&str; 11] = [
+    "heapsize_full",
+    "free_bytes_full",
+    "unmapped_bytes",
+    "bytes_allocd_since_gc",
+    "allocd_bytes_before_gc",
+    "non_gc_bytes",
+    "gc_no",
+    "markers_m1",
+    "bytes_reclaimed_since_gc",
+    "reclaimed_bytes_before_gc",
+    "expl_freed_bytes_since_gc",
+];
+const USAGE_KEYS: [&str; 5] =
+    ["heap_size", "free_bytes", "unmapped_bytes", "bytes_since_gc", "total_bytes"];
+
+#[test]
+fn snapshot_renders_as_json_with_the_documented_fields() {
+    common::setup();
+    bmalloc::collect();
+    let snapshot = GcSnapshot::take().unwrap();
+    let json = snapshot.to_json().to_string();
+    assert!(!json.contains('\n'), "{json}");
+    let value = Parser::parse(&json);
+
+    assert_eq!(value.keys(), ["version", "gc_version", "stats", "usage"]);
+    match value.get("version") {
+        Value::Str(v) => assert_eq!(v.split('.').count(), 3, "{v}"),
+        v => panic!("version {v:?}"),
+    }
+    let (major, minor, micro) = snapshot.gc_version;
+    assert_eq!(value.get("gc_version"), &Value::Str(format!("{major}.{minor}.{micro}")));
+
+    let stats = value.get("stats");
+    assert_eq!(stats.keys(), STATS_KEYS);
+    let s = &snapshot.stats;
+    assert_eq!(stats.get("heapsize_full").num(), s.heapsize_full);
+    assert_eq!(stats.get("free_bytes_full").num(), s.free_bytes_full);
+    assert_eq!(stats.get("gc_no").num(), s.gc_no);
+    assert_eq!(stats.get("non_gc_bytes").num(), s.non_gc_bytes);
+    assert!(s.gc_no > 0);
+
+    let usage = value.get("usage");
+    assert_eq!(usage.keys(), USAGE_KEYS);
+    let u = &snapshot.usage;
+    assert_eq!(usage.get("heap_size").num(), u.heap_size);
+    assert_eq!(usage.get("free_bytes").num(), u.free_bytes);
+    assert_eq!(usage.get("total_bytes").num(), u.total_bytes);
+    assert!(u.heap_size > 0);
+}
+
+#[test]
+fn extreme_values_stay_valid_json() {
+    common::setup();
+    let mut snapshot = GcSnapshot::take().unwrap();
+    snapshot.gc_version = (u8::MAX, 0, 0);
+    snapshot.stats.heapsize_full = usize::MAX;
+    snapshot.stats.gc_no = 0;
+    snapshot.usage.total_bytes = usize::MAX;
+    let value = Parser::parse(&snapshot.to_json().to_string());
+    assert_eq!(value.get("gc_version"), &Value::Str("255.0.0".into()));
+    assert_eq!(value.get("stats").get("heapsize_full").num(), usize::MAX);
+    assert_eq!(value.get("stats").get("gc_no").num(), 0);
+    assert_eq!(value.get("usage").get("total_bytes").num(), usize::MAX);
+}
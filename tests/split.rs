@@ -0,0 +1,64 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::ptr::NonNull;
+use std::time::Duration;
+
+use bmalloc::testing::{assert_collected, assert_retained, CollectionProbe};
+use bmalloc::SplitAlloc;
+
+const BODY: usize = 4 << 20;
+
+type Body = [usize; BODY / 8];
+
+struct Header {
+    target: NonNull<[u64; 4]>,
+}
+
+struct Made {
+    split: SplitAlloc<Header, Body>,
+    in_header: CollectionProbe,
+    in_body: CollectionProbe,
+    body: CollectionProbe,
+}
+
+// Only moved out of the thread that made it.
+unsafe impl Send for Made {}
+
+// A split object whose header and body each hold the only pointer to a GC
+// object of their own.
+fn make() -> Made {
+    common::isolated(|| {
+        let target = bmalloc::gc_try_new([1u64; 4]).unwrap();
+        let split = unsafe { SplitAlloc::<_, Body>::new_zeroed(Header { target }) }.unwrap();
+        let hidden = bmalloc::gc_try_new([2u64; 4]).unwrap();
+        let body = unsafe { &mut *split.body().as_ptr() };
+        body[0] = hidden.as_ptr() as usize;
+        body[BODY / 8 - 1] = usize::MAX;
+        unsafe {
+            Made {
+                split,
+                in_header: CollectionProbe::for_ptr(target.as_ptr() as *const u8),
+                in_body: CollectionProbe::for_ptr(hidden.as_ptr() as *const u8),
+                body: CollectionProbe::for_ptr(split.body().as_ptr() as *const u8),
+            }
+        }
+    })
+}
+
+#[test]
+fn header_is_scanned_and_body_is_not() {
+    common::setup();
+    let Made { split, in_header, in_body, body } = make();
+    assert_collected(in_body, Duration::from_secs(5));
+    assert_retained(&in_header, Duration::from_millis(50));
+    assert_retained(&body, Duration::from_millis(50));
+
+    let header = unsafe { split.header().as_ref() };
+    assert_eq!(unsafe { *header.target.as_ptr() }, [1; 4]);
+    let data = unsafe { split.body().as_ref() };
+    assert_eq!(data[BODY / 8 - 1], usize::MAX);
+    assert!(data[1..BODY / 8 - 1].iter().all(|&w| w == 0));
+    std::hint::black_box(split);
+}
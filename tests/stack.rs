@@ -0,0 +1,73 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+use std::mem::MaybeUninit;
+
+use bmalloc::testing::CollectionProbe;
+
+// Allocate an object and return a probe for it and its address, hidden from
+// the collector.
+#[inline(never)]
+fn allocate() -> (CollectionProbe, usize) {
+    let obj = bmalloc::gc_malloc_atomic(64).unwrap().as_ptr();
+    (unsafe { CollectionProbe::for_ptr(obj) }, !(obj as usize))
+}
+
+// Zero a region well below the caller, removing the copies of the address
+// that the allocator's own (deeper) frames left behind.
+#[inline(never)]
+fn scrub_deep() {
+    black_box(&mut [0usize; 16 * 1024]);
+}
+
+// Recurse `depth` frames, each holding a copy of the address. Every copy is
+// dead once this returns.
+#[inline(never)]
+fn leave_pointers(depth: usize, hidden: usize) {
+    let copy = !hidden;
+    if depth > 0 {
+        leave_pointers(depth - 1, hidden);
+    }
+    black_box(&copy);
+}
+
+// Collect from below a large, uninitialised frame, so that whatever earlier
+// calls left in that stack region is scanned as if it were live.
+#[inline(never)]
+fn collect_over_stale_region() {
+    let mut pad = MaybeUninit::<[usize; 64 * 1024]>::uninit();
+    black_box(&mut pad);
+    bmalloc::collect();
+}
+
+// Whether the object `run` is given the hidden address of is reclaimed by
+// a collection that scans the stack region `run` used.
+fn collected_after(run: impl FnOnce(usize) + Send) -> bool {
+    common::isolated(|| {
+        let (probe, hidden) = allocate();
+        scrub_deep();
+        run(hidden);
+        collect_over_stale_region();
+        probe.is_collected()
+    })
+}
+
+#[test]
+fn stale_pointers_retain_without_clearing() {
+    assert!(!collected_after(|hidden| leave_pointers(8, hidden)));
+}
+
+#[test]
+fn clear_stack_drops_stale_pointers_of_returned_frames() {
+    assert!(collected_after(|hidden| {
+        leave_pointers(8, hidden);
+        bmalloc::clear_stack();
+    }));
+}
+
+#[test]
+fn with_stack_cleared_scrubs_after_the_closure() {
+    assert!(collected_after(|hidden| bmalloc::with_stack_cleared(|| leave_pointers(8, hidden))));
+}
@@ -0,0 +1,47 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+
+const OBJECT: usize = 32;
+const OBJECTS: usize = 1 << 16;
+
+fn reclaimed_since_gc() -> usize {
+    bmalloc::get_prof_stats().unwrap().bytes_reclaimed_since_gc
+}
+
+#[test]
+fn sweeping_cost_falls_where_configured() {
+    common::setup();
+    bmalloc::set_automatic_collection(false);
+    // Keep every other object, so that no block is entirely free and
+    // sweeping is left to the allocator when it is lazy.
+    let mut kept = vec![std::ptr::null_mut::<u8>(); OBJECTS / 2];
+    for i in 0..OBJECTS {
+        let obj = bmalloc::gc_malloc_atomic(OBJECT).unwrap().as_ptr();
+        if i % 2 == 0 {
+            kept[i / 2] = obj;
+        }
+    }
+    let kept = bmalloc::gc_try_new_slice(&kept).unwrap();
+    bmalloc::collect();
+    let after_collect = reclaimed_since_gc();
+    for _ in 0..OBJECTS / 4 {
+        black_box(bmalloc::gc_malloc_atomic(OBJECT).unwrap());
+    }
+    let after_alloc = reclaimed_since_gc();
+    let garbage = OBJECTS / 2 * OBJECT;
+    if bmalloc::lazy_sweep() {
+        // Allocating after the collection paid for sweeping the free space
+        // it reused.
+        assert!(after_collect < garbage, "{after_collect} bytes swept by the collection");
+        assert!(after_alloc - after_collect >= OBJECTS / 4 * OBJECT);
+    } else {
+        // The collection swept everything; allocating afterwards swept nothing.
+        assert!(after_collect >= garbage, "{after_collect} bytes swept by the collection");
+        assert_eq!(after_alloc, after_collect);
+    }
+    black_box(kept);
+    bmalloc::set_automatic_collection(true);
+}
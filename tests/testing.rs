@@ -0,0 +1,121 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bmalloc::testing::{assert_collected, assert_retained, CollectionProbe};
+
+// `assert_all_finalizers_ran` counts finalizers process-wide.
+static FINALIZING: Mutex<()> = Mutex::new(());
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+struct Counted;
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn finalizers_counted_have_run() {
+    let _serial = FINALIZING.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    // Nothing runs finalizers unless asked to, and then one at a time.
+    unsafe {
+        bmalloc::GC_set_finalize_on_demand(1);
+        bmalloc::GC_set_interrupt_finalizers(1);
+    }
+    bmalloc::testing::assert_all_finalizers_ran(0);
+    common::isolated(|| {
+        for _ in 0..3 {
+            bmalloc::GcAny::new(Counted).unwrap();
+        }
+    });
+    bmalloc::testing::assert_all_finalizers_ran(3);
+    assert_eq!(DROPPED.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+#[should_panic(expected = "expected 3 finalizers to run, but 2 ran")]
+fn rooted_object_fails_assert_all_finalizers_ran() {
+    let _serial = FINALIZING.lock().unwrap_or_else(|e| e.into_inner());
+    common::setup();
+    unsafe {
+        bmalloc::GC_set_finalize_on_demand(1);
+        bmalloc::GC_set_interrupt_finalizers(1);
+    }
+    bmalloc::testing::assert_all_finalizers_ran(0);
+    let rooted = bmalloc::GcAny::new(String::from("rooted")).unwrap();
+    common::isolated(|| {
+        for _ in 0..2 {
+            bmalloc::GcAny::new(String::from("finalized")).unwrap();
+        }
+    });
+    bmalloc::testing::assert_all_finalizers_ran(3);
+    black_box(rooted);
+}
+
+struct Probe(CollectionProbe);
+
+unsafe impl Send for Probe {}
+
+fn probed() -> (CollectionProbe, *mut u8) {
+    let obj = bmalloc::gc_malloc_atomic(64).unwrap().as_ptr();
+    (unsafe { CollectionProbe::for_ptr(obj) }, obj)
+}
+
+#[inline(never)]
+fn probed_hidden() -> (CollectionProbe, usize) {
+    let (probe, obj) = probed();
+    (probe, !(obj as usize))
+}
+
+// Leave copies of `addr` in `depth` frames that are gone once this returns.
+#[inline(never)]
+fn leave_copies(addr: usize, depth: usize) {
+    let copies = [addr; 16];
+    if depth > 0 {
+        leave_copies(addr, depth - 1);
+    }
+    black_box(&copies);
+}
+
+#[test]
+fn dead_object_is_collected() {
+    common::setup();
+    let probe = common::isolated(|| Probe(probed().0));
+    assert_collected(probe.0, Duration::from_secs(5));
+}
+
+#[test]
+fn live_object_is_retained() {
+    common::setup();
+    let (probe, obj) = probed();
+    assert_retained(&probe, Duration::from_millis(100));
+    black_box(obj);
+}
+
+#[test]
+#[should_panic(expected = "still reachable")]
+fn live_object_fails_assert_collected() {
+    common::setup();
+    let (probe, obj) = probed();
+    black_box(&obj);
+    assert_collected(probe, Duration::from_millis(100));
+    black_box(obj);
+}
+
+// Copies left on the asserting thread's own stack by calls that have since
+// returned must not keep the object alive.
+#[test]
+fn stale_copies_on_the_asserting_thread_are_scrubbed() {
+    common::setup();
+    let (probe, hidden) = probed_hidden();
+    leave_copies(!hidden, 32);
+    assert_collected(probe, Duration::from_secs(5));
+}
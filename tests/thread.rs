@@ -0,0 +1,244 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use core::ptr::NonNull;
+
+// Under interpose-threads, std threads are registered by the collector's
+// own wrapper, and so cannot be flushed.
+#[test]
+#[cfg(not(feature = "interpose-threads"))]
+fn flush_keeps_thread_registered_and_objects_alive() {
+    common::setup();
+    // On the stack, so the objects stay reachable only if the thread is
+    // still scanned after flushing.
+    let objects: [NonNull<u64>; 64] =
+        core::array::from_fn(|i| bmalloc::gc_try_new(i as u64).unwrap());
+    assert!(bmalloc::flush_thread_cache());
+    assert!(bmalloc::thread_is_registered());
+    bmalloc::collect();
+    for (i, obj) in objects.iter().enumerate() {
+        assert_eq!(bmalloc::base_of(obj.as_ptr()), Some(obj.cast()));
+        assert_eq!(unsafe { *obj.as_ptr() }, i as u64);
+    }
+    let after = bmalloc::gc_try_new(7u64).unwrap();
+    assert_eq!(unsafe { *after.as_ptr() }, 7);
+}
+
+#[test]
+fn flush_refuses_threads_it_did_not_register() {
+    std::thread::spawn(|| {
+        assert!(!bmalloc::flush_thread_cache());
+        bmalloc::call_with_gc_active(|| {
+            assert!(bmalloc::thread_is_registered());
+            assert!(!bmalloc::flush_thread_cache());
+        });
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+#[cfg(not(feature = "interpose-threads"))]
+fn flushed_cache_is_freed_by_the_next_collection() {
+    // Sizes served from the thread-local free lists.
+    const CACHED_SIZES: std::ops::RangeInclusive<usize> = 16..=400;
+
+    common::setup();
+    let step = std::sync::Barrier::new(2);
+    let free_bytes = || {
+        bmalloc::collect();
+        bmalloc::heap_usage().free_bytes
+    };
+    let (cached, flushed) = std::thread::scope(|s| {
+        let worker = s.spawn(|| {
+            common::setup();
+            // Garbage, leaving a partly used block cached for each size.
+            bmalloc::with_stack_cleared(|| {
+                for size in CACHED_SIZES.step_by(16) {
+                    for _ in 0..1000 {
+                        std::hint::black_box(bmalloc::gc_malloc_atomic(size).unwrap());
+                    }
+                }
+            });
+            step.wait();
+            step.wait();
+            let flushed = bmalloc::flush_thread_cache();
+            step.wait();
+            // Parked until the collection below is done.
+            step.wait();
+            flushed
+        });
+        step.wait();
+        // The cached objects count as allocated, so their blocks stay in use.
+        let cached = free_bytes();
+        step.wait();
+        step.wait();
+        // Once returned, the collection frees the blocks they were cut from.
+        let flushed = free_bytes();
+        step.wait();
+        assert!(worker.join().unwrap());
+        (cached, flushed)
+    });
+    assert!(flushed >= cached + (16 << 12), "{cached} free bytes before flushing, {flushed} after");
+}
+
+#[test]
+fn blocking_io_reports_errno() {
+    // EBADF on Linux.
+    const EBADF: i32 = 9;
+
+    common::setup();
+    let mut buf = [0u8; 4];
+    assert_eq!(bmalloc::blocking_read(-1, &mut buf), Err(EBADF));
+    assert_eq!(bmalloc::blocking_write(-1, &buf), Err(EBADF));
+}
+
+// Under interpose-threads, std threads start out registered.
+#[test]
+#[cfg(not(feature = "interpose-threads"))]
+fn call_with_gc_active_unregisters_on_unwind() {
+    std::thread::spawn(|| {
+        let unwound = std::panic::catch_unwind(|| {
+            bmalloc::call_with_gc_active(|| {
+                assert!(bmalloc::thread_is_registered());
+                panic!("unwinding out of the callback");
+            })
+        });
+        assert!(unwound.is_err());
+        assert!(!bmalloc::thread_is_registered());
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn auto_registered_threads_unregister_at_exit() {
+    common::setup();
+    for _ in 0..4 {
+        std::thread::spawn(|| {
+            assert!(bmalloc::auto_register_current_thread());
+            assert!(bmalloc::thread_is_registered());
+            let obj = bmalloc::gc_try_new(1u64).unwrap();
+            assert_eq!(unsafe { *obj.as_ptr() }, 1);
+        })
+        .join()
+        .unwrap();
+    }
+    bmalloc::collect();
+}
+
+// Under interpose-threads, std threads start out registered.
+#[test]
+#[cfg(not(feature = "interpose-threads"))]
+fn std_threads_register_once_registration_is_allowed() {
+    common::setup();
+    // `init` already allowed it; allowing again is harmless.
+    bmalloc::allow_register_threads();
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                assert!(!bmalloc::thread_is_registered());
+                assert!(bmalloc::ensure_thread_registered());
+                assert!(bmalloc::thread_is_registered());
+                let obj = bmalloc::gc_try_new(7u64).unwrap();
+                bmalloc::collect();
+                assert_eq!(unsafe { *obj.as_ptr() }, 7);
+            });
+        }
+    });
+}
+
+#[test]
+fn pool_workers_register_once_across_tasks() {
+    common::setup();
+    std::thread::scope(|s| {
+        for worker in 0..4u64 {
+            s.spawn(move || {
+                for task in 0..100 {
+                    assert!(bmalloc::ensure_thread_registered());
+                    let obj = bmalloc::gc_try_new(worker * 100 + task).unwrap();
+                    assert_eq!(unsafe { *obj.as_ptr() }, worker * 100 + task);
+                }
+                // The collector already knows the thread: registering it
+                // again reports a duplicate.
+                let mut sb = bmalloc::StackBase { mem_base: std::ptr::null_mut() };
+                assert_eq!(unsafe { bmalloc::GC_get_stack_base(&mut sb) }, 0);
+                assert_eq!(unsafe { bmalloc::GC_register_my_thread(&sb) }, 1);
+            });
+        }
+    });
+    // The workers unregistered as they exited, or stopping them would fail.
+    bmalloc::collect();
+}
+
+#[test]
+fn collections_during_a_blocking_call_leave_its_objects_intact() {
+    common::setup();
+    let (entered_tx, entered_rx) = std::sync::mpsc::channel();
+    let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    let worker = std::thread::spawn(move || {
+        assert!(bmalloc::ensure_thread_registered());
+        // Above the blocking call, so still scanned while it runs.
+        let objects: [NonNull<u64>; 32] =
+            core::array::from_fn(|i| bmalloc::gc_try_new(i as u64).unwrap());
+        let made_inside = bmalloc::do_blocking(|| {
+            entered_tx.send(()).unwrap();
+            // Stands in for a long FFI call.
+            release_rx.recv().unwrap();
+            bmalloc::call_with_gc_active(|| {
+                let obj = bmalloc::gc_try_new(99u64).unwrap();
+                bmalloc::collect();
+                unsafe { *obj.as_ptr() }
+            })
+        });
+        assert_eq!(made_inside, 99);
+        for (i, obj) in objects.iter().enumerate() {
+            assert_eq!(unsafe { *obj.as_ptr() }, i as u64);
+        }
+    });
+    entered_rx.recv().unwrap();
+    for _ in 0..8 {
+        for _ in 0..64 {
+            bmalloc::gc_try_new([0u64; 16]).unwrap();
+        }
+        bmalloc::collect();
+    }
+    release_tx.send(()).unwrap();
+    worker.join().unwrap();
+}
+
+#[test]
+fn collection_does_not_wait_for_a_thread_blocked_in_read() {
+    common::setup();
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let [read_fd, write_fd] = fds;
+    let (entered_tx, entered_rx) = std::sync::mpsc::channel();
+    let reader = std::thread::spawn(move || {
+        assert!(bmalloc::ensure_thread_registered());
+        // GC memory, kept alive by this frame while the read blocks.
+        let buf = bmalloc::gc_malloc_atomic(4).unwrap();
+        let buf = unsafe { std::slice::from_raw_parts_mut(buf.as_ptr(), 4) };
+        entered_tx.send(()).unwrap();
+        let read = bmalloc::blocking_read(read_fd, buf);
+        (read, u32::from_ne_bytes(buf.try_into().unwrap()))
+    });
+    entered_rx.recv().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(10));
+
+    let start = std::time::Instant::now();
+    for _ in 0..8 {
+        bmalloc::collect();
+    }
+    let elapsed = start.elapsed();
+    assert!(elapsed < std::time::Duration::from_secs(1), "collections took {elapsed:?}");
+
+    let message = 0xfeed_beefu32.to_ne_bytes();
+    assert_eq!(unsafe { libc::write(write_fd, message.as_ptr().cast(), 4) }, 4);
+    assert_eq!(reader.join().unwrap(), (Ok(4), 0xfeed_beef));
+    unsafe {
+        libc::close(read_fd);
+        libc::close(write_fd);
+    }
+}
@@ -0,0 +1,108 @@
+#![cfg(target_os = "linux")]
+#![feature(allocator_api)]
+
+mod common;
+
+use std::hint::black_box;
+use std::mem::MaybeUninit;
+use std::ptr::NonNull;
+use std::time::Duration;
+
+use bmalloc::testing::{assert_collected, assert_retained, CollectionProbe};
+use bmalloc::AtomicGcAllocator;
+
+const LEN: usize = 16;
+
+#[derive(Clone, Copy)]
+struct Array(NonNull<[MaybeUninit<*mut u64>]>);
+
+unsafe impl Send for Array {}
+
+impl Array {
+    fn slots(&self) -> *mut MaybeUninit<*mut u64> {
+        self.0.as_ptr() as *mut MaybeUninit<*mut u64>
+    }
+}
+
+struct Probes(Vec<CollectionProbe>);
+
+unsafe impl Send for Probes {}
+
+#[test]
+fn partly_initialised_array_survives_collection() {
+    common::setup();
+    let array = Array(bmalloc::gc_uninit_array::<*mut u64>(LEN).unwrap());
+    let slots = array.slots();
+    assert_eq!(array.0.len(), LEN);
+    for i in 0..LEN {
+        assert!(unsafe { (*slots.add(i)).assume_init() }.is_null());
+    }
+    bmalloc::collect();
+
+    // Initialise every other slot from another thread, so the array is the
+    // only thing referring to the new objects.
+    let probes = common::isolated(move || {
+        let slots = array.slots();
+        let probes = (0..LEN)
+            .step_by(2)
+            .map(|i| {
+                let obj = bmalloc::gc_try_new(i as u64).unwrap().as_ptr();
+                unsafe { (*slots.add(i)).write(obj) };
+                unsafe { CollectionProbe::for_ptr(obj as *const u8) }
+            })
+            .collect();
+        Probes(probes)
+    });
+    for probe in &probes.0 {
+        assert_retained(probe, Duration::from_millis(50));
+    }
+    for i in 0..LEN {
+        let slot = unsafe { (*slots.add(i)).assume_init() };
+        if i % 2 == 0 {
+            assert_eq!(unsafe { *slot }, i as u64);
+        } else {
+            assert!(slot.is_null());
+        }
+    }
+    black_box(array);
+}
+
+const SCRATCH: usize = 4 << 20;
+
+struct Scratch(Box<[MaybeUninit<u8>], AtomicGcAllocator>, CollectionProbe);
+
+unsafe impl Send for Scratch {}
+
+#[test]
+fn uninit_scratch_buffer_keeps_its_contents() {
+    common::setup();
+    // Only the buffer refers to the probed object, from a word the
+    // collector must not scan.
+    let Scratch(mut scratch, unscanned) = common::isolated(|| {
+        let buffer = AtomicGcAllocator.allocate_uninit_atomic(SCRATCH).unwrap();
+        let mut scratch = unsafe { Box::from_raw_in(buffer.as_ptr(), AtomicGcAllocator) };
+        assert_eq!(scratch.len(), SCRATCH);
+        let obj = bmalloc::gc_try_new(0u64).unwrap().as_ptr();
+        let probe = unsafe { CollectionProbe::for_ptr(obj as *const u8) };
+        for (i, byte) in (obj as usize).to_ne_bytes().into_iter().enumerate() {
+            scratch[8 + i].write(byte);
+        }
+        Scratch(scratch, probe)
+    });
+    assert_collected(unscanned, Duration::from_secs(5));
+
+    for round in 0..2u8 {
+        for (i, byte) in scratch.iter_mut().enumerate() {
+            byte.write((i as u8).wrapping_add(round));
+        }
+        for _ in 0..4 {
+            bmalloc::collect();
+            for _ in 0..64 {
+                black_box(bmalloc::gc_malloc_atomic(64 << 10).unwrap());
+            }
+        }
+        for (i, byte) in scratch.iter().enumerate() {
+            assert_eq!(unsafe { byte.assume_init() }, (i as u8).wrapping_add(round), "{i}");
+        }
+    }
+}
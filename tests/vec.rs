@@ -0,0 +1,103 @@
+#![cfg(target_os = "linux")]
+#![feature(allocator_api)]
+
+mod common;
+
+use bmalloc::GcAllocator;
+
+// Elements of `T` that fit in the object behind `ptr`, less the byte the
+// collector reserves for one-past-the-end pointers.
+fn fits<T>(ptr: *const T) -> usize {
+    let padding = unsafe { bmalloc::GC_get_all_interior_pointers() } as usize;
+    (unsafe { bmalloc::GC_size(ptr as *const u8) } - padding) / std::mem::size_of::<T>()
+}
+
+fn check_rounded<T: Default>(requested: usize) {
+    let (ptr, capacity) = bmalloc::gc_vec_raw_parts::<T>(requested).unwrap();
+    assert!(capacity >= requested);
+    assert_eq!(capacity, fits(ptr.as_ptr()), "{requested} x {}", std::any::type_name::<T>());
+
+    // The vector fills all of it before reallocating.
+    let mut v = unsafe { Vec::from_raw_parts_in(ptr.as_ptr(), 0, capacity, GcAllocator) };
+    for _ in 0..capacity {
+        v.push(T::default());
+    }
+    assert_eq!(v.as_ptr(), ptr.as_ptr());
+    assert_eq!(v.capacity(), capacity);
+}
+
+#[test]
+fn capacity_fills_the_size_class() {
+    common::setup();
+    for requested in [1, 3, 10, 17, 100, 1000] {
+        check_rounded::<u8>(requested);
+        check_rounded::<u32>(requested);
+        check_rounded::<[u8; 24]>(requested);
+    }
+    // Size classes leave slack for at least one of these.
+    let (_, capacity) = bmalloc::gc_vec_raw_parts::<u32>(10).unwrap();
+    assert!(capacity > 10);
+}
+
+#[test]
+fn empty_and_zero_sized_requests() {
+    common::setup();
+    assert_eq!(bmalloc::gc_vec_raw_parts::<u64>(0).unwrap().1, 0);
+    assert_eq!(bmalloc::gc_vec_raw_parts::<()>(5).unwrap().1, usize::MAX);
+}
+
+#[test]
+fn filled_slice_uses_the_whole_object() {
+    common::setup();
+    for min_len in [1, 3, 10, 17, 100, 1000] {
+        let slice = bmalloc::gc_slice_filled(7u32, min_len).unwrap();
+        assert!(slice.len() >= min_len);
+        assert_eq!(slice.len(), fits(slice.as_ptr() as *const u32), "{min_len}");
+
+        let boxed = unsafe { Box::from_raw_in(slice.as_ptr(), GcAllocator) };
+        assert!(boxed.iter().all(|&x| x == 7));
+    }
+
+    let boxed =
+        unsafe { Box::from_raw_in(bmalloc::gc_slice_filled((), 5).unwrap().as_ptr(), GcAllocator) };
+    assert_eq!(boxed.len(), 5);
+    assert_eq!(bmalloc::gc_slice_filled(0u64, 0).unwrap().len(), 0);
+}
+
+#[test]
+fn shrink_to_keeps_the_object() {
+    common::setup();
+    let mut v: Vec<u64, GcAllocator> = Vec::with_capacity_in(1000, GcAllocator);
+    v.extend(0..10);
+    let ptr = v.as_ptr();
+    let object_size = unsafe { bmalloc::GC_size(ptr as *const u8) };
+
+    v.shrink_to(20);
+    assert_eq!(v.capacity(), 20);
+    v.shrink_to_fit();
+    assert_eq!(v.capacity(), 10);
+    // A copy would have needed a new object.
+    assert_eq!(v.as_ptr(), ptr);
+    assert_eq!(unsafe { bmalloc::GC_size(ptr as *const u8) }, object_size);
+    assert!(v.iter().copied().eq(0..10));
+
+    // Growing again reallocates as usual.
+    v.extend(10..2000);
+    assert!(v.iter().copied().eq(0..2000));
+}
+
+#[test]
+fn shrink_to_a_stricter_alignment_copies() {
+    use std::alloc::{Allocator, Layout};
+
+    common::setup();
+    let old = Layout::from_size_align(256, 8).unwrap();
+    let new = Layout::from_size_align(64, 64).unwrap();
+    let ptr = GcAllocator.allocate(old).unwrap().cast::<u8>();
+    unsafe { ptr.as_ptr().copy_from((0..64).collect::<Vec<u8>>().as_ptr(), 64) };
+    let shrunk = unsafe { GcAllocator.shrink(ptr, old, new) }.unwrap();
+    assert_eq!(shrunk.len(), 64);
+    assert_ne!(shrunk.cast::<u8>(), ptr);
+    assert_eq!(shrunk.cast::<u8>().as_ptr() as usize % 64, 0);
+    assert!(unsafe { shrunk.as_ref() }.iter().copied().eq(0..64));
+}
@@ -0,0 +1,103 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::ffi::{c_char, CStr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// The warning routing is process-wide.
+static SERIAL: Mutex<()> = Mutex::new(());
+// Warnings that reached the C warn proc installed before the crate's.
+static FORWARDED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn c_warn_proc(_msg: *const c_char, _arg: usize) {
+    FORWARDED.fetch_add(1, Ordering::Relaxed);
+}
+
+// Raise a warning the way the collector does, through the current warn proc.
+fn warn(msg: &CStr, arg: usize) {
+    unsafe { bmalloc::GC_get_warn_proc()(msg.as_ptr(), arg) };
+}
+
+fn setup() -> std::sync::MutexGuard<'static, ()> {
+    common::setup();
+    let serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    static C_PROC: std::sync::Once = std::sync::Once::new();
+    C_PROC.call_once(|| unsafe { bmalloc::GC_set_warn_proc(c_warn_proc) });
+    serial
+}
+
+fn recent() -> Vec<String> {
+    let mut out = Vec::new();
+    bmalloc::recent_warnings(|w| out.push(w.to_owned()));
+    out
+}
+
+#[test]
+fn buffer_keeps_the_most_recent_in_order() {
+    let _serial = setup();
+    bmalloc::set_warning_buffer_capacity(4);
+    for i in 0..10 {
+        warn(c"GC Warning: number %ld\n", i);
+    }
+    assert_eq!(
+        recent(),
+        [
+            "GC Warning: number 6",
+            "GC Warning: number 7",
+            "GC Warning: number 8",
+            "GC Warning: number 9"
+        ]
+    );
+    bmalloc::set_warning_buffer_capacity(0);
+}
+
+#[test]
+fn unhandled_warnings_reach_the_previous_proc() {
+    let _serial = setup();
+    bmalloc::set_warning_buffer_capacity(1);
+    bmalloc::set_warning_buffer_capacity(0);
+    let before = FORWARDED.load(Ordering::Relaxed);
+    warn(c"GC Warning: nobody listens\n", 0);
+    assert_eq!(FORWARDED.load(Ordering::Relaxed), before + 1);
+    assert!(recent().is_empty());
+}
+
+#[test]
+fn buffering_outlives_an_enclosing_handler_scope() {
+    let _serial = setup();
+    bmalloc::with_warn_handler(|_| {}, || bmalloc::set_warning_buffer_capacity(2));
+    let before = FORWARDED.load(Ordering::Relaxed);
+    warn(c"GC Warning: after the scope\n", 0);
+    assert_eq!(recent(), ["GC Warning: after the scope"]);
+    assert_eq!(FORWARDED.load(Ordering::Relaxed), before);
+    bmalloc::set_warning_buffer_capacity(0);
+}
+
+static OUTER: AtomicUsize = AtomicUsize::new(0);
+static INNER: AtomicUsize = AtomicUsize::new(0);
+
+#[test]
+fn nested_handlers_route_to_the_innermost() {
+    let _serial = setup();
+    let outer = |_: &str| {
+        OUTER.fetch_add(1, Ordering::Relaxed);
+    };
+    let inner = |_: &str| {
+        INNER.fetch_add(1, Ordering::Relaxed);
+    };
+    let forwarded = FORWARDED.load(Ordering::Relaxed);
+    bmalloc::with_warn_handler(outer, || {
+        warn(c"GC Warning: outer\n", 0);
+        bmalloc::with_warn_handler(inner, || warn(c"GC Warning: inner\n", 0));
+        assert_eq!((OUTER.load(Ordering::Relaxed), INNER.load(Ordering::Relaxed)), (1, 1));
+        warn(c"GC Warning: outer again\n", 0);
+    });
+    assert_eq!((OUTER.load(Ordering::Relaxed), INNER.load(Ordering::Relaxed)), (2, 1));
+    assert_eq!(FORWARDED.load(Ordering::Relaxed), forwarded);
+
+    warn(c"GC Warning: after both scopes\n", 0);
+    assert_eq!(FORWARDED.load(Ordering::Relaxed), forwarded + 1);
+    assert_eq!(OUTER.load(Ordering::Relaxed), 2);
+}
@@ -0,0 +1,87 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::ffi::CStr;
+use std::sync::Mutex;
+
+use bmalloc::GcWarning;
+
+#[test]
+fn known_prefixes_are_classified() {
+    for msg in [
+        "GC Warning: Repeated allocation of very large block (appr. size 65536 KiB):\n\
+         \tMay lead to memory leak and poor performance",
+        "Repeated allocation of very large block (appr. size 8 KiB):",
+    ] {
+        assert_eq!(GcWarning::classify(msg), GcWarning::RepeatedLargeBlock, "{msg:?}");
+    }
+    for msg in [
+        "GC Warning: Out of Memory! Heap size: 512 MiB. Returning NULL!",
+        "GC Warning: Out of Memory!  Trying to continue...",
+        "GC Warning: Failed to expand heap by 4096 KiB",
+        "Failed to expand heap by 4096 KiB",
+    ] {
+        assert_eq!(GcWarning::classify(msg), GcWarning::OutOfMemory, "{msg:?}");
+    }
+    assert_eq!(
+        GcWarning::classify("GC Warning: Finalization cycle involving 0x1234"),
+        GcWarning::Other("Finalization cycle involving 0x1234")
+    );
+    // Only a prefix counts.
+    assert_eq!(
+        GcWarning::classify("GC Warning: Not Out of Memory!"),
+        GcWarning::Other("Not Out of Memory!")
+    );
+    assert_eq!(GcWarning::classify(""), GcWarning::Other(""));
+}
+
+// What the handler received, with `Other` reduced to its text.
+static RECEIVED: Mutex<Vec<(Option<&str>, String)>> = Mutex::new(Vec::new());
+
+fn record(warning: GcWarning<'_>, msg: &str) {
+    let kind = match warning {
+        GcWarning::RepeatedLargeBlock => Some("repeated"),
+        GcWarning::OutOfMemory => Some("oom"),
+        GcWarning::Other(text) => {
+            assert!(msg.ends_with(text), "{text:?} is not the end of {msg:?}");
+            None
+        }
+    };
+    RECEIVED.lock().unwrap_or_else(|e| e.into_inner()).push((kind, msg.to_owned()));
+}
+
+// Raise a warning the way the collector does, through the current warn proc.
+fn warn(msg: &CStr, arg: usize) {
+    unsafe { bmalloc::GC_get_warn_proc()(msg.as_ptr(), arg) };
+}
+
+// The handler stays installed for the rest of the process, hence the file of
+// its own.
+#[test]
+fn handler_receives_the_kind_and_the_formatted_text() {
+    common::setup();
+    bmalloc::set_classified_warn_handler(record);
+    // The collector's own format strings.
+    warn(
+        c"GC Warning: Repeated allocation of very large block (appr. size %lu KiB):\n\
+          \tMay lead to memory leak and poor performance\n",
+        1 << 16,
+    );
+    warn(c"GC Warning: Out of Memory! Heap size: %lu MiB. Returning NULL!\n", 512);
+    warn(c"GC Warning: Failed to expand heap by %lu KiB\n", 4096);
+    warn(c"GC Warning: Finalization cycle involving %p\n", 0x1234);
+
+    let received = RECEIVED.lock().unwrap_or_else(|e| e.into_inner());
+    let kinds: Vec<_> = received.iter().map(|(kind, _)| *kind).collect();
+    assert_eq!(kinds, [Some("repeated"), Some("oom"), Some("oom"), None]);
+    assert_eq!(
+        received[0].1,
+        "GC Warning: Repeated allocation of very large block (appr. size 65536 KiB):\n\
+         \tMay lead to memory leak and poor performance"
+    );
+    assert_eq!(received[1].1, "GC Warning: Out of Memory! Heap size: 512 MiB. Returning NULL!");
+    assert_eq!(received[2].1, "GC Warning: Failed to expand heap by 4096 KiB");
+    assert!(received[3].1.starts_with("GC Warning: Finalization cycle involving 0x"));
+    assert!(received[3].1.ends_with("1234"), "{}", received[3].1);
+}
@@ -0,0 +1,79 @@
+#![cfg(target_os = "linux")]
+#![feature(allocator_api)]
+
+mod common;
+
+use std::collections::HashSet;
+
+use bmalloc::{gc_new_cyclic, GcAllocator, GcWeak};
+
+const OBJECTS: usize = 512;
+
+struct Node {
+    id: usize,
+    this: GcWeak<Node>,
+}
+
+// Weak references, kept in scanned memory, and the addresses of their
+// objects, hidden from the collector.
+struct Made(Vec<GcWeak<Node>, GcAllocator>, Vec<usize>);
+
+// Only moved out of the thread that made them.
+unsafe impl Send for Made {}
+
+// Make `OBJECTS` nodes numbered from `first`.
+fn make(first: usize) -> Made {
+    let mut weaks = Vec::with_capacity_in(OBJECTS, GcAllocator);
+    let mut hidden = Vec::with_capacity(OBJECTS);
+    for id in first..first + OBJECTS {
+        let node = gc_new_cyclic(|this| Node { id, this }).unwrap();
+        weaks.push(unsafe { node.as_ref() }.this);
+        hidden.push(!(node.as_ptr() as usize));
+    }
+    Made(weaks, hidden)
+}
+
+#[test]
+fn upgrade_sees_the_object_it_was_made_for() {
+    common::setup();
+    let node = gc_new_cyclic(|this| Node { id: 7, this }).unwrap();
+    let node = unsafe { node.as_ref() };
+    assert_eq!(node.this.upgrade().map(|n| unsafe { n.as_ref() }.id), Some(7));
+}
+
+#[test]
+fn self_reference_survives_collection() {
+    common::setup();
+    let node = gc_new_cyclic(|this| Node { id: 9, this }).unwrap();
+    for _ in 0..3 {
+        bmalloc::collect();
+    }
+    let this = unsafe { node.as_ref() }.this.upgrade().unwrap();
+    assert_eq!(this.as_ptr(), node.as_ptr());
+    assert_eq!(unsafe { this.as_ref() }.id, 9);
+}
+
+#[test]
+fn no_stale_upgrade_after_address_reuse() {
+    common::setup();
+    let mut reused = 0;
+    for round in 0..8 {
+        let Made(stale, old) = common::isolated(|| make(round * 2 * OBJECTS));
+        // Twice: with poison-on-reclaim, garbage is only reclaimed by the second.
+        common::isolated(bmalloc::collect);
+        common::isolated(bmalloc::collect);
+        // Keep the new nodes reachable while the old references are checked.
+        let Made(fresh, new) = make(round * 2 * OBJECTS + OBJECTS);
+        let old: HashSet<_> = old.into_iter().collect();
+        reused += new.iter().filter(|addr| old.contains(addr)).count();
+        // An object kept alive by a stale copy of its address may still
+        // upgrade, but only to itself.
+        for (i, weak) in stale.iter().enumerate() {
+            if let Some(node) = weak.upgrade() {
+                assert_eq!(unsafe { node.as_ref() }.id, round * 2 * OBJECTS + i);
+            }
+        }
+        drop(fresh);
+    }
+    assert!(reused > 0, "no address was reused");
+}
@@ -0,0 +1,46 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+static PROGRESS: AtomicUsize = AtomicUsize::new(0);
+static DONE: AtomicBool = AtomicBool::new(false);
+
+fn wait_for_progress() {
+    let start = PROGRESS.load(Ordering::SeqCst);
+    while PROGRESS.load(Ordering::SeqCst) == start {
+        std::thread::yield_now();
+    }
+}
+
+#[test]
+fn stopped_world_is_consistent_and_resumes() {
+    common::setup();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            common::setup();
+            while !DONE.load(Ordering::SeqCst) {
+                bmalloc::gc_malloc_atomic(256).unwrap();
+                PROGRESS.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        wait_for_progress();
+
+        let guard = bmalloc::stop_the_world();
+        let seen = PROGRESS.load(Ordering::SeqCst);
+        let usage = guard.heap_usage();
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(PROGRESS.load(Ordering::SeqCst), seen, "a thread ran while stopped");
+        let again = guard.heap_usage();
+        drop(guard);
+
+        assert_eq!(usage.total_bytes, again.total_bytes);
+        assert_eq!(usage.free_bytes, again.free_bytes);
+        assert!(usage.free_bytes <= usage.heap_size);
+
+        wait_for_progress();
+        DONE.store(true, Ordering::SeqCst);
+    });
+}
@@ -0,0 +1,53 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use bmalloc::GcWriter;
+
+// Forwards `io::Write` as the `GcWriter` docs suggest.
+struct IoWriter(GcWriter);
+
+impl Write for IoWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_all(buf).map_err(|_| io::ErrorKind::OutOfMemory)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn chunked_writes_survive_collections() {
+    common::setup();
+    let mut expected = Vec::new();
+    let mut writer = IoWriter(GcWriter::new());
+    for i in 0..2000u32 {
+        let chunk = vec![i as u8; i as usize % 97];
+        writer.write_all(&chunk).unwrap();
+        expected.extend_from_slice(&chunk);
+        if i % 100 == 0 {
+            bmalloc::collect();
+        }
+    }
+    write!(writer.0, "end {}", 2000).unwrap();
+    expected.extend_from_slice(b"end 2000");
+    assert_eq!(writer.0.len(), expected.len());
+
+    let bytes = writer.0.into_gc_bytes();
+    bmalloc::collect();
+    assert_eq!(unsafe { bytes.as_ref() }, &expected[..]);
+}
+
+#[test]
+fn empty_writer() {
+    common::setup();
+    let writer = GcWriter::with_capacity(16).unwrap();
+    assert!(writer.is_empty());
+    assert_eq!(writer.as_bytes(), b"");
+    assert_eq!(GcWriter::new().into_gc_bytes().len(), 0);
+}